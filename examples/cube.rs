@@ -5,14 +5,12 @@ extern crate camera_controllers;
 extern crate gfx;
 extern crate gfx_device_gl;
 extern crate sdl2_window;
-extern crate piston_meta;
-extern crate piston_meta_search;
 extern crate shader_version;
+extern crate opengex;
 
 use shader_version::Shaders;
 use shader_version::glsl::GLSL;
 use std::fs::File;
-use std::io::Read;
 use sdl2_window::Sdl2Window;
 use piston_window::*;
 use camera_controllers::{
@@ -22,8 +20,7 @@ use camera_controllers::{
     model_view_projection
 };
 use gfx::traits::*;
-use piston_meta::*;
-use piston_meta_search::*;
+use opengex::structure::*;
 
 //----------------------------------------
 // Cube associated data
@@ -64,43 +61,25 @@ fn main() {
 
     let ref mut factory = events.factory.borrow().clone();
 
-    // Read OpenGEX meta rules.
-    let mut file_h = File::open("examples/assets/opengex-syntax.txt").unwrap();
-    let mut source = String::new();
-    file_h.read_to_string(&mut source).unwrap();
-    let rules = stderr_unwrap(&source, syntax(&source));
-
     // Read cube.ogex.
-    let mut file_h = File::open("examples/assets/cube.ogex").unwrap();
-    let mut source = String::new();
-    file_h.read_to_string(&mut source).unwrap();
-    let mut data = vec![];
-    stderr_unwrap(&source, parse(&rules, &source, &mut data));
-
-    let s = Search::new(&data);
-    let vertex_data: Vec<Vertex> = stderr_unwrap(&source, s.for_bool("position", true,
-        |ref mut s| {
-            let mut vs = Vec::with_capacity(24);
-            for _ in 0 .. 24 {
-                vs.push(Vertex::new([
-                    try!(s.f64("x")) as f32,
-                    try!(s.f64("y")) as f32,
-                    try!(s.f64("z")) as f32
-                ]));
-            }
-            Ok(vs)
-        }));
-
-    let index_data: Vec<u8> = stderr_unwrap(&source, s.for_node("IndexArray",
-        |ref mut s| {
-            let mut is = Vec::with_capacity(36);
-            for _ in 0 .. 12 {
-                is.push(try!(s.f64("a")) as u8);
-                is.push(try!(s.f64("b")) as u8);
-                is.push(try!(s.f64("c")) as u8);
-            }
-            Ok(is)
-        }));
+    let file_h = File::open("examples/assets/cube.ogex").unwrap();
+    let scene = match Scene::from_reader(file_h) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return;
+        }
+    };
+    let mesh = &scene.geometry_objects[0].meshes[0];
+
+    let positions = mesh.vertex_arrays.iter().find(|a| a.attrib == "position").unwrap();
+    let vertex_data: Vec<Vertex> = positions.data.to_f32().chunks(positions.components)
+        .map(|p| Vertex::new([p[0], p[1], p[2]]))
+        .collect();
+
+    let index_data: Vec<u32> = mesh.index_arrays[0].data.to_u64().iter()
+        .map(|&i| i as u32)
+        .collect();
 
     let (vbuf, slice) = factory.create_vertex_buffer_indexed(&vertex_data,
         &index_data[..]);
@@ -0,0 +1,374 @@
+//! Sampling of animation tracks.
+//!
+//! A track is evaluated in two steps. The time curve is searched for the key interval containing
+//! the sampled time, and the curve parameter at which it is reached. For a Bezier time curve, this
+//! means inverting the curve numerically. The value curve is then evaluated at that parameter, for
+//! every component of the value.
+//!
+//! Sampling an animation gives a `Pose`, holding the sampled value of every transformation and
+//! morph weight targeted by its tracks. As those are shared with the nodes of a scene, a pose can
+//! be used to evaluate the animated scene without changing it.
+//!
+//! The animations of a scene are grouped into clips, like "walk" and "run", through the `clip`
+//! property of every animation. `Scene::clips` gathers the animations of each clip, so that a
+//! clip can be played as a whole.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use structure::*;
+use transform::{ self, multiply, Matrix, IDENTITY };
+
+impl Transformation {
+    /// Returns the values of the transformation, in the order the OpenGEX structure holds them.
+    /// These are the components of the values of a track targeting the transformation.
+    pub fn components(&self) -> Vec<f32> {
+        match *self {
+            Transformation::Transform(Transform(ref m)) => m.to_vec(),
+            Transformation::Translation(ref t) => match *t {
+                Translation::X(x) | Translation::Y(x) | Translation::Z(x) => vec![x],
+                Translation::Xyz(x, y, z) => vec![x, y, z]
+            },
+            Transformation::Rotation(ref r) => match *r {
+                Rotation::X(a) | Rotation::Y(a) | Rotation::Z(a) => vec![a],
+                Rotation::Axis(a, x, y, z) => vec![a, x, y, z],
+                Rotation::Quaternion(x, y, z, w) => vec![x, y, z, w]
+            },
+            Transformation::Scale(ref s) => match *s {
+                Scale::X(x) | Scale::Y(x) | Scale::Z(x) => vec![x],
+                Scale::Xyz(x, y, z) => vec![x, y, z]
+            }
+        }
+    }
+
+    /// Returns a transformation of the same kind with the given values, or `None` if their number
+    /// does not match the kind.
+    pub fn with_components(&self, v: &[f32]) -> Option<Transformation> {
+        if v.len() != self.components().len() {
+            return None;
+        }
+        Some(match *self {
+            Transformation::Transform(_) => {
+                let mut m = [0.0; 16];
+                m.copy_from_slice(v);
+                Transformation::Transform(Transform(m))
+            }
+            Transformation::Translation(ref t) => Transformation::Translation(match *t {
+                Translation::X(_) => Translation::X(v[0]),
+                Translation::Y(_) => Translation::Y(v[0]),
+                Translation::Z(_) => Translation::Z(v[0]),
+                Translation::Xyz(..) => Translation::Xyz(v[0], v[1], v[2])
+            }),
+            Transformation::Rotation(ref r) => Transformation::Rotation(match *r {
+                Rotation::X(_) => Rotation::X(v[0]),
+                Rotation::Y(_) => Rotation::Y(v[0]),
+                Rotation::Z(_) => Rotation::Z(v[0]),
+                Rotation::Axis(..) => Rotation::Axis(v[0], v[1], v[2], v[3]),
+                Rotation::Quaternion(..) => Rotation::Quaternion(v[0], v[1], v[2], v[3])
+            }),
+            Transformation::Scale(ref s) => Transformation::Scale(match *s {
+                Scale::X(_) => Scale::X(v[0]),
+                Scale::Y(_) => Scale::Y(v[0]),
+                Scale::Z(_) => Scale::Z(v[0]),
+                Scale::Xyz(..) => Scale::Xyz(v[0], v[1], v[2])
+            })
+        })
+    }
+}
+
+/// Returns a transformation with its quaternion scaled to a length of 1, if it is a quaternion
+/// rotation, since interpolating the components of quaternions shortens them.
+fn normalized(t: Transformation) -> Transformation {
+    match t {
+        Transformation::Rotation(Rotation::Quaternion(x, y, z, w)) => {
+            let len = (x * x + y * y + z * z + w * w).sqrt();
+            if len > 0.0 {
+                return Transformation::Rotation(
+                    Rotation::Quaternion(x / len, y / len, z / len, w / len));
+            }
+            t
+        }
+        t => t
+    }
+}
+
+/// Returns the time range covering a list of time ranges.
+fn union<I: Iterator<Item = (f32, f32)>>(ranges: I) -> Option<(f32, f32)> {
+    ranges.fold(None, |union, (begin, end)| Some(match union {
+        Some((b, e)) => (begin.min(b), end.max(e)),
+        None => (begin, end)
+    }))
+}
+
+/// Evaluates a cubic Bezier curve.
+fn bezier(p0: f32, p1: f32, p2: f32, p3: f32, s: f32) -> f32 {
+    let r = 1.0 - s;
+    r * r * r * p0 + 3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s * p3
+}
+
+/// Evaluates a cubic Hermite curve.
+fn hermite(p0: f32, m0: f32, p1: f32, m1: f32, s: f32) -> f32 {
+    let (s2, s3) = (s * s, s * s * s);
+    (2.0 * s3 - 3.0 * s2 + 1.0) * p0 + (s3 - 2.0 * s2 + s) * m0 + (-2.0 * s3 + 3.0 * s2) * p1 +
+        (s3 - s2) * m1
+}
+
+impl Time {
+    /// Returns the time of a key.
+    pub fn key(&self, i: usize) -> f32 {
+        match *self {
+            Time::Linear(ref v) => v[i],
+            Time::Bezier(ref v) => v[i].0
+        }
+    }
+
+    /// Returns the index of the key starting the interval containing `time`, and the curve
+    /// parameter between 0 and 1 at which `time` is reached in that interval. Times before the
+    /// first key or after the last one are clamped.
+    ///
+    /// For a Bezier curve, the parameter is found by bisection, which assumes the curve is
+    /// increasing, as the OpenGEX specification requires.
+    pub fn locate(&self, time: f32) -> (usize, f32) {
+        let len = self.len();
+        if len < 2 || time <= self.key(0) {
+            return (0, 0.0);
+        }
+        if time >= self.key(len - 1) {
+            return (len - 2, 1.0);
+        }
+        let i = (0..len - 1).find(|&i| time < self.key(i + 1)).unwrap();
+        match *self {
+            Time::Linear(ref v) => (i, (time - v[i]) / (v[i + 1] - v[i])),
+            Time::Bezier(ref v) => {
+                let (p0, p1, p2, p3) = (v[i].0, v[i].2, v[i + 1].1, v[i + 1].0);
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let s = (low + high) / 2.0;
+                    if bezier(p0, p1, p2, p3, s) < time {
+                        low = s;
+                    } else {
+                        high = s;
+                    }
+                }
+                (i, (low + high) / 2.0)
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Returns the number of values, which is the number of keys times the number of components.
+    pub fn len(&self) -> usize {
+        match *self {
+            Value::Constant(ref v) | Value::Linear(ref v) => v.len(),
+            Value::Bezier(ref v) => v.len(),
+            Value::Tcb(ref v) => v.len()
+        }
+    }
+
+    /// Whether the value curve contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Evaluates component `j` of a value with `n` components, in the interval starting at key `i`
+    /// at curve parameter `s`.
+    fn evaluate(&self, n: usize, i: usize, j: usize, s: f32) -> f32 {
+        let keys = self.len() / n;
+        let k = |i: usize| i.min(keys - 1) * n + j;
+        match *self {
+            Value::Constant(ref v) => if s >= 1.0 { v[k(i + 1)] } else { v[k(i)] },
+            Value::Linear(ref v) => v[k(i)] + (v[k(i + 1)] - v[k(i)]) * s,
+            Value::Bezier(ref v) => bezier(v[k(i)].0, v[k(i)].2, v[k(i + 1)].1, v[k(i + 1)].0, s),
+            Value::Tcb(ref v) => {
+                // Kochanek-Bartels tangents, with the first and last keys repeated past the ends.
+                let p = |i: isize| v[k(i.max(0) as usize)].0;
+                let (i, next) = (i as isize, i as isize + 1);
+                let (_, tension, bias, continuity) = v[k(i as usize)];
+                let out = (1.0 - tension) / 2.0 *
+                    ((1.0 + continuity) * (1.0 + bias) * (p(i) - p(i - 1)) +
+                     (1.0 - continuity) * (1.0 - bias) * (p(next) - p(i)));
+                let (_, tension, bias, continuity) = v[k(next as usize)];
+                let into = (1.0 - tension) / 2.0 *
+                    ((1.0 - continuity) * (1.0 + bias) * (p(next) - p(i)) +
+                     (1.0 + continuity) * (1.0 - bias) * (p(next + 1) - p(next)));
+                hermite(p(i), out, p(next), into, s)
+            }
+        }
+    }
+}
+
+impl Track {
+    /// Returns the number of components of every value of the track.
+    pub fn components(&self) -> usize {
+        if self.time.is_empty() { 0 } else { self.value.len() / self.time.len() }
+    }
+
+    /// Samples the track at a time, returning the components of the value. Times outside of the
+    /// keys of the track are clamped to the first or last key.
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let n = self.components();
+        if n == 0 {
+            return vec![];
+        }
+        let (i, s) = self.time.locate(time);
+        (0..n).map(|j| self.value.evaluate(n, i, j, s)).collect()
+    }
+}
+
+impl Animation {
+    /// Returns the time range of the animation. The `begin` and `end` of the animation are used
+    /// when present, and otherwise the earliest and latest key times of its tracks. Returns `None`
+    /// if neither is known.
+    pub fn time_range(&self) -> Option<(f32, f32)> {
+        let keys = union(self.tracks.iter().filter(|t| !t.time.is_empty())
+            .map(|t| (t.time.key(0), t.time.key(t.time.len() - 1))));
+        match (self.begin.or(keys.map(|k| k.0)), self.end.or(keys.map(|k| k.1))) {
+            (Some(begin), Some(end)) => Some((begin, end)),
+            _ => None
+        }
+    }
+
+    /// Samples every track of the animation at a time, and returns the resulting pose. The time
+    /// is clamped to the `begin` and `end` of the animation.
+    pub fn sample(&self, time: f32) -> Pose {
+        let mut pose = Pose::default();
+        self.sample_into(time, &mut pose);
+        pose
+    }
+
+    /// Samples every track of the animation at a time, adding the values to a pose.
+    pub fn sample_into(&self, time: f32, pose: &mut Pose) {
+        let time = match self.begin {
+            Some(begin) if time < begin => begin,
+            _ => time
+        };
+        let time = match self.end {
+            Some(end) if time > end => end,
+            _ => time
+        };
+        for track in &self.tracks {
+            let v = track.sample(time);
+            match track.target {
+                TrackTarget::Transformation(ref t) => {
+                    if let Some(sampled) = t.with_components(&v) {
+                        pose.transformations.insert(&**t as *const _, normalized(sampled));
+                    }
+                }
+                TrackTarget::MorphWeight(ref w) => {
+                    if let Some(&weight) = v.first() {
+                        pose.morph_weights.insert(&**w as *const _, weight);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The sampled values of the transformations and morph weights targeted by animations.
+///
+/// The values are keyed by the shared transformation or morph weight they replace. Targets that
+/// were not sampled keep their own value.
+#[derive(Clone, Debug, Default)]
+pub struct Pose {
+    transformations: HashMap<*const Transformation, Transformation>,
+    morph_weights: HashMap<*const MorphWeight, f32>
+}
+
+impl Pose {
+    /// Returns the value of a transformation in this pose.
+    pub fn transformation<'a>(&'a self, t: &'a Arc<Transformation>) -> &'a Transformation {
+        self.transformations.get(&(&**t as *const _)).unwrap_or(t)
+    }
+
+    /// Returns the weight of a morph weight in this pose.
+    pub fn morph_weight(&self, w: &Arc<MorphWeight>) -> f32 {
+        self.morph_weights.get(&(&**w as *const _)).cloned().unwrap_or(w.weight)
+    }
+
+    /// Returns the product of a list of transformations in this pose, in the order they appear.
+    pub fn local_matrix(&self, transformations: &[Arc<Transformation>]) -> Matrix {
+        transform::local_matrix(transformations.iter().map(|t| self.transformation(t)))
+    }
+}
+
+impl Scene {
+    /// Returns every node of the scene along with its world matrix in a pose, like
+    /// `Scene::world_matrices`.
+    pub fn posed_world_matrices(&self, pose: &Pose) -> Vec<(&Nodes, Matrix)> {
+        fn visit<'a>(nodes: &'a [Nodes], parent: &Matrix, pose: &Pose,
+            matrices: &mut Vec<(&'a Nodes, Matrix)>)
+        {
+            for node in nodes {
+                let world = multiply(parent, &pose.local_matrix(node.transformations()));
+                matrices.push((node, world));
+                visit(node.children(), &world, pose, matrices);
+            }
+        }
+
+        let mut matrices = vec![];
+        visit(&self.nodes, &IDENTITY, pose, &mut matrices);
+        matrices
+    }
+}
+
+/// The animations belonging to a clip, gathered from the whole node hierarchy of a scene.
+#[derive(Clone, Debug)]
+pub struct ClipAnimations<'a> {
+    /// The index of the clip.
+    pub index: u32,
+    /// The Clip structure with this index, if the scene has one.
+    pub clip: Option<&'a Clip>,
+    /// The animations of the clip, along with the node they belong to, in the order of
+    /// `Scene::world_matrices`.
+    pub animations: Vec<(&'a Nodes, &'a Animation)>,
+    /// The time at which the earliest animation of the clip begins.
+    pub begin: f32,
+    /// The time at which the latest animation of the clip ends.
+    pub end: f32
+}
+
+impl<'a> ClipAnimations<'a> {
+    /// Returns the name of the clip, if it has one.
+    pub fn name(&self) -> Option<&'a str> {
+        self.clip.and_then(|clip| clip.name.as_deref())
+    }
+
+    /// Samples every animation of the clip at a time, and returns the resulting pose.
+    pub fn sample(&self, time: f32) -> Pose {
+        let mut pose = Pose::default();
+        for &(_, animation) in &self.animations {
+            animation.sample_into(time, &mut pose);
+        }
+        pose
+    }
+}
+
+impl Scene {
+    /// Returns the animations of every clip, ordered by clip index. This includes the clips of the
+    /// scene without any animation, and the clips that animations refer to without a Clip
+    /// structure. Animations without a Clip structure belong to clip 0.
+    pub fn clips(&self) -> Vec<ClipAnimations<'_>> {
+        fn visit<'a>(nodes: &'a [Nodes], animations: &mut Vec<(&'a Nodes, &'a Animation)>) {
+            for node in nodes {
+                animations.extend(node.animations().iter().map(|a| (node, a)));
+                visit(node.children(), animations);
+            }
+        }
+
+        let mut animations = vec![];
+        visit(&self.nodes, &mut animations);
+        let mut indices: Vec<u32> = self.clip_structures.keys().map(|i| i as u32)
+            .chain(animations.iter().map(|&(_, a)| a.clip)).collect();
+        indices.sort();
+        indices.dedup();
+        indices.into_iter().map(|index| {
+            let animations: Vec<_> = animations.iter().cloned()
+                .filter(|&(_, a)| a.clip == index).collect();
+            let (begin, end) = union(animations.iter().filter_map(|&(_, a)| a.time_range()))
+                .unwrap_or((0.0, 0.0));
+            let clip = self.clip_structures.get(index as usize);
+            ClipAnimations { index, clip, animations, begin, end }
+        }).collect()
+    }
+}
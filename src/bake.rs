@@ -0,0 +1,133 @@
+//! Baking of animations into linearly interpolated keys.
+//!
+//! Baking samples every track of an animation at a fixed rate, and replaces its curves with linear
+//! time and value curves holding the samples. The keys that linear interpolation between their
+//! neighbors reproduces within a tolerance can then be dropped. The tracks keep their targets, so a
+//! baked animation can replace the original one in its node.
+
+use rewrite::{ self, NodeParts, Rewrite };
+use structure::*;
+
+/// The largest number of keys a track is baked into. Over a time range that would take more keys
+/// at the requested rate, the keys are spread evenly over the range instead.
+const MAX_KEYS: usize = 1 << 20;
+
+impl Animation {
+    /// Bakes the animation, sampling every track `rate` times per unit of time over the time range
+    /// of the animation. The last key is placed at the end of the time range.
+    ///
+    /// With a tolerance, keys are dropped as long as interpolating linearly between the remaining
+    /// keys stays within the tolerance of every dropped key, for every component.
+    ///
+    /// Panics if `rate` is not a finite, positive number.
+    pub fn bake(&self, rate: f32, tolerance: Option<f32>) -> Animation {
+        match self.time_range() {
+            Some((begin, end)) => self.bake_range(begin, end, rate, tolerance),
+            None => self.clone()
+        }
+    }
+
+    fn bake_range(&self, begin: f32, end: f32, rate: f32, tolerance: Option<f32>) -> Animation {
+        let times = key_times(begin, end, rate);
+        let tracks = self.tracks.iter().map(|track| {
+            let n = track.components();
+            if n == 0 {
+                return track.clone();
+            }
+            let values: Vec<Vec<f32>> = times.iter().map(|&t| track.sample(t)).collect();
+            let keys = match tolerance {
+                Some(tolerance) => simplify(&times, &values, tolerance),
+                None => (0..times.len()).collect()
+            };
+            Track {
+                target: track.target.clone(),
+                time: Time::Linear(keys.iter().map(|&i| times[i]).collect()),
+                value: Value::Linear(keys.iter().flat_map(|&i| values[i].iter().cloned()).collect())
+            }
+        }).collect();
+        Animation { tracks, ..self.clone() }
+    }
+}
+
+/// Returns the times at which a time range is sampled `rate` times per unit of time, ending with
+/// the end of the range, and with at most `MAX_KEYS` times.
+pub fn key_times(begin: f32, end: f32, rate: f32) -> Vec<f32> {
+    assert!(rate.is_finite() && rate > 0.0, "the rate must be finite and positive, not {}", rate);
+    let frames = ((end - begin) * rate).ceil().max(0.0) as usize;
+    let (frames, rate) = if frames < MAX_KEYS {
+        (frames, rate)
+    } else {
+        (MAX_KEYS - 1, (MAX_KEYS - 1) as f32 / (end - begin))
+    };
+    let mut times: Vec<f32> = (0..frames).map(|i| begin + i as f32 / rate).collect();
+    times.push(end);
+    times
+}
+
+/// Returns the indices of the keys to keep, so that interpolating linearly between them stays
+/// within `tolerance` of every key.
+///
+/// The slopes from the last kept key that pass close to every key since then form a range for each
+/// component, which is narrowed key by key, so that every key is only looked at once.
+fn simplify(times: &[f32], values: &[Vec<f32>], tolerance: f32) -> Vec<usize> {
+    let n = values.first().map_or(0, |v| v.len());
+    let mut keys = vec![0];
+    let mut slopes = vec![(f32::NEG_INFINITY, f32::INFINITY); n];
+    let mut narrowed = slopes.clone();
+    for i in 1..times.len().saturating_sub(1) {
+        // Key `i` is dropped if the line from the last kept key to the next one passes close to
+        // every key in between, that is, if its slope is in the range of every component.
+        let a = *keys.last().unwrap();
+        let (dt, db) = (times[i] - times[a], times[i + 1] - times[a]);
+        let close = dt > 0.0 && db > 0.0 && narrowed.iter_mut().enumerate().all(|(c, range)| {
+            let (lo, hi) = slopes[c];
+            let d = values[i][c] - values[a][c];
+            *range = (lo.max((d - tolerance) / dt), hi.min((d + tolerance) / dt));
+            let slope = (values[i + 1][c] - values[a][c]) / db;
+            range.0 <= slope && slope <= range.1
+        });
+        if close {
+            slopes.copy_from_slice(&narrowed);
+        } else {
+            keys.push(i);
+            slopes.fill((f32::NEG_INFINITY, f32::INFINITY));
+        }
+    }
+    if times.len() > 1 {
+        keys.push(times.len() - 1);
+    }
+    keys
+}
+
+impl Scene {
+    /// Bakes every animation of a clip in the node hierarchy, like `Animation::bake`. All the
+    /// animations are sampled at the same times, over the time range of the whole clip.
+    ///
+    /// Panics if `rate` is not a finite, positive number.
+    pub fn bake_clip(&mut self, clip: u32, rate: f32, tolerance: Option<f32>) {
+        assert!(rate.is_finite() && rate > 0.0,
+            "the rate must be finite and positive, not {}", rate);
+        let (begin, end) = match self.clips().into_iter().find(|c| c.index == clip) {
+            Some(ref c) if !c.animations.is_empty() => (c.begin, c.end),
+            _ => return
+        };
+        rewrite::rewrite(self, &mut Bake { clip, begin, end, rate, tolerance });
+    }
+}
+
+/// Bakes the animations of a clip.
+struct Bake {
+    clip: u32,
+    begin: f32,
+    end: f32,
+    rate: f32,
+    tolerance: Option<f32>
+}
+
+impl Rewrite for Bake {
+    fn node(&mut self, node: NodeParts) {
+        for animation in node.animations.iter_mut().filter(|a| a.clip == self.clip) {
+            *animation = animation.bake_range(self.begin, self.end, self.rate, self.tolerance);
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! Command-line tool for inspecting, checking, formatting and converting OpenGEX files.
+//!
+//! ```text
+//! ogex info <file>
+//! ogex validate [--deny-warnings] <file>...
+//! ogex fmt [--check] [--spaces <n>] [--precision <digits>] [--minify] <file>...
+//! ogex convert [--up <axis>] [--forward <axis>] [--distance <meters>] [--angle <radians>]
+//!              [--time <seconds>] [--bake <rate>] <input> <output>
+//! ```
+//!
+//! The exit code is 0 on success, 1 if a file is invalid or could not be processed, and 2 if the
+//! command line is invalid.
+
+extern crate opengex;
+
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use opengex::ddl;
+use opengex::error::Error;
+use opengex::gltf;
+use opengex::structure::*;
+use opengex::validate::Severity;
+use opengex::write::{ format_with, Indent, Options, Precision };
+
+const USAGE: &str = "\
+usage: ogex <command> [options] <file>...
+
+commands:
+    info <file>          print the node tree, object counts, mesh totals and metrics
+    validate <file>...   check files against the OpenGEX specification
+        --deny-warnings      fail on warnings as well as errors
+    fmt <file>...        reformat files in place, except files with comments
+        --check              only report the files that are not formatted
+        --spaces <n>         indent with n spaces instead of tabs
+        --precision <n>      write floats with n digits after the decimal point
+        --minify             write without any whitespace
+    convert <input> <output>
+                         convert a file, choosing formats by file extension
+                         (.ogex, .obj, and .gltf or .glb for output)
+        --up <axis>          convert to an up axis, like y or -z
+        --forward <axis>     convert to a forward axis
+        --distance <meters>  convert to a distance unit
+        --angle <radians>    convert to an angle unit
+        --time <seconds>     convert to a time unit
+        --bake <rate>        bake every animation clip at a rate, in keys per time unit
+";
+
+/// A failure of a command, with the exit code of the tool.
+enum Failure {
+    /// The command line is invalid.
+    Usage(String),
+    /// A file is invalid, or could not be processed. The message has been printed already if it
+    /// is empty.
+    Failed(String)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|s| s.as_str()) {
+        Some("info") => info(&args[1..]),
+        Some("validate") => validate(&args[1..]),
+        Some("fmt") => fmt(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(Failure::Usage(format!("unknown command `{}`", command))),
+        None => Err(Failure::Usage("missing command".to_string()))
+    };
+    match result {
+        Ok(()) => {}
+        Err(Failure::Usage(message)) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+        Err(Failure::Failed(message)) => {
+            if !message.is_empty() {
+                eprintln!("error: {}", message);
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// The options and files given to a command.
+struct Args<'a> {
+    /// The flags, and the options along with their values.
+    options: Vec<(&'a str, Option<&'a str>)>,
+    files: Vec<&'a str>
+}
+
+/// Splits the arguments of a command into options and files. `flags` are the options without a
+/// value, and `valued` the ones with a value.
+fn parse_args<'a>(args: &'a [String], flags: &[&str], valued: &[&str])
+    -> Result<Args<'a>, Failure>
+{
+    let mut parsed = Args { options: vec![], files: vec![] };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if flags.contains(&arg.as_str()) {
+            parsed.options.push((arg, None));
+        } else if valued.contains(&arg.as_str()) {
+            match args.next() {
+                Some(value) => parsed.options.push((arg, Some(value))),
+                None => return Err(Failure::Usage(format!("missing value for `{}`", arg)))
+            }
+        } else if arg.starts_with("--") {
+            return Err(Failure::Usage(format!("unknown option `{}`", arg)));
+        } else {
+            parsed.files.push(arg);
+        }
+    }
+    Ok(parsed)
+}
+
+impl<'a> Args<'a> {
+    fn flag(&self, flag: &str) -> bool {
+        self.options.iter().any(|o| o.0 == flag)
+    }
+
+    fn value(&self, option: &str) -> Option<&'a str> {
+        self.options.iter().rev().find(|o| o.0 == option).and_then(|o| o.1)
+    }
+
+    fn number<T: std::str::FromStr>(&self, option: &str) -> Result<Option<T>, Failure> {
+        match self.value(option) {
+            Some(value) => value.parse().map(Some).map_err(|_| {
+                Failure::Usage(format!("invalid value `{}` for `{}`", value, option))
+            }),
+            None => Ok(None)
+        }
+    }
+
+    /// Parses the value of an option that must be a finite, positive number.
+    fn positive(&self, option: &str) -> Result<Option<f32>, Failure> {
+        match self.number::<f32>(option)? {
+            Some(value) if !(value.is_finite() && value > 0.0) => {
+                let value = self.value(option).unwrap_or_default();
+                Err(Failure::Usage(format!("invalid value `{}` for `{}`", value, option)))
+            }
+            value => Ok(value)
+        }
+    }
+}
+
+fn read(path: &str) -> Result<String, Failure> {
+    fs::read_to_string(path).map_err(|err| Failure::Failed(format!("{}: {}", path, err)))
+}
+
+fn load(path: &str) -> Result<Scene, Failure> {
+    read(path)?.parse().map_err(|err| Failure::Failed(format!("{}: {}", path, err)))
+}
+
+fn info(args: &[String]) -> Result<(), Failure> {
+    let args = parse_args(args, &[], &[])?;
+    let path = match args.files[..] {
+        [path] => path,
+        _ => return Err(Failure::Usage("`info` takes a single file".to_string()))
+    };
+    let scene = load(path)?;
+    let mut out = String::new();
+
+    let metric = &scene.metric;
+    writeln!(out, "metric:").unwrap();
+    writeln!(out, "    distance: {}", metric.distance).unwrap();
+    writeln!(out, "    angle: {}", metric.angle).unwrap();
+    writeln!(out, "    time: {}", metric.time).unwrap();
+    writeln!(out, "    up: {}", axis_name(metric.up)).unwrap();
+    if let Some(forward) = metric.forward {
+        writeln!(out, "    forward: {}", axis_name(forward)).unwrap();
+    }
+
+    let (vertices, triangles) = scene.geometry_objects.iter()
+        .map(|g| mesh_totals(g))
+        .fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+    writeln!(out, "objects:").unwrap();
+    writeln!(out, "    geometry: {}", scene.geometry_objects.len()).unwrap();
+    writeln!(out, "    light: {}", scene.light_objects.len()).unwrap();
+    writeln!(out, "    camera: {}", scene.camera_objects.len()).unwrap();
+    writeln!(out, "    material: {}", scene.materials.len()).unwrap();
+    writeln!(out, "    clip: {}", scene.clips().len()).unwrap();
+    writeln!(out, "meshes:").unwrap();
+    writeln!(out, "    vertices: {}", vertices).unwrap();
+    writeln!(out, "    triangles: {}", triangles).unwrap();
+
+    writeln!(out, "nodes:").unwrap();
+    fn tree(out: &mut String, nodes: &[Nodes], depth: usize) {
+        for node in nodes {
+            let (kind, details) = match *node {
+                Nodes::Node(_) => ("Node", None),
+                Nodes::BoneNode(_) => ("BoneNode", None),
+                Nodes::GeometryNode(ref node) => {
+                    let (vertices, triangles) = mesh_totals(&node.geometry);
+                    ("GeometryNode", Some(format!("{} vertices, {} triangles", vertices,
+                        triangles)))
+                }
+                Nodes::CameraNode(_) => ("CameraNode", None),
+                Nodes::LightNode(ref node) => {
+                    ("LightNode", Some(format!("{:?}", node.light.light_type).to_lowercase()))
+                }
+            };
+            write!(out, "{}{}", "    ".repeat(depth + 1), kind).unwrap();
+            if let Some(name) = node.name() {
+                write!(out, " \"{}\"", name).unwrap();
+            }
+            if let Some(details) = details {
+                write!(out, " ({})", details).unwrap();
+            }
+            writeln!(out).unwrap();
+            tree(out, node.children(), depth + 1);
+        }
+    }
+    tree(&mut out, &scene.nodes, 0);
+    print!("{}", out);
+    Ok(())
+}
+
+/// Returns the number of vertices and triangles of the mesh of a geometry object with the highest
+/// level of detail.
+fn mesh_totals(geometry: &GeometryObject) -> (usize, usize) {
+    let mesh = match geometry.meshes.values().next() {
+        Some(mesh) => mesh,
+        None => return (0, 0)
+    };
+    let vertices = mesh.vertex_arrays.iter()
+        .find(|a| a.attrib == "position" && a.morph == 0)
+        .map_or(0, |a| a.len());
+    let triangles = mesh.to_triangle_list()
+        .map_or(0, |m| m.index_arrays.iter().map(|a| a.data.len() / 3).sum());
+    (vertices, triangles)
+}
+
+fn axis_name(axis: Axis) -> &'static str {
+    match axis {
+        Axis::X => "x",
+        Axis::Y => "y",
+        Axis::Z => "z",
+        Axis::NegX => "-x",
+        Axis::NegY => "-y",
+        Axis::NegZ => "-z"
+    }
+}
+
+fn validate(args: &[String]) -> Result<(), Failure> {
+    let args = parse_args(args, &["--deny-warnings"], &[])?;
+    if args.files.is_empty() {
+        return Err(Failure::Usage("`validate` takes at least one file".to_string()));
+    }
+    let deny_warnings = args.flag("--deny-warnings");
+    let mut failed = false;
+    for &path in &args.files {
+        let scene = match load(path) {
+            Ok(scene) => scene,
+            Err(Failure::Failed(message)) => {
+                eprintln!("error: {}", message);
+                failed = true;
+                continue;
+            }
+            Err(failure) => return Err(failure)
+        };
+        for diagnostic in scene.validate() {
+            eprintln!("{}: {}", path, diagnostic);
+            failed |= diagnostic.severity() == Severity::Error || deny_warnings;
+        }
+    }
+    if failed { Err(Failure::Failed(String::new())) } else { Ok(()) }
+}
+
+fn fmt(args: &[String]) -> Result<(), Failure> {
+    let args = parse_args(args, &["--check", "--minify"], &["--spaces", "--precision"])?;
+    if args.files.is_empty() {
+        return Err(Failure::Usage("`fmt` takes at least one file".to_string()));
+    }
+    let mut options = Options::default();
+    if let Some(spaces) = args.number("--spaces")? {
+        options.indent = Indent::Spaces(spaces);
+    }
+    if let Some(digits) = args.number("--precision")? {
+        options.precision = Precision::Fixed(digits);
+    }
+    options.minify = args.flag("--minify");
+
+    let mut failed = false;
+    for &path in &args.files {
+        let source = match read(path) {
+            Ok(source) => source,
+            Err(Failure::Failed(message)) => {
+                eprintln!("error: {}", message);
+                failed = true;
+                continue;
+            }
+            Err(failure) => return Err(failure)
+        };
+        // Formatting would drop the comments, which the parser skips.
+        if has_comments(&source) {
+            eprintln!("error: {}: the file has comments, which formatting would remove", path);
+            failed = true;
+            continue;
+        }
+        // Files are formatted as OpenDDL, so that structures unknown to OpenGEX are kept.
+        let structures = match ddl::parse(&source) {
+            Ok(structures) => structures,
+            Err(err) => {
+                eprintln!("error: {}: {}", path, opengex::Error::parse(err, &source));
+                failed = true;
+                continue;
+            }
+        };
+        let formatted = format_with(&structures, &options);
+        if formatted == source {
+            continue;
+        }
+        if args.flag("--check") {
+            println!("{}", path);
+            failed = true;
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("error: {}: {}", path, err);
+            failed = true;
+        }
+    }
+    if failed { Err(Failure::Failed(String::new())) } else { Ok(()) }
+}
+
+/// Returns whether OpenDDL source text has comments, outside of string and character literals.
+fn has_comments(source: &str) -> bool {
+    let mut bytes = source.bytes();
+    let mut quote = None;
+    while let Some(c) = bytes.next() {
+        match (quote, c) {
+            (Some(_), b'\\') => {
+                bytes.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"') | (None, b'\'') => quote = Some(c),
+            (None, b'/') if matches!(bytes.clone().next(), Some(b'/') | Some(b'*')) => return true,
+            (None, _) => {}
+        }
+    }
+    false
+}
+
+fn convert(args: &[String]) -> Result<(), Failure> {
+    let args = parse_args(args, &[], &["--up", "--forward", "--distance", "--angle", "--time",
+        "--bake"])?;
+    let (input, output) = match args.files[..] {
+        [input, output] => (input, output),
+        _ => return Err(Failure::Usage("`convert` takes an input and an output file".to_string()))
+    };
+    let distance = args.positive("--distance")?;
+    let angle = args.positive("--angle")?;
+    let time = args.positive("--time")?;
+    let rate = args.positive("--bake")?;
+    let mut scene = match extension(input).as_str() {
+        "ogex" => load(input)?,
+        "obj" => Scene::from_obj_file(input)
+            .map_err(|err| Failure::Failed(format!("{}: {}", input, err)))?,
+        ext => return Err(Failure::Usage(format!("unsupported input format `{}`", ext)))
+    };
+
+    let mut metric = scene.metric.clone();
+    if let Some(up) = args.value("--up") {
+        metric.up = parse_axis(up)?;
+    }
+    if let Some(forward) = args.value("--forward") {
+        metric.forward = Some(parse_axis(forward)?);
+    }
+    metric.distance = distance.unwrap_or(metric.distance);
+    metric.angle = angle.unwrap_or(metric.angle);
+    metric.time = time.unwrap_or(metric.time);
+    if metric != scene.metric {
+        scene.normalize(&metric);
+    }
+    if let Some(rate) = rate {
+        let clips: Vec<u32> = scene.clips().iter().map(|c| c.index).collect();
+        for clip in clips {
+            scene.bake_clip(clip, rate, None);
+        }
+    }
+
+    let written = match extension(output).as_str() {
+        "ogex" => scene.to_string_with(&Options::default())
+            .and_then(|source| Ok(fs::write(output, source)?)),
+        "obj" => scene.to_obj_file(output).map_err(Error::from),
+        "gltf" => scene.to_gltf_file(output, &gltf::Options::default()).map_err(Error::from),
+        "glb" => scene.to_glb_file(output, &gltf::Options::default()).map_err(Error::from),
+        ext => return Err(Failure::Usage(format!("unsupported output format `{}`", ext)))
+    };
+    written.map_err(|err| Failure::Failed(format!("{}: {}", output, err)))
+}
+
+/// Returns the lowercase extension of a path, or an empty string.
+fn extension(path: &str) -> String {
+    Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase()
+}
+
+fn parse_axis(axis: &str) -> Result<Axis, Failure> {
+    Ok(match axis {
+        "x" => Axis::X,
+        "y" => Axis::Y,
+        "z" => Axis::Z,
+        "-x" => Axis::NegX,
+        "-y" => Axis::NegY,
+        "-z" => Axis::NegZ,
+        _ => return Err(Failure::Usage(format!("invalid axis `{}`", axis)))
+    })
+}
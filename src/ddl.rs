@@ -0,0 +1,1188 @@
+//! A native parser for the Open Data Description Language (OpenDDL), the generic data language
+//! OpenGEX files are written in.
+//!
+//! The parser turns OpenDDL text into a tree of `Structure`s without loading any grammar file at
+//! runtime. Every structure is either a primitive data structure, holding an array of values of a
+//! single data type, or a derived structure, holding an identifier, properties and any number of
+//! substructures. For more information about OpenDDL, please go to http://openddl.org.
+
+use std::cell::Cell;
+use std::error;
+use std::fmt;
+use std::str;
+
+use nom::{ Err, ErrorKind, IResult };
+use nom::{ is_alphabetic, is_digit, is_hex_digit, is_oct_digit };
+
+/// An OpenDDL name, which can be assigned to any structure so it can be referenced by others.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Name {
+    /// A global name, written as `$identifier`. Global names are unique throughout the file.
+    Global(String),
+    /// A local name, written as `%identifier`. Local names are unique among siblings.
+    Local(String)
+}
+
+impl Name {
+    /// Returns the identifier of this name, without the `$` or `%` prefix.
+    pub fn identifier(&self) -> &str {
+        match *self {
+            Name::Global(ref id) | Name::Local(ref id) => id
+        }
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Name::Global(ref id) => write!(f, "${}", id),
+            Name::Local(ref id) => write!(f, "%{}", id)
+        }
+    }
+}
+
+/// A reference to a structure, given as a sequence of names. The first name may be global or
+/// local, all names following it are local names. A reference without any names is the `null`
+/// reference.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Reference(pub Vec<Name>);
+
+impl Reference {
+    /// Whether this is the `null` reference.
+    pub fn is_null(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_null() {
+            return write!(f, "null");
+        }
+        for name in &self.0 {
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// The data types a primitive data structure can hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataType {
+    /// A boolean value, `bool` or `b`.
+    Bool,
+    /// An 8-bit signed integer, `int8` or `i8`.
+    Int8,
+    /// A 16-bit signed integer, `int16` or `i16`.
+    Int16,
+    /// A 32-bit signed integer, `int32` or `i32`.
+    Int32,
+    /// A 64-bit signed integer, `int64` or `i64`.
+    Int64,
+    /// An 8-bit unsigned integer, `unsigned_int8` or `u8`.
+    UnsignedInt8,
+    /// A 16-bit unsigned integer, `unsigned_int16` or `u16`.
+    UnsignedInt16,
+    /// A 32-bit unsigned integer, `unsigned_int32` or `u32`.
+    UnsignedInt32,
+    /// A 64-bit unsigned integer, `unsigned_int64` or `u64`.
+    UnsignedInt64,
+    /// A 16-bit floating-point value, `half`, `float16`, `h` or `f16`.
+    Half,
+    /// A 32-bit floating-point value, `float`, `float32`, `f` or `f32`.
+    Float,
+    /// A 64-bit floating-point value, `double`, `float64`, `d` or `f64`.
+    Double,
+    /// A string, `string` or `s`.
+    String,
+    /// A reference to another structure, `ref` or `r`.
+    Ref,
+    /// A data type, `type` or `t`.
+    Type
+}
+
+impl DataType {
+    /// Looks up the data type with the given identifier. Both the long and the short forms of the
+    /// data type names are recognized.
+    pub fn from_identifier(identifier: &str) -> Option<DataType> {
+        Some(match identifier {
+            "bool" | "b" => DataType::Bool,
+            "int8" | "i8" => DataType::Int8,
+            "int16" | "i16" => DataType::Int16,
+            "int32" | "i32" => DataType::Int32,
+            "int64" | "i64" => DataType::Int64,
+            "unsigned_int8" | "u8" => DataType::UnsignedInt8,
+            "unsigned_int16" | "u16" => DataType::UnsignedInt16,
+            "unsigned_int32" | "u32" => DataType::UnsignedInt32,
+            "unsigned_int64" | "u64" => DataType::UnsignedInt64,
+            "half" | "float16" | "h" | "f16" => DataType::Half,
+            "float" | "float32" | "f" | "f32" => DataType::Float,
+            "double" | "float64" | "d" | "f64" => DataType::Double,
+            "string" | "s" => DataType::String,
+            "ref" | "r" => DataType::Ref,
+            "type" | "t" => DataType::Type,
+            _ => return None
+        })
+    }
+
+    /// Returns the long name of this data type, as written in OpenDDL.
+    pub fn identifier(&self) -> &'static str {
+        match *self {
+            DataType::Bool => "bool",
+            DataType::Int8 => "int8",
+            DataType::Int16 => "int16",
+            DataType::Int32 => "int32",
+            DataType::Int64 => "int64",
+            DataType::UnsignedInt8 => "unsigned_int8",
+            DataType::UnsignedInt16 => "unsigned_int16",
+            DataType::UnsignedInt32 => "unsigned_int32",
+            DataType::UnsignedInt64 => "unsigned_int64",
+            DataType::Half => "half",
+            DataType::Float => "float",
+            DataType::Double => "double",
+            DataType::String => "string",
+            DataType::Ref => "ref",
+            DataType::Type => "type"
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.identifier())
+    }
+}
+
+/// The data contained in a primitive data structure. There is one variant for every data type.
+///
+/// Half-precision values are widened to `f32` while parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Data {
+    /// An array of `bool` values.
+    Bool(Vec<bool>),
+    /// An array of `int8` values.
+    Int8(Vec<i8>),
+    /// An array of `int16` values.
+    Int16(Vec<i16>),
+    /// An array of `int32` values.
+    Int32(Vec<i32>),
+    /// An array of `int64` values.
+    Int64(Vec<i64>),
+    /// An array of `unsigned_int8` values.
+    UnsignedInt8(Vec<u8>),
+    /// An array of `unsigned_int16` values.
+    UnsignedInt16(Vec<u16>),
+    /// An array of `unsigned_int32` values.
+    UnsignedInt32(Vec<u32>),
+    /// An array of `unsigned_int64` values.
+    UnsignedInt64(Vec<u64>),
+    /// An array of `half` values.
+    Half(Vec<f32>),
+    /// An array of `float` values.
+    Float(Vec<f32>),
+    /// An array of `double` values.
+    Double(Vec<f64>),
+    /// An array of `string` values.
+    String(Vec<String>),
+    /// An array of `ref` values.
+    Ref(Vec<Reference>),
+    /// An array of `type` values.
+    Type(Vec<DataType>)
+}
+
+impl Data {
+    /// Creates an empty array of the given data type.
+    pub fn new(data_type: DataType) -> Data {
+        match data_type {
+            DataType::Bool => Data::Bool(vec![]),
+            DataType::Int8 => Data::Int8(vec![]),
+            DataType::Int16 => Data::Int16(vec![]),
+            DataType::Int32 => Data::Int32(vec![]),
+            DataType::Int64 => Data::Int64(vec![]),
+            DataType::UnsignedInt8 => Data::UnsignedInt8(vec![]),
+            DataType::UnsignedInt16 => Data::UnsignedInt16(vec![]),
+            DataType::UnsignedInt32 => Data::UnsignedInt32(vec![]),
+            DataType::UnsignedInt64 => Data::UnsignedInt64(vec![]),
+            DataType::Half => Data::Half(vec![]),
+            DataType::Float => Data::Float(vec![]),
+            DataType::Double => Data::Double(vec![]),
+            DataType::String => Data::String(vec![]),
+            DataType::Ref => Data::Ref(vec![]),
+            DataType::Type => Data::Type(vec![])
+        }
+    }
+
+    /// Returns the data type of the values in this array.
+    pub fn data_type(&self) -> DataType {
+        match *self {
+            Data::Bool(_) => DataType::Bool,
+            Data::Int8(_) => DataType::Int8,
+            Data::Int16(_) => DataType::Int16,
+            Data::Int32(_) => DataType::Int32,
+            Data::Int64(_) => DataType::Int64,
+            Data::UnsignedInt8(_) => DataType::UnsignedInt8,
+            Data::UnsignedInt16(_) => DataType::UnsignedInt16,
+            Data::UnsignedInt32(_) => DataType::UnsignedInt32,
+            Data::UnsignedInt64(_) => DataType::UnsignedInt64,
+            Data::Half(_) => DataType::Half,
+            Data::Float(_) => DataType::Float,
+            Data::Double(_) => DataType::Double,
+            Data::String(_) => DataType::String,
+            Data::Ref(_) => DataType::Ref,
+            Data::Type(_) => DataType::Type
+        }
+    }
+
+    /// Returns the number of values in this array.
+    pub fn len(&self) -> usize {
+        match *self {
+            Data::Bool(ref v) => v.len(),
+            Data::Int8(ref v) => v.len(),
+            Data::Int16(ref v) => v.len(),
+            Data::Int32(ref v) => v.len(),
+            Data::Int64(ref v) => v.len(),
+            Data::UnsignedInt8(ref v) => v.len(),
+            Data::UnsignedInt16(ref v) => v.len(),
+            Data::UnsignedInt32(ref v) => v.len(),
+            Data::UnsignedInt64(ref v) => v.len(),
+            Data::Half(ref v) | Data::Float(ref v) => v.len(),
+            Data::Double(ref v) => v.len(),
+            Data::String(ref v) => v.len(),
+            Data::Ref(ref v) => v.len(),
+            Data::Type(ref v) => v.len()
+        }
+    }
+
+    /// Whether this array contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A property value of a derived structure.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    /// A boolean literal.
+    Bool(bool),
+    /// An integer literal, including character literals.
+    Integer(i64),
+    /// An integer literal above the largest `i64`, such as the largest 64-bit restart index.
+    Unsigned(u64),
+    /// A floating-point literal.
+    Float(f64),
+    /// A string literal.
+    String(String),
+    /// A reference.
+    Ref(Reference),
+    /// A data type.
+    Type(DataType)
+}
+
+/// A property of a derived structure, given as `identifier = value` inside parentheses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    /// The identifier of the property.
+    pub identifier: String,
+    /// The value of the property. A property written without a value is a `true` boolean.
+    pub value: Literal
+}
+
+/// A primitive data structure, such as `float[3] {{1.0, 2.0, 3.0}}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrimitiveStructure {
+    /// The optional name of this structure.
+    pub name: Option<Name>,
+    /// The size of the subarrays, if the data is organized in subarrays. The data is stored
+    /// flattened, so it holds `data.len() / array_size` subarrays.
+    pub array_size: Option<usize>,
+    /// The values of this structure.
+    pub data: Data,
+    /// The byte offset of this structure in the source text.
+    pub offset: usize
+}
+
+/// A derived structure, such as `Mesh (primitive = "triangles") { ... }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DerivedStructure {
+    /// The identifier of this structure.
+    pub identifier: String,
+    /// The optional name of this structure.
+    pub name: Option<Name>,
+    /// The properties of this structure, in the order they were written.
+    pub properties: Vec<Property>,
+    /// The substructures of this structure, in the order they were written.
+    pub children: Vec<Structure>,
+    /// The byte offset of this structure in the source text.
+    pub offset: usize
+}
+
+impl DerivedStructure {
+    /// Returns the value of the property with the given identifier, if it is present.
+    pub fn property(&self, identifier: &str) -> Option<&Literal> {
+        self.properties.iter().find(|p| p.identifier == identifier).map(|p| &p.value)
+    }
+}
+
+/// A single OpenDDL structure.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Structure {
+    /// A primitive data structure.
+    Primitive(PrimitiveStructure),
+    /// A derived structure.
+    Derived(DerivedStructure)
+}
+
+impl Structure {
+    /// Returns the name of this structure, if it has one.
+    pub fn name(&self) -> Option<&Name> {
+        match *self {
+            Structure::Primitive(ref s) => s.name.as_ref(),
+            Structure::Derived(ref s) => s.name.as_ref()
+        }
+    }
+
+    /// Returns the byte offset of this structure in the source text.
+    pub fn offset(&self) -> usize {
+        match *self {
+            Structure::Primitive(ref s) => s.offset,
+            Structure::Derived(ref s) => s.offset
+        }
+    }
+}
+
+/// Describes what the parser expected to find when it failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expected {
+    /// A structure, or the end of the enclosing structure or file.
+    Structure,
+    /// An identifier.
+    Identifier,
+    /// A name, starting with `$` or `%`.
+    Name,
+    /// A property, or the end of the property list.
+    Property,
+    /// A value of the given data type.
+    Value(DataType),
+    /// A property value.
+    PropertyValue,
+    /// A subarray holding the given number of values.
+    Subarray(usize),
+    /// An array size.
+    ArraySize,
+    /// A valid escape sequence in a string literal.
+    Escape,
+    /// The given character.
+    Char(char),
+    /// The end of a comment.
+    CommentEnd
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expected::Structure => write!(f, "a structure"),
+            Expected::Identifier => write!(f, "an identifier"),
+            Expected::Name => write!(f, "a name"),
+            Expected::Property => write!(f, "a property"),
+            Expected::Value(ty) => write!(f, "a {} value", ty),
+            Expected::PropertyValue => write!(f, "a property value"),
+            Expected::Subarray(n) => write!(f, "a subarray of {} values", n),
+            Expected::ArraySize => write!(f, "an array size"),
+            Expected::Escape => write!(f, "an escape sequence"),
+            Expected::Char(c) => write!(f, "`{}`", c),
+            Expected::CommentEnd => write!(f, "`*/`")
+        }
+    }
+}
+
+/// An error produced when the source text is not valid OpenDDL.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// The byte offset in the source text where the error occurred.
+    pub offset: usize,
+    /// What the parser expected to find at that offset.
+    pub expected: Expected
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {} at byte {}", self.expected, self.offset)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Parses OpenDDL source text into the list of its top-level structures.
+pub fn parse(source: &str) -> Result<Vec<Structure>, ParseError> {
+    let parser = Parser { source: source.as_bytes(), subarray: Cell::new(0) };
+    let error = |rest: &[u8], code: u32| ParseError {
+        offset: parser.offset(rest),
+        expected: if code < SUBARRAY {
+            EXPECTED[code as usize]
+        } else {
+            Expected::Subarray(parser.subarray.get())
+        }
+    };
+    match parser.file(source.as_bytes()) {
+        IResult::Done(_, structures) => Ok(structures),
+        IResult::Error(Err::Position(ErrorKind::Custom(code), rest)) => Err(error(rest, code)),
+        IResult::Error(_) | IResult::Incomplete(_) => Err(ParseError {
+            offset: source.len(),
+            expected: Expected::Structure
+        })
+    }
+}
+
+// The parser reports errors through `ErrorKind::Custom` codes that index into this table. The
+// data types are listed in the same order as their `Expected::Value` codes.
+const EXPECTED: [Expected; 26] = [
+    Expected::Structure,
+    Expected::Identifier,
+    Expected::Name,
+    Expected::Property,
+    Expected::PropertyValue,
+    Expected::ArraySize,
+    Expected::Escape,
+    Expected::CommentEnd,
+    Expected::Char('{'),
+    Expected::Char('}'),
+    Expected::Char(')'),
+    Expected::Char(']'),
+    Expected::Char('"'),
+    Expected::Char('\''),
+    Expected::Value(DataType::Bool),
+    Expected::Value(DataType::Int8),
+    Expected::Value(DataType::Int16),
+    Expected::Value(DataType::Int32),
+    Expected::Value(DataType::Int64),
+    Expected::Value(DataType::UnsignedInt8),
+    Expected::Value(DataType::UnsignedInt16),
+    Expected::Value(DataType::UnsignedInt32),
+    Expected::Value(DataType::UnsignedInt64),
+    Expected::Value(DataType::Half),
+    Expected::Value(DataType::Float),
+    Expected::Value(DataType::Double)
+];
+
+const STRUCTURE: u32 = 0;
+const IDENTIFIER: u32 = 1;
+const NAME: u32 = 2;
+const PROPERTY: u32 = 3;
+const PROPERTY_VALUE: u32 = 4;
+const ARRAY_SIZE: u32 = 5;
+const ESCAPE: u32 = 6;
+const COMMENT_END: u32 = 7;
+const OPEN_BRACE: u32 = 8;
+const CLOSE_BRACE: u32 = 9;
+const CLOSE_PAREN: u32 = 10;
+const CLOSE_BRACKET: u32 = 11;
+const QUOTE: u32 = 12;
+const APOSTROPHE: u32 = 13;
+const BOOL: u32 = 14;
+// Subarray errors are reported past the end of the table, with the expected array size kept by the
+// parser.
+const SUBARRAY: u32 = 26;
+
+fn value_code(data_type: DataType) -> u32 {
+    match data_type {
+        DataType::Bool => BOOL,
+        DataType::Int8 => BOOL + 1,
+        DataType::Int16 => BOOL + 2,
+        DataType::Int32 => BOOL + 3,
+        DataType::Int64 => BOOL + 4,
+        DataType::UnsignedInt8 => BOOL + 5,
+        DataType::UnsignedInt16 => BOOL + 6,
+        DataType::UnsignedInt32 => BOOL + 7,
+        DataType::UnsignedInt64 => BOOL + 8,
+        DataType::Half => BOOL + 9,
+        DataType::Float => BOOL + 10,
+        DataType::Double => BOOL + 11,
+        DataType::String => QUOTE,
+        DataType::Ref => NAME,
+        DataType::Type => IDENTIFIER
+    }
+}
+
+type Res<'a, O> = IResult<&'a [u8], O>;
+
+fn fail<O>(input: &[u8], code: u32) -> Res<'_, O> {
+    IResult::Error(Err::Position(ErrorKind::Custom(code), input))
+}
+
+/// Unwraps a successful parse result, or returns the error from the enclosing function.
+macro_rules! next {
+    ($e:expr) => (
+        match $e {
+            IResult::Done(rest, out) => (rest, out),
+            IResult::Error(e) => return IResult::Error(e),
+            IResult::Incomplete(n) => return IResult::Incomplete(n)
+        }
+    )
+}
+
+fn is_identifier_char(c: u8) -> bool {
+    is_alphabetic(c) || is_digit(c) || c == b'_'
+}
+
+fn is_digit_or_separator(c: u8) -> bool {
+    is_hex_digit(c) || c == b'_'
+}
+
+/// A number literal as written, before it is converted to a specific data type.
+enum Number {
+    /// A decimal literal. The text has its separators removed and includes the sign.
+    Decimal(String, bool),
+    /// A hexadecimal, octal, binary or character literal, holding an exact bit pattern.
+    Bits(bool, u64)
+}
+
+impl Number {
+    fn to_unsigned(&self, bits: u32) -> Option<u64> {
+        let max = if bits == 64 { !0 } else { (1 << bits) - 1 };
+        let value = match *self {
+            Number::Decimal(ref text, false) => match text.parse::<u64>() {
+                Ok(value) => value,
+                Err(_) => return None
+            },
+            Number::Decimal(_, true) => return None,
+            Number::Bits(false, value) => value,
+            Number::Bits(true, value) => if value == 0 { 0 } else { return None }
+        };
+        if value <= max { Some(value) } else { None }
+    }
+
+    fn to_signed(&self, bits: u32) -> Option<i64> {
+        let max = (1u64 << (bits - 1)) - 1;
+        match *self {
+            Number::Decimal(ref text, false) => match text.parse::<i64>() {
+                Ok(value) if value >= -(max as i64) - 1 && value <= max as i64 => Some(value),
+                _ => None
+            },
+            Number::Decimal(_, true) => None,
+            Number::Bits(negative, value) => {
+                if negative {
+                    if value <= max + 1 { Some((value as i64).wrapping_neg()) } else { None }
+                } else if value <= max {
+                    Some(value as i64)
+                } else {
+                    // Bit patterns that fill the whole type are taken as two's complement.
+                    let unsigned = if bits == 64 { !0 } else { (1 << bits) - 1 };
+                    if value <= unsigned {
+                        Some((value | !unsigned) as i64)
+                    } else {
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn to_float(&self, data_type: DataType) -> Option<f64> {
+        match *self {
+            Number::Decimal(ref text, _) => text.parse::<f64>().ok(),
+            Number::Bits(negative, value) => {
+                let float = match data_type {
+                    DataType::Half if value <= 0xffff => half_to_f32(value as u16) as f64,
+                    DataType::Float if value <= 0xffff_ffff =>
+                        f32::from_bits(value as u32) as f64,
+                    DataType::Double => f64::from_bits(value),
+                    _ => return None
+                };
+                Some(if negative { -float } else { float })
+            }
+        }
+    }
+}
+
+/// Converts the bits of an IEEE 754 half-precision float into an `f32`.
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = ((bits as u32) & 0x8000) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+    let bits = if exponent == 0x1f {
+        sign | 0x7f80_0000 | (mantissa << 13)
+    } else if exponent != 0 {
+        sign | ((exponent + 112) << 23) | (mantissa << 13)
+    } else if mantissa == 0 {
+        sign
+    } else {
+        // Subnormal halves become normal floats.
+        let shift = mantissa.leading_zeros() - 21;
+        sign | ((113 - shift) << 23) | (((mantissa << shift) & 0x3ff) << 13)
+    };
+    f32::from_bits(bits)
+}
+
+struct Parser<'a> {
+    source: &'a [u8],
+    /// The number of values expected in the subarray of the last `SUBARRAY` error, which does not
+    /// fit in its error code.
+    subarray: Cell<usize>
+}
+
+impl<'a> Parser<'a> {
+    fn offset(&self, rest: &[u8]) -> usize {
+        self.source.len() - rest.len()
+    }
+
+    fn file(&self, input: &'a [u8]) -> Res<'a, Vec<Structure>> {
+        let (rest, structures) = next!(self.structures(input));
+        if rest.is_empty() {
+            IResult::Done(rest, structures)
+        } else {
+            fail(rest, STRUCTURE)
+        }
+    }
+
+    /// Parses structures until the end of the input or a closing brace.
+    fn structures(&self, input: &'a [u8]) -> Res<'a, Vec<Structure>> {
+        let mut structures = vec![];
+        let mut input = input;
+        loop {
+            let (rest, _) = next!(whitespace(input));
+            if rest.is_empty() || rest[0] == b'}' {
+                return IResult::Done(rest, structures);
+            }
+            let (rest, structure) = next!(self.structure(rest));
+            structures.push(structure);
+            input = rest;
+        }
+    }
+
+    fn structure(&self, input: &'a [u8]) -> Res<'a, Structure> {
+        let offset = self.offset(input);
+        let (rest, identifier) = match identifier(input) {
+            IResult::Done(rest, identifier) => (rest, identifier),
+            _ => return fail(input, STRUCTURE)
+        };
+        match DataType::from_identifier(identifier) {
+            Some(data_type) => {
+                let (rest, structure) = next!(self.primitive(rest, data_type, offset));
+                IResult::Done(rest, Structure::Primitive(structure))
+            }
+            None => {
+                let (rest, structure) = next!(self.derived(rest, identifier, offset));
+                IResult::Done(rest, Structure::Derived(structure))
+            }
+        }
+    }
+
+    fn primitive(&self, input: &'a [u8], data_type: DataType, offset: usize)
+        -> Res<'a, PrimitiveStructure>
+    {
+        let (rest, _) = next!(whitespace(input));
+        let (rest, array_size) = if rest.first() == Some(&b'[') {
+            let (rest, _) = next!(whitespace(&rest[1..]));
+            let (after, number) = match number(rest) {
+                IResult::Done(after, number) => (after, number),
+                _ => return fail(rest, ARRAY_SIZE)
+            };
+            let size = match number.to_unsigned(32) {
+                Some(size) if size > 0 => size as usize,
+                _ => return fail(rest, ARRAY_SIZE)
+            };
+            let (after, _) = next!(whitespace(after));
+            let (after, _) = next!(symbol(after, b']', CLOSE_BRACKET));
+            (after, Some(size))
+        } else {
+            (rest, None)
+        };
+        let (rest, _) = next!(whitespace(rest));
+        let (rest, name) = next!(opt_name(rest));
+        let (rest, _) = next!(whitespace(rest));
+        let (mut rest, _) = next!(symbol(rest, b'{', OPEN_BRACE));
+        let mut data = Data::new(data_type);
+        loop {
+            let (after, _) = next!(whitespace(rest));
+            if after.first() == Some(&b'}') {
+                rest = &after[1..];
+                break;
+            }
+            if !data.is_empty() {
+                let (after, _) = next!(symbol(after, b',', CLOSE_BRACE));
+                rest = next!(whitespace(after)).0;
+            } else {
+                rest = after;
+            }
+            rest = match array_size {
+                Some(size) => {
+                    let (after, _) = next!(symbol(rest, b'{', OPEN_BRACE));
+                    let (after, _) = next!(whitespace(after));
+                    let (after, count) = next!(values(after, &mut data, Some(size)));
+                    if count != size {
+                        self.subarray.set(size);
+                        return fail(after, SUBARRAY);
+                    }
+                    let (after, _) = next!(whitespace(after));
+                    next!(symbol(after, b'}', CLOSE_BRACE)).0
+                }
+                None => {
+                    let (after, _) = next!(values(rest, &mut data, None));
+                    let (after, _) = next!(whitespace(after));
+                    next!(symbol(after, b'}', CLOSE_BRACE)).0
+                }
+            };
+            if array_size.is_none() {
+                break;
+            }
+        }
+        IResult::Done(rest, PrimitiveStructure {
+            name,
+            array_size,
+            data,
+            offset
+        })
+    }
+
+    fn derived(&self, input: &'a [u8], identifier: &str, offset: usize)
+        -> Res<'a, DerivedStructure>
+    {
+        let (rest, _) = next!(whitespace(input));
+        let (rest, name) = next!(opt_name(rest));
+        let (rest, _) = next!(whitespace(rest));
+        let (rest, properties) = if rest.first() == Some(&b'(') {
+            next!(properties(&rest[1..]))
+        } else {
+            (rest, vec![])
+        };
+        let (rest, _) = next!(whitespace(rest));
+        let (rest, _) = next!(symbol(rest, b'{', OPEN_BRACE));
+        let (rest, children) = next!(self.structures(rest));
+        let (rest, _) = next!(symbol(rest, b'}', CLOSE_BRACE));
+        IResult::Done(rest, DerivedStructure {
+            identifier: identifier.to_string(),
+            name,
+            properties,
+            children,
+            offset
+        })
+    }
+}
+
+/// Skips whitespace and comments.
+fn whitespace(input: &[u8]) -> Res<'_, ()> {
+    let mut input = input;
+    loop {
+        input = next!(take_while!(input, is_whitespace_char)).0;
+        if input.starts_with(b"//") {
+            input = match input.iter().position(|&c| c == b'\n') {
+                Some(n) => &input[n + 1..],
+                None => &input[input.len()..]
+            };
+        } else if input.starts_with(b"/*") {
+            input = match input.windows(2).position(|w| w == b"*/") {
+                Some(n) => &input[n + 2..],
+                None => return fail(&input[input.len()..], COMMENT_END)
+            };
+        } else {
+            return IResult::Done(input, ());
+        }
+    }
+}
+
+fn is_whitespace_char(c: u8) -> bool {
+    c <= b' '
+}
+
+fn symbol(input: &[u8], c: u8, code: u32) -> Res<'_, ()> {
+    if input.first() == Some(&c) {
+        IResult::Done(&input[1..], ())
+    } else {
+        fail(input, code)
+    }
+}
+
+fn identifier(input: &[u8]) -> Res<'_, &str> {
+    match input.first() {
+        Some(&c) if is_alphabetic(c) || c == b'_' => {
+            let (rest, id) = next!(take_while!(input, is_identifier_char));
+            IResult::Done(rest, str::from_utf8(id).unwrap())
+        }
+        _ => fail(input, IDENTIFIER)
+    }
+}
+
+fn name(input: &[u8]) -> Res<'_, Name> {
+    let global = match input.first() {
+        Some(&b'$') => true,
+        Some(&b'%') => false,
+        _ => return fail(input, NAME)
+    };
+    let (rest, id) = next!(identifier(&input[1..]));
+    let id = id.to_string();
+    IResult::Done(rest, if global { Name::Global(id) } else { Name::Local(id) })
+}
+
+fn opt_name(input: &[u8]) -> Res<'_, Option<Name>> {
+    match input.first() {
+        Some(&b'$') | Some(&b'%') => {
+            let (rest, name) = next!(name(input));
+            IResult::Done(rest, Some(name))
+        }
+        _ => IResult::Done(input, None)
+    }
+}
+
+fn reference(input: &[u8]) -> Res<'_, Reference> {
+    if let IResult::Done(rest, "null") = identifier(input) {
+        return IResult::Done(rest, Reference(vec![]));
+    }
+    let (mut rest, first) = next!(name(input));
+    let mut names = vec![first];
+    while rest.first() == Some(&b'%') {
+        let (after, local) = next!(name(rest));
+        names.push(local);
+        rest = after;
+    }
+    IResult::Done(rest, Reference(names))
+}
+
+fn data_type(input: &[u8]) -> Res<'_, DataType> {
+    match identifier(input) {
+        IResult::Done(rest, id) => match DataType::from_identifier(id) {
+            Some(data_type) => IResult::Done(rest, data_type),
+            None => fail(input, IDENTIFIER)
+        },
+        _ => fail(input, IDENTIFIER)
+    }
+}
+
+fn bool_literal(input: &[u8]) -> Res<'_, bool> {
+    match identifier(input) {
+        IResult::Done(rest, "true") => IResult::Done(rest, true),
+        IResult::Done(rest, "false") => IResult::Done(rest, false),
+        _ => fail(input, BOOL)
+    }
+}
+
+fn number(input: &[u8]) -> Res<'_, Number> {
+    let (rest, negative) = match input.first() {
+        Some(&b'-') => (&input[1..], true),
+        Some(&b'+') => (&input[1..], false),
+        _ => (input, false)
+    };
+    if rest.first() == Some(&b'\'') {
+        let (rest, value) = next!(char_literal(rest));
+        return IResult::Done(rest, Number::Bits(negative, value));
+    }
+    let radix = if rest.len() > 1 && rest[0] == b'0' {
+        match rest[1] {
+            b'x' | b'X' => 16,
+            b'o' | b'O' => 8,
+            b'b' | b'B' => 2,
+            _ => 10
+        }
+    } else {
+        10
+    };
+    if radix != 10 {
+        let digits = &rest[2..];
+        let (after, text) = next!(take_while!(digits, is_digit_or_separator));
+        let valid = |c: u8| match radix {
+            16 => is_hex_digit(c),
+            8 => is_oct_digit(c),
+            _ => c == b'0' || c == b'1'
+        };
+        if text.is_empty() || text[0] == b'_' || !text.iter().all(|&c| valid(c) || c == b'_') {
+            return fail(input, PROPERTY_VALUE);
+        }
+        let mut value: u64 = 0;
+        for &c in text.iter().filter(|&&c| c != b'_') {
+            let digit = (c as char).to_digit(16).unwrap() as u64;
+            value = match value.checked_mul(radix).and_then(|v| v.checked_add(digit)) {
+                Some(value) => value,
+                None => return fail(input, PROPERTY_VALUE)
+            };
+        }
+        return IResult::Done(after, Number::Bits(negative, value));
+    }
+    // Decimal literals, either integer or floating-point.
+    let mut text = String::new();
+    if negative {
+        text.push('-');
+    }
+    let mut is_float = false;
+    let mut digits = 0;
+    let mut i = 0;
+    while i < rest.len() {
+        let c = rest[i];
+        if is_digit(c) {
+            digits += 1;
+            text.push(c as char);
+        } else if c == b'_' && digits > 0 {
+            // Separators are ignored.
+        } else if c == b'.' && !is_float {
+            is_float = true;
+            text.push('.');
+        } else if (c == b'e' || c == b'E') && digits > 0 {
+            text.push('e');
+            if i + 1 < rest.len() && (rest[i + 1] == b'+' || rest[i + 1] == b'-') {
+                i += 1;
+                text.push(rest[i] as char);
+            }
+            let exponent = take_while!(&rest[i + 1..], is_digit);
+            let (after, exponent) = next!(exponent);
+            if exponent.is_empty() {
+                return fail(input, PROPERTY_VALUE);
+            }
+            text.push_str(str::from_utf8(exponent).unwrap());
+            return IResult::Done(after, Number::Decimal(text, true));
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    if digits == 0 {
+        return fail(input, PROPERTY_VALUE);
+    }
+    IResult::Done(&rest[i..], Number::Decimal(text, is_float))
+}
+
+fn char_literal(input: &[u8]) -> Res<'_, u64> {
+    let (mut rest, _) = next!(symbol(input, b'\'', APOSTROPHE));
+    let mut value: u64 = 0;
+    loop {
+        match rest.first() {
+            Some(&b'\'') => return IResult::Done(&rest[1..], value),
+            Some(_) => {
+                let (after, c) = next!(character(rest, b'\''));
+                value = (value << 8) | (c as u64 & 0xff);
+                rest = after;
+            }
+            None => return fail(rest, APOSTROPHE)
+        }
+    }
+}
+
+/// Parses a single, possibly escaped, character inside a string or character literal.
+fn character(input: &[u8], quote: u8) -> Res<'_, char> {
+    if input.first() != Some(&b'\\') {
+        // Decode one UTF-8 character. The input always starts at a character boundary.
+        let len = match input.first() {
+            Some(&c) if c < 0x80 => 1,
+            Some(&c) if c >= 0xf0 => 4,
+            Some(&c) if c >= 0xe0 => 3,
+            Some(_) => 2,
+            None => return fail(input, if quote == b'"' { QUOTE } else { APOSTROPHE })
+        };
+        let len = ::std::cmp::min(len, input.len());
+        return match str::from_utf8(&input[..len]) {
+            Ok(s) => IResult::Done(&input[len..], s.chars().next().unwrap()),
+            Err(_) => fail(input, ESCAPE)
+        };
+    }
+    let escaped = match input.get(1) {
+        Some(&c) => c,
+        None => return fail(input, ESCAPE)
+    };
+    let hex = |len: usize| -> Option<char> {
+        if input.len() < 2 + len {
+            return None;
+        }
+        let digits = &input[2..2 + len];
+        if !digits.iter().all(|&c| is_hex_digit(c)) {
+            return None;
+        }
+        u32::from_str_radix(str::from_utf8(digits).unwrap(), 16).ok()
+            .and_then(::std::char::from_u32)
+    };
+    let (len, c) = match escaped {
+        b'"' => (2, Some('"')),
+        b'\'' => (2, Some('\'')),
+        b'?' => (2, Some('?')),
+        b'\\' => (2, Some('\\')),
+        b'a' => (2, Some('\x07')),
+        b'b' => (2, Some('\x08')),
+        b'f' => (2, Some('\x0c')),
+        b'n' => (2, Some('\n')),
+        b'r' => (2, Some('\r')),
+        b't' => (2, Some('\t')),
+        b'v' => (2, Some('\x0b')),
+        b'x' => (4, hex(2)),
+        b'u' => (6, hex(4)),
+        b'U' => (8, hex(6)),
+        _ => (0, None)
+    };
+    match c {
+        Some(c) => IResult::Done(&input[len..], c),
+        None => fail(input, ESCAPE)
+    }
+}
+
+fn string_literal(input: &[u8]) -> Res<'_, String> {
+    let mut result = String::new();
+    let (mut rest, _) = next!(symbol(input, b'"', QUOTE));
+    loop {
+        match rest.first() {
+            Some(&b'"') => {
+                // Adjacent string literals are concatenated.
+                let (after, _) = next!(whitespace(&rest[1..]));
+                if after.first() == Some(&b'"') {
+                    rest = &after[1..];
+                } else {
+                    return IResult::Done(&rest[1..], result);
+                }
+            }
+            Some(_) => {
+                let (after, c) = next!(character(rest, b'"'));
+                result.push(c);
+                rest = after;
+            }
+            None => return fail(rest, QUOTE)
+        }
+    }
+}
+
+/// Parses a comma-separated list of values and appends them to `data`. When `limit` is set, at
+/// most that many values are parsed. Returns the number of values parsed.
+fn values<'a>(input: &'a [u8], data: &mut Data, limit: Option<usize>) -> Res<'a, usize> {
+    let mut rest = input;
+    let mut count = 0;
+    loop {
+        if count == 0 && rest.first() == Some(&b'}') {
+            return IResult::Done(rest, count);
+        }
+        if count > 0 {
+            let (after, _) = next!(whitespace(rest));
+            if after.first() != Some(&b',') || limit == Some(count) {
+                return IResult::Done(rest, count);
+            }
+            rest = next!(whitespace(&after[1..])).0;
+        }
+        rest = next!(value(rest, data)).0;
+        count += 1;
+    }
+}
+
+fn value<'a>(input: &'a [u8], data: &mut Data) -> Res<'a, ()> {
+    let kind = data.data_type();
+    macro_rules! push_number {
+        ($v:expr, $convert:expr) => ({
+            let (rest, number) = match number(input) {
+                IResult::Done(rest, number) => (rest, number),
+                _ => return fail(input, value_code(kind))
+            };
+            match $convert(&number) {
+                Some(value) => $v.push(value),
+                None => return fail(input, value_code(kind))
+            }
+            IResult::Done(rest, ())
+        })
+    }
+
+    match *data {
+        Data::Bool(ref mut v) => {
+            let (rest, b) = next!(bool_literal(input));
+            v.push(b);
+            IResult::Done(rest, ())
+        }
+        Data::Int8(ref mut v) => push_number!(v, |n: &Number| n.to_signed(8).map(|x| x as i8)),
+        Data::Int16(ref mut v) =>
+            push_number!(v, |n: &Number| n.to_signed(16).map(|x| x as i16)),
+        Data::Int32(ref mut v) =>
+            push_number!(v, |n: &Number| n.to_signed(32).map(|x| x as i32)),
+        Data::Int64(ref mut v) => push_number!(v, |n: &Number| n.to_signed(64)),
+        Data::UnsignedInt8(ref mut v) =>
+            push_number!(v, |n: &Number| n.to_unsigned(8).map(|x| x as u8)),
+        Data::UnsignedInt16(ref mut v) =>
+            push_number!(v, |n: &Number| n.to_unsigned(16).map(|x| x as u16)),
+        Data::UnsignedInt32(ref mut v) =>
+            push_number!(v, |n: &Number| n.to_unsigned(32).map(|x| x as u32)),
+        Data::UnsignedInt64(ref mut v) => push_number!(v, |n: &Number| n.to_unsigned(64)),
+        Data::Half(ref mut v) | Data::Float(ref mut v) =>
+            push_number!(v, |n: &Number| n.to_float(kind).map(|x| x as f32)),
+        Data::Double(ref mut v) => push_number!(v, |n: &Number| n.to_float(kind)),
+        Data::String(ref mut v) => {
+            let (rest, s) = next!(string_literal(input));
+            v.push(s);
+            IResult::Done(rest, ())
+        }
+        Data::Ref(ref mut v) => {
+            let (rest, r) = next!(reference(input));
+            v.push(r);
+            IResult::Done(rest, ())
+        }
+        Data::Type(ref mut v) => {
+            let (rest, t) = next!(data_type(input));
+            v.push(t);
+            IResult::Done(rest, ())
+        }
+    }
+}
+
+fn properties(input: &[u8]) -> Res<'_, Vec<Property>> {
+    let mut properties = vec![];
+    let (mut rest, _) = next!(whitespace(input));
+    if rest.first() == Some(&b')') {
+        return IResult::Done(&rest[1..], properties);
+    }
+    loop {
+        let (after, id) = match identifier(rest) {
+            IResult::Done(after, id) => (after, id),
+            _ => return fail(rest, PROPERTY)
+        };
+        let (after, _) = next!(whitespace(after));
+        let (after, value) = if after.first() == Some(&b'=') {
+            let (after, _) = next!(whitespace(&after[1..]));
+            next!(property_value(after))
+        } else {
+            (after, Literal::Bool(true))
+        };
+        properties.push(Property { identifier: id.to_string(), value });
+        let (after, _) = next!(whitespace(after));
+        match after.first() {
+            Some(&b',') => rest = next!(whitespace(&after[1..])).0,
+            Some(&b')') => return IResult::Done(&after[1..], properties),
+            _ => return fail(after, CLOSE_PAREN)
+        }
+    }
+}
+
+fn property_value(input: &[u8]) -> Res<'_, Literal> {
+    match input.first() {
+        Some(&b'"') => {
+            let (rest, s) = next!(string_literal(input));
+            IResult::Done(rest, Literal::String(s))
+        }
+        Some(&b'$') | Some(&b'%') => {
+            let (rest, r) = next!(reference(input));
+            IResult::Done(rest, Literal::Ref(r))
+        }
+        Some(&c) if is_alphabetic(c) || c == b'_' => {
+            let (rest, id) = next!(identifier(input));
+            match id {
+                "true" => IResult::Done(rest, Literal::Bool(true)),
+                "false" => IResult::Done(rest, Literal::Bool(false)),
+                "null" => IResult::Done(rest, Literal::Ref(Reference(vec![]))),
+                _ => match DataType::from_identifier(id) {
+                    Some(data_type) => IResult::Done(rest, Literal::Type(data_type)),
+                    None => fail(input, PROPERTY_VALUE)
+                }
+            }
+        }
+        _ => {
+            let (rest, number) = match number(input) {
+                IResult::Done(rest, number) => (rest, number),
+                _ => return fail(input, PROPERTY_VALUE)
+            };
+            let literal = match number {
+                Number::Decimal(ref text, true) => text.parse().ok().map(Literal::Float),
+                Number::Decimal(ref text, false) => match text.parse() {
+                    Ok(value) => Some(Literal::Integer(value)),
+                    Err(_) => text.parse().ok().map(Literal::Unsigned)
+                },
+                Number::Bits(false, value) if value <= i64::MAX as u64 =>
+                    Some(Literal::Integer(value as i64)),
+                Number::Bits(false, value) => Some(Literal::Unsigned(value)),
+                // The magnitude of the smallest `i64` is one above the largest.
+                Number::Bits(true, value) if value <= 1 << 63 =>
+                    Some(Literal::Integer((value as i64).wrapping_neg())),
+                Number::Bits(true, _) => None
+            };
+            match literal {
+                Some(literal) => IResult::Done(rest, literal),
+                None => fail(input, PROPERTY_VALUE)
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! The error type of the crate.
+//!
+//! Errors found in OpenGEX source text carry a `Location`, so that they can be displayed with an
+//! excerpt of the offending line:
+//!
+//! ```text
+//! VertexArray must contain floating-point data, found int32[3] data
+//!  --> line 7, column 9, in GeometryObject $geometry1 / Mesh / VertexArray
+//!   |
+//! 7 |         VertexArray (attrib = "position") {int32[3] {{0, 0, 0}, {1, 1, 1}}}
+//!   |         ^^^^^^^^^^^
+//! ```
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use ddl::{ self, DataType, Expected, Reference };
+
+/// An error produced while loading an OpenGEX file.
+#[derive(Debug)]
+pub struct Error {
+    /// What went wrong.
+    pub kind: ErrorKind,
+    /// Where it went wrong. This is `None` for errors that are not caused by the source text.
+    pub location: Option<Box<Location>>
+}
+
+/// The kinds of errors produced while loading an OpenGEX file.
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not valid OpenDDL.
+    Parse {
+        /// What the parser expected to find.
+        expected: Expected,
+        /// What the parser found instead, e.g. "`}`" or "end of file".
+        found: String
+    },
+    /// The file is valid OpenDDL, but a structure does not follow the OpenGEX specification.
+    Invalid(String),
+    /// A name or reference does not follow the OpenDDL naming rules, or refers to the wrong
+    /// kind of structure.
+    Reference(ReferenceError)
+}
+
+/// A problem with a name or a reference.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReferenceError {
+    /// No structure has the referenced name, or the reference is null.
+    Unresolved(Reference),
+    /// A global name is used twice in the file, or a local name is used twice in the same scope.
+    Duplicate(ddl::Name),
+    /// The reference refers to a structure of the wrong type.
+    WrongType {
+        /// The reference.
+        reference: Reference,
+        /// The identifiers of the structures the reference may refer to.
+        expected: &'static [&'static str],
+        /// The identifier of the referenced structure, or the data type of a primitive structure.
+        found: String
+    },
+    /// An object depends on itself, e.g. a bone node containing a geometry node skinned to it.
+    Cyclic(String)
+}
+
+/// The position of an error in OpenGEX source text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    /// The byte offset in the source text.
+    pub offset: usize,
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The column number in characters, starting at 1.
+    pub column: usize,
+    /// The enclosing structures, outermost first, each written as its identifier followed by its
+    /// name if it has one, e.g. `["GeometryObject $geometry1", "Mesh", "VertexArray"]`.
+    pub path: Vec<String>,
+    /// The text of the line containing the error.
+    pub source_line: String,
+    /// The number of characters to underline, starting at the column.
+    pub width: usize
+}
+
+impl Error {
+    /// Creates an error located at a byte offset of the source text.
+    pub fn new(kind: ErrorKind, source: &str, offset: usize) -> Error {
+        Error { kind, location: Some(Box::new(Location::new(source, offset))) }
+    }
+
+    /// Creates an error from an OpenDDL syntax error in the source text.
+    pub fn parse(err: ddl::ParseError, source: &str) -> Error {
+        let found = match token(source, err.offset) {
+            "" => "end of file".to_string(),
+            token => format!("`{}`", token)
+        };
+        Error::new(ErrorKind::Parse { expected: err.expected, found }, source, err.offset)
+    }
+}
+
+impl Location {
+    /// Computes the location of a byte offset in the source text.
+    pub fn new(source: &str, offset: usize) -> Location {
+        let offset = offset.min(source.len());
+        let before = &source[..offset];
+        let start = before.rfind('\n').map_or(0, |n| n + 1);
+        let end = source[offset..].find('\n').map_or(source.len(), |n| offset + n);
+        Location {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[start..].chars().count() + 1,
+            path: path(source, offset),
+            source_line: source[start..end].trim_end_matches('\r').to_string(),
+            width: token(source, offset).chars().count().max(1)
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::Io(ref err) => write!(f, "{}", err),
+            ErrorKind::Parse { ref expected, ref found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            ErrorKind::Invalid(ref message) => write!(f, "{}", message),
+            ErrorKind::Reference(ref err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl fmt::Display for ReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReferenceError::Unresolved(ref r) => write!(f, "unresolved reference {}", r),
+            ReferenceError::Duplicate(ref name) => write!(f, "duplicate name {}", name),
+            ReferenceError::WrongType { ref reference, expected, ref found } => {
+                write!(f, "{} refers to a {} instead of a {}",
+                    reference, found, expected.join(" or "))
+            }
+            ReferenceError::Cyclic(ref id) => write!(f, "cyclic reference to {}", id)
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let number = self.line.to_string();
+        let margin = " ".repeat(number.len());
+        write!(f, "{}--> line {}, column {}", margin, self.line, self.column)?;
+        if !self.path.is_empty() {
+            write!(f, ", in {}", self.path.join(" / "))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{} |", margin)?;
+        writeln!(f, "{} | {}", number, self.source_line)?;
+        // Tabs are kept in the indentation, so that the carets line up with the excerpt.
+        let indent: String = self.source_line.chars().take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        write!(f, "{} | {}{}", margin, indent, "^".repeat(self.width))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some(ref location) => write!(f, "{}\n{}", self.kind, location),
+            None => write!(f, "{}", self.kind)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.kind {
+            ErrorKind::Io(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error { kind: ErrorKind::Io(err), location: None }
+    }
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Returns the token starting at a byte offset: an identifier or number, or a single character.
+fn token(source: &str, offset: usize) -> &str {
+    let rest = &source[offset..];
+    match rest.chars().next() {
+        Some(c) if is_identifier(c) => {
+            &rest[..rest.find(|c| !is_identifier(c)).unwrap_or(rest.len())]
+        }
+        Some(c) => &rest[..c.len_utf8()],
+        None => ""
+    }
+}
+
+/// Finds the structures enclosing a byte offset of the source text.
+///
+/// The source is scanned rather than parsed, so that the path is also known for syntax errors.
+/// If a structure starts at the offset, it is included in the path.
+fn path(source: &str, offset: usize) -> Vec<String> {
+    // The enclosing structures, with `None` for primitive structures and subarrays.
+    let mut stack: Vec<Option<String>> = vec![];
+    // The identifier and name of the structure being read, until its opening brace.
+    let mut header: Option<String> = None;
+    let mut in_properties = false;
+    let mut chars = source.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if i >= offset {
+            if i == offset {
+                stack.push(structure_header(&source[i..]));
+            }
+            break;
+        }
+        match c {
+            '/' if chars.peek().map(|&(_, c)| c) == Some('/') => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' { break; }
+                }
+            }
+            '/' if chars.peek().map(|&(_, c)| c) == Some('*') => {
+                chars.next();
+                let mut star = false;
+                for (_, c) in chars.by_ref() {
+                    if star && c == '/' { break; }
+                    star = c == '*';
+                }
+            }
+            '"' | '\'' => {
+                while let Some((_, d)) = chars.next() {
+                    if d == '\\' {
+                        chars.next();
+                    } else if d == c {
+                        break;
+                    }
+                }
+            }
+            _ if in_properties => in_properties = c != ')',
+            '(' => in_properties = true,
+            '{' => stack.push(header.take()),
+            '}' => {
+                stack.pop();
+                header = None;
+            }
+            ',' => header = None,
+            '$' | '%' => {
+                let rest = &source[i + 1..];
+                let len = rest.find(|c| !is_identifier(c)).unwrap_or(rest.len());
+                if let Some(ref mut id) = header {
+                    id.push(' ');
+                    id.push_str(&source[i..i + 1 + len]);
+                }
+                while chars.peek().map_or(false, |&(_, c)| is_identifier(c)) {
+                    chars.next();
+                }
+            }
+            _ if is_identifier(c) => {
+                let rest = &source[i..];
+                let len = rest.find(|c| !is_identifier(c)).unwrap_or(rest.len());
+                header = if c.is_ascii_alphabetic() || c == '_' {
+                    structure_identifier(&rest[..len])
+                } else {
+                    None
+                };
+                while chars.peek().map_or(false, |&(_, c)| is_identifier(c)) {
+                    chars.next();
+                }
+            }
+            _ => {}
+        }
+    }
+    stack.into_iter().flatten().collect()
+}
+
+/// Returns the identifier if it names a derived structure rather than a primitive data type.
+fn structure_identifier(id: &str) -> Option<String> {
+    match (DataType::from_identifier(id), id) {
+        (Some(_), _) | (None, "true") | (None, "false") | (None, "null") => None,
+        _ => Some(id.to_string())
+    }
+}
+
+/// Returns the identifier and name of the derived structure starting the text, if there is one.
+fn structure_header(text: &str) -> Option<String> {
+    let len = text.find(|c| !is_identifier(c)).unwrap_or(text.len());
+    match text.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return None
+    }
+    let mut header = structure_identifier(&text[..len])?;
+    let rest = text[len..].trim_start();
+    if rest.starts_with('$') || rest.starts_with('%') {
+        let name_len = rest[1..].find(|c| !is_identifier(c)).unwrap_or(rest.len() - 1);
+        header.push(' ');
+        header.push_str(&rest[..1 + name_len]);
+    }
+    Some(header)
+}
@@ -0,0 +1,1212 @@
+//! Conversion of scenes to glTF 2.0.
+//!
+//! The scene is first normalized into the conventions of glTF: distances in meters, angles in
+//! radians, times in seconds, the y axis up and the z axis forward. Every node then becomes a glTF
+//! node with the same place in the hierarchy, its transformations combined into a translation, a
+//! rotation and a scale.
+//!
+//! The mesh of the first level of detail of every geometry object is split into triangles or
+//! lines, and every index array becomes a primitive with the material of the node for its index.
+//! Its morph targets are written as displacements from target 0, and its skin as joints and
+//! weights limited to four bones per vertex, with the inverse bind matrices of the skeleton. Only
+//! texture coordinates and colors with consecutive indices from 0 are written, as glTF requires.
+//!
+//! Materials become metallic-roughness materials without metalness. The "diffuse" color and
+//! texture give the base color, with the "opacity" color for alpha, the "specular_power" parameter
+//! gives the roughness, and the "emission" and "normal" textures are kept. Translations and scales
+//! of texture coordinates are written with `KHR_texture_transform`.
+//!
+//! Lights are written with `KHR_lights_punctual`, their cone angles taken from their angle
+//! attenuations and their range from the end of their distance attenuations. Cameras get a
+//! vertical field of view from their horizontal one and the aspect ratio of `Options`.
+//!
+//! Every animation clip becomes a glTF animation, with the transformations of every animated node
+//! sampled into translation, rotation and scale channels, and its morph weights into a weights
+//! channel. The tracks of a node are sampled at their own keys when glTF interpolates them the same
+//! way, which takes linear or constant curves animating translations, rotations about a fixed
+//! axis by less than half a turn per key, and scales, in that order. Other tracks, such as Bezier
+//! and TCB curves, are baked at the rate of `Options`. The animations of textures are left out.
+
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_4;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use animation::Pose;
+use primitive::vertex_count;
+use structure::*;
+use transform::{ invert, local_matrix, multiply, Matrix, IDENTITY };
+
+/// Options controlling the conversion of scenes to glTF.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// The number of keys per second of animations whose curves are baked, which must be finite
+    /// and positive.
+    pub rate: f32,
+    /// The aspect ratio of cameras, their width divided by their height. OpenGEX cameras have a
+    /// horizontal field of view, while glTF cameras have a vertical one.
+    pub aspect_ratio: f32
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            rate: 30.0,
+            aspect_ratio: 16.0 / 9.0
+        }
+    }
+}
+
+impl Scene {
+    /// Converts the scene to a glTF JSON document, and the binary buffer it refers to with the
+    /// URI `bin_uri`. The buffer is empty, and not referred to, when the scene has no data for it.
+    pub fn to_gltf(&self, bin_uri: &str, options: &Options) -> (String, Vec<u8>) {
+        let (mut document, buffer) = Converter::convert(self, options);
+        if !buffer.is_empty() {
+            document.push(("buffers".to_string(), Json::Array(vec![object(vec![
+                ("byteLength", buffer.len().into()),
+                ("uri", uri(bin_uri).into())
+            ])])));
+        }
+        (Json::Object(document).to_string(), buffer)
+    }
+
+    /// Converts the scene to a binary glTF file, holding the JSON document and the binary buffer.
+    pub fn to_glb(&self, options: &Options) -> Vec<u8> {
+        let (mut document, mut buffer) = Converter::convert(self, options);
+        if !buffer.is_empty() {
+            document.push(("buffers".to_string(), Json::Array(vec![object(vec![
+                ("byteLength", buffer.len().into())
+            ])])));
+        }
+        let mut json = Json::Object(document).to_string().into_bytes();
+        pad(&mut json, b' ');
+        pad(&mut buffer, 0);
+
+        let mut length = 12 + 8 + json.len();
+        if !buffer.is_empty() {
+            length += 8 + buffer.len();
+        }
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        if !buffer.is_empty() {
+            glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+            glb.extend_from_slice(b"BIN\0");
+            glb.extend_from_slice(&buffer);
+        }
+        glb
+    }
+
+    /// Writes the scene to a glTF file, and its binary buffer to a file with the same name and the
+    /// extension "bin".
+    pub fn to_gltf_file<P: AsRef<Path>>(&self, path: P, options: &Options) -> io::Result<()> {
+        let path = path.as_ref();
+        let bin_path = path.with_extension("bin");
+        let bin_uri = bin_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let (json, buffer) = self.to_gltf(bin_uri, options);
+        fs::write(path, json)?;
+        if !buffer.is_empty() {
+            fs::write(&bin_path, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the scene to a binary glTF file.
+    pub fn to_glb_file<P: AsRef<Path>>(&self, path: P, options: &Options) -> io::Result<()> {
+        fs::write(path, self.to_glb(options))
+    }
+}
+
+/// The target of buffer views holding vertex attributes.
+const ARRAY_BUFFER: u32 = 34962;
+/// The target of buffer views holding indices.
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+/// The accessors of a geometry object, shared by the glTF meshes using it with different
+/// materials.
+struct Geometry {
+    /// The glTF primitive mode.
+    mode: u32,
+    attributes: Vec<(String, usize)>,
+    targets: Vec<Vec<(String, usize)>>,
+    /// The morph target index of every glTF morph target.
+    target_indices: Vec<u32>,
+    /// The material index and the index accessor of every primitive.
+    primitives: Vec<(u32, usize)>,
+    skin: Option<usize>
+}
+
+/// The glTF objects of a scene being converted.
+struct Converter<'a> {
+    options: &'a Options,
+    /// The glTF index of every node.
+    nodes: HashMap<*const Nodes, usize>,
+    /// The glTF index of the node of every bone.
+    bones: HashMap<*const BoneNode, usize>,
+    buffer: Vec<u8>,
+    buffer_views: Vec<Json>,
+    accessors: Vec<Json>,
+    geometries: Vec<(*const GeometryObject, Option<Arc<Geometry>>)>,
+    meshes: Vec<Json>,
+    /// The glTF mesh of every geometry object with the materials of a node.
+    mesh_keys: Vec<(*const GeometryObject, Vec<(usize, *const Material)>)>,
+    materials: Vec<Json>,
+    material_keys: Vec<*const Material>,
+    textures: Vec<Json>,
+    images: Vec<Json>,
+    image_keys: Vec<String>,
+    cameras: Vec<Json>,
+    camera_keys: Vec<*const CameraObject>,
+    lights: Vec<Json>,
+    light_keys: Vec<*const LightObject>,
+    skins: Vec<Json>,
+    extensions: Vec<&'static str>
+}
+
+impl<'a> Converter<'a> {
+    /// Converts a scene to the fields of a glTF document without its buffers, and its binary
+    /// buffer.
+    fn convert(scene: &Scene, options: &Options) -> (Vec<(String, Json)>, Vec<u8>) {
+        let mut scene = scene.clone();
+        scene.normalize(&Metric {
+            distance: 1.0,
+            angle: 1.0,
+            time: 1.0,
+            up: Axis::Y,
+            forward: Some(Axis::Z),
+            others: scene.metric.others.clone()
+        });
+        let scene = &scene;
+        let world = scene.world_matrices();
+        let mut converter = Converter {
+            options,
+            nodes: HashMap::new(),
+            bones: HashMap::new(),
+            buffer: vec![],
+            buffer_views: vec![],
+            accessors: vec![],
+            geometries: vec![],
+            meshes: vec![],
+            mesh_keys: vec![],
+            materials: vec![],
+            material_keys: vec![],
+            textures: vec![],
+            images: vec![],
+            image_keys: vec![],
+            cameras: vec![],
+            camera_keys: vec![],
+            lights: vec![],
+            light_keys: vec![],
+            skins: vec![],
+            extensions: vec![]
+        };
+        for (i, &(node, _)) in world.iter().enumerate() {
+            converter.nodes.insert(node as *const Nodes, i);
+            if let Nodes::BoneNode(ref bone) = *node {
+                converter.bones.insert(&**bone as *const BoneNode, i);
+            }
+        }
+        let nodes: Vec<Json> = world.iter().map(|&(node, _)| converter.node(node)).collect();
+        let animations: Vec<Json> = scene.clips().iter()
+            .filter_map(|clip| converter.animation(clip))
+            .collect();
+        let roots: Vec<Json> = scene.nodes.iter()
+            .map(|node| converter.nodes[&(node as *const Nodes)].into())
+            .collect();
+
+        let mut document = vec![];
+        let mut field = |name: &str, value: Json| document.push((name.to_string(), value));
+        field("asset", object(vec![("version", "2.0".into()), ("generator", "opengex".into())]));
+        if !converter.extensions.is_empty() {
+            field("extensionsUsed", converter.extensions.iter().map(|&e| e.into()).collect());
+        }
+        field("scene", 0usize.into());
+        // A scene without nodes leaves them out, as glTF requires arrays to have elements.
+        let scene = if roots.is_empty() { vec![] } else { vec![("nodes", Json::Array(roots))] };
+        field("scenes", Json::Array(vec![object(scene)]));
+        let arrays = vec![
+            ("nodes", nodes),
+            ("meshes", converter.meshes),
+            ("materials", converter.materials),
+            ("textures", converter.textures),
+            ("images", converter.images),
+            ("cameras", converter.cameras),
+            ("skins", converter.skins),
+            ("animations", animations),
+            ("accessors", converter.accessors),
+            ("bufferViews", converter.buffer_views)
+        ];
+        for (name, array) in arrays {
+            if !array.is_empty() {
+                field(name, Json::Array(array));
+            }
+        }
+        if !converter.lights.is_empty() {
+            field("extensions", object(vec![("KHR_lights_punctual", object(vec![
+                ("lights", Json::Array(converter.lights))
+            ]))]));
+        }
+        (document, converter.buffer)
+    }
+
+    fn node(&mut self, node: &Nodes) -> Json {
+        let mut fields = vec![];
+        if let Some(name) = node.name() {
+            fields.push(("name", name.into()));
+        }
+        let children: Vec<Json> = node.children().iter()
+            .map(|child| self.nodes[&(child as *const Nodes)].into())
+            .collect();
+        if !children.is_empty() {
+            fields.push(("children", Json::Array(children)));
+        }
+        // Animations of glTF nodes target their translation, rotation and scale, so nodes never get
+        // a matrix.
+        let (translation, rotation, scale) = decompose(&node.local_matrix());
+        if translation != [0.0; 3] {
+            fields.push(("translation", floats(&translation)));
+        }
+        if rotation != [0.0, 0.0, 0.0, 1.0] {
+            fields.push(("rotation", floats(&rotation)));
+        }
+        if scale != [1.0; 3] {
+            fields.push(("scale", floats(&scale)));
+        }
+        match *node {
+            Nodes::GeometryNode(ref node) => {
+                if let Some((mesh, geometry)) = self.mesh(node) {
+                    fields.push(("mesh", mesh.into()));
+                    if let Some(skin) = geometry.skin {
+                        fields.push(("skin", skin.into()));
+                    }
+                    if !geometry.target_indices.is_empty() {
+                        let weights = morph_weights(node, &geometry, &Pose::default());
+                        fields.push(("weights", floats(&weights)));
+                    }
+                }
+            }
+            Nodes::CameraNode(ref node) => {
+                fields.push(("camera", self.camera(&node.camera).into()));
+            }
+            Nodes::LightNode(ref node) => {
+                let light = self.light(&node.light);
+                fields.push(("extensions", object(vec![("KHR_lights_punctual", object(vec![
+                    ("light", light.into())
+                ]))])));
+            }
+            _ => {}
+        }
+        object(fields)
+    }
+
+    /// Returns the glTF mesh of a geometry node, along with the accessors of its geometry, or
+    /// `None` if the geometry has no mesh that glTF can hold.
+    fn mesh(&mut self, node: &GeometryNode) -> Option<(usize, Arc<Geometry>)> {
+        let geometry = self.geometry(&node.geometry)?;
+        let key = (&*node.geometry as *const GeometryObject,
+            node.materials.iter().map(|(i, m)| (i, &**m as *const Material)).collect());
+        if let Some(mesh) = self.mesh_keys.iter().position(|k| *k == key) {
+            return Some((mesh, geometry));
+        }
+
+        let primitives: Vec<Json> = geometry.primitives.iter().map(|&(material, indices)| {
+            let mut fields = vec![
+                ("attributes", Json::Object(geometry.attributes.iter()
+                    .map(|&(ref name, accessor)| (name.clone(), accessor.into())).collect())),
+                ("indices", indices.into())
+            ];
+            if geometry.mode != 4 {
+                fields.push(("mode", geometry.mode.into()));
+            }
+            if let Some(material) = node.materials.get(material as usize) {
+                fields.push(("material", self.material(material).into()));
+            }
+            if !geometry.targets.is_empty() {
+                fields.push(("targets", geometry.targets.iter().map(|target| {
+                    Json::Object(target.iter()
+                        .map(|&(ref name, accessor)| (name.clone(), accessor.into())).collect())
+                }).collect()));
+            }
+            object(fields)
+        }).collect();
+        let mut fields = vec![];
+        if let Some(ref name) = node.name {
+            fields.push(("name", name.as_str().into()));
+        }
+        fields.push(("primitives", Json::Array(primitives)));
+        self.meshes.push(object(fields));
+        self.mesh_keys.push(key);
+        Some((self.meshes.len() - 1, geometry))
+    }
+
+    /// Returns the accessors of a geometry object, writing them on its first use.
+    fn geometry(&mut self, geometry: &GeometryObject) -> Option<Arc<Geometry>> {
+        let key = geometry as *const GeometryObject;
+        if let Some((_, converted)) = self.geometries.iter().find(|g| g.0 == key) {
+            return converted.clone();
+        }
+        let converted = self.convert_geometry(geometry).map(Arc::new);
+        self.geometries.push((key, converted.clone()));
+        converted
+    }
+
+    fn convert_geometry(&mut self, geometry: &GeometryObject) -> Option<Geometry> {
+        let source = geometry.meshes.values().next()?;
+        let (mode, corners, mesh) = match (source.to_triangle_list(), source.to_line_list()) {
+            (Some(triangles), _) => (4, 3, triangles),
+            (None, Some(lines)) => (1, 2, lines),
+            (None, None) => (0, 1, source.clone())
+        };
+        let count = vertex_count(&mesh);
+        let array = |attrib: &str, index: u32, morph: u32| mesh.vertex_arrays.iter()
+            .find(|a| a.attrib == attrib && a.index == index && a.morph == morph);
+        let values = |array: &VertexArray, n: usize| -> Vec<f32> {
+            let data = array.data.to_f32();
+            let components = array.components;
+            (0..count * n).map(|i| {
+                let (v, k) = (i / n, i % n);
+                if k < components {
+                    data.get(v * components + k).cloned().unwrap_or(0.0)
+                } else {
+                    0.0
+                }
+            }).collect()
+        };
+        let position = array("position", 0, 0)?;
+        if count == 0 {
+            return None;
+        }
+
+        // The primitives of every index array, without restarts or indices out of the vertices.
+        let mut primitives = vec![];
+        let arrays: Vec<(u32, FrontFace, Vec<u64>)> = if mesh.index_arrays.is_empty() {
+            vec![(0, FrontFace::CounterClockwise, (0..count as u64).collect())]
+        } else {
+            mesh.index_arrays.iter().map(|a| {
+                let indices = a.data.to_u64().into_iter().filter(|&i| Some(i) != a.restart);
+                (a.material, a.front_face, indices.collect())
+            }).collect()
+        };
+        for (material, front_face, indices) in arrays {
+            let mut kept = vec![];
+            for primitive in indices.chunks(corners) {
+                if primitive.len() == corners && primitive.iter().all(|&i| (i as usize) < count) {
+                    kept.extend_from_slice(primitive);
+                    if corners == 3 && front_face == FrontFace::Clockwise {
+                        let len = kept.len();
+                        kept.swap(len - 2, len - 1);
+                    }
+                }
+            }
+            if !kept.is_empty() {
+                primitives.push((material, self.indices(count, &kept)));
+            }
+        }
+        if primitives.is_empty() {
+            return None;
+        }
+
+        let mut attributes = vec![];
+        let positions = values(position, 3);
+        attributes.push(("POSITION".to_string(),
+            self.floats(&positions, "VEC3", Some(ARRAY_BUFFER), true)));
+        let normals = array("normal", 0, 0).map(|a| {
+            let mut normals = values(a, 3);
+            for n in normals.chunks_mut(3) {
+                let unit = normalize([n[0], n[1], n[2]]).unwrap_or([0.0, 0.0, 1.0]);
+                n.copy_from_slice(&unit);
+            }
+            normals
+        });
+        if let Some(ref normals) = normals {
+            attributes.push(("NORMAL".to_string(),
+                self.floats(normals, "VEC3", Some(ARRAY_BUFFER), false)));
+            if let Some(tangent) = array("tangent", 0, 0) {
+                let tangents = values(tangent, 3);
+                let bitangents = array("bitangent", 0, 0).map(|a| values(a, 3));
+                let mut values = Vec::with_capacity(count * 4);
+                for v in 0..count {
+                    let n = [normals[v * 3], normals[v * 3 + 1], normals[v * 3 + 2]];
+                    let t = [tangents[v * 3], tangents[v * 3 + 1], tangents[v * 3 + 2]];
+                    let t = normalize(t).unwrap_or_else(|| perpendicular(n));
+                    // The bitangent is the cross product of the normal and the tangent, times w.
+                    let w = match bitangents {
+                        Some(ref b) => {
+                            let b = [b[v * 3], b[v * 3 + 1], b[v * 3 + 2]];
+                            if dot(cross(n, t), b) < 0.0 { -1.0 } else { 1.0 }
+                        }
+                        None => 1.0
+                    };
+                    values.extend_from_slice(&[t[0], t[1], t[2], w]);
+                }
+                attributes.push(("TANGENT".to_string(),
+                    self.floats(&values, "VEC4", Some(ARRAY_BUFFER), false)));
+            }
+        }
+        // glTF texture coordinates start at the top of the image, and OpenGEX ones at the bottom.
+        let mut index = 0;
+        while let Some(texcoord) = array("texcoord", index, 0) {
+            let mut values = values(texcoord, 2);
+            for uv in values.chunks_mut(2) {
+                uv[1] = 1.0 - uv[1];
+            }
+            attributes.push((format!("TEXCOORD_{}", index),
+                self.floats(&values, "VEC2", Some(ARRAY_BUFFER), false)));
+            index += 1;
+        }
+        let mut index = 0;
+        while let Some(color) = array("color", index, 0) {
+            let (n, kind) = if color.components >= 4 { (4, "VEC4") } else { (3, "VEC3") };
+            let values: Vec<f32> = values(color, n).iter().map(|c| c.clamp(0.0, 1.0)).collect();
+            attributes.push((format!("COLOR_{}", index),
+                self.floats(&values, kind, Some(ARRAY_BUFFER), false)));
+            index += 1;
+        }
+        for other in mesh.vertex_arrays.iter().filter(|a| a.morph == 0) {
+            let known = ["position", "normal", "tangent", "bitangent", "texcoord", "color"];
+            if known.contains(&other.attrib.as_str()) || other.components == 0 ||
+                other.components > 4
+            {
+                continue;
+            }
+            let kind = ["SCALAR", "VEC2", "VEC3", "VEC4"][other.components - 1];
+            let values = values(other, other.components);
+            attributes.push((format!("_{}_{}", other.attrib.to_uppercase(), other.index),
+                self.floats(&values, kind, Some(ARRAY_BUFFER), false)));
+        }
+
+        let skin = mesh.skin.as_ref().and_then(|skin| self.skin(skin, count));
+        if let Some((_, joints, weights)) = skin {
+            attributes.push(("JOINTS_0".to_string(), joints));
+            attributes.push(("WEIGHTS_0".to_string(), weights));
+        }
+
+        // Absolute targets are written relative to target 0, which assumes that the weights of
+        // the targets, target 0 included, add up to 1.
+        let mut morphs: Vec<u32> = mesh.vertex_arrays.iter().map(|a| a.morph)
+            .filter(|&m| m != 0).collect();
+        morphs.sort();
+        morphs.dedup();
+        let mut targets = vec![];
+        let mut target_indices = vec![];
+        for morph in morphs {
+            let base = geometry.morphs.get(morph as usize)
+                .and_then(|m| m.base_target_index)
+                .unwrap_or(0);
+            let mut target = vec![];
+            for &(attrib, name) in &[("position", "POSITION"), ("normal", "NORMAL")] {
+                let matches = |a: &&VertexArray| array(attrib, 0, 0).map_or(false, |zero| {
+                    a.components == zero.components && a.data.len() == zero.data.len()
+                });
+                let (values_of, base_of) = match (array(attrib, 0, morph).filter(matches),
+                    array(attrib, 0, base).filter(matches))
+                {
+                    (Some(a), Some(b)) => (values(a, 3), values(b, 3)),
+                    _ => continue
+                };
+                let displacements: Vec<f32> = values_of.iter().zip(&base_of)
+                    .map(|(a, b)| a - b)
+                    .collect();
+                target.push((name.to_string(), self.floats(&displacements, "VEC3",
+                    Some(ARRAY_BUFFER), name == "POSITION")));
+            }
+            if !target.is_empty() {
+                targets.push(target);
+                target_indices.push(morph);
+            }
+        }
+
+        Some(Geometry {
+            mode,
+            attributes,
+            targets,
+            target_indices,
+            primitives,
+            skin: skin.map(|s| s.0)
+        })
+    }
+
+    /// Writes the skin of a mesh with `count` vertices, returning its index and the accessors of
+    /// the joints and weights of the vertices, or `None` if a bone is missing from the nodes.
+    fn skin(&mut self, skin: &Skin, count: usize) -> Option<(usize, usize, usize)> {
+        let joints: Vec<usize> = skin.skeleton.bone_ref_array.iter()
+            .map(|bone| self.bones.get(&(&**bone as *const BoneNode)).cloned())
+            .collect::<Option<_>>()?;
+        if joints.is_empty() {
+            return None;
+        }
+        // The inverse bind matrix of a joint takes the vertices from the bind pose of the mesh to
+        // the space of the joint in the bind pose of the skeleton.
+        let transform = skin.transform.as_ref().map_or(IDENTITY, |t| t.0);
+        let mut matrices = vec![];
+        for i in 0..joints.len() {
+            let bind = skin.skeleton.transforms.get(i).map_or(IDENTITY, |t| t.0);
+            let inverse = invert(&bind).unwrap_or(IDENTITY);
+            matrices.extend_from_slice(&multiply(&inverse, &transform));
+        }
+        let inverse_bind_matrices = self.floats(&matrices, "MAT4", None, false);
+
+        let influences = skin.limit_influences::<4>();
+        let mut indices = Vec::with_capacity(count * 4);
+        let mut weights = Vec::with_capacity(count * 4);
+        for v in 0..count {
+            let mut i = influences.indices.get(v).cloned().unwrap_or([0; 4]);
+            let mut w = influences.weights.get(v).cloned().unwrap_or([0.0; 4]);
+            for k in 0..4 {
+                if i[k] as usize >= joints.len() {
+                    i[k] = 0;
+                    w[k] = 0.0;
+                }
+            }
+            // glTF requires the weights of every vertex to add up to 1.
+            let sum: f32 = w.iter().sum();
+            if sum > 0.0 {
+                w.iter_mut().for_each(|w| *w /= sum);
+            } else {
+                w = [1.0, 0.0, 0.0, 0.0];
+            }
+            indices.extend_from_slice(&i);
+            weights.extend_from_slice(&w);
+        }
+        let mut bytes = Vec::with_capacity(indices.len() * 2);
+        for i in &indices {
+            bytes.extend_from_slice(&i.to_le_bytes());
+        }
+        let view = self.view(&bytes, Some(ARRAY_BUFFER));
+        let joints_accessor = self.accessor(view, UNSIGNED_SHORT, count, "VEC4", None);
+        let weights_accessor = self.floats(&weights, "VEC4", Some(ARRAY_BUFFER), false);
+
+        self.skins.push(object(vec![
+            ("inverseBindMatrices", inverse_bind_matrices.into()),
+            ("joints", joints.iter().map(|&j| j.into()).collect())
+        ]));
+        Some((self.skins.len() - 1, joints_accessor, weights_accessor))
+    }
+
+    fn material(&mut self, material: &Arc<Material>) -> usize {
+        let key = &**material as *const Material;
+        if let Some(index) = self.material_keys.iter().position(|&k| k == key) {
+            return index;
+        }
+        let color = |key: &str| material.color.get(key).map(|c| match *c {
+            Color::Rgb(r, g, b) => [r, g, b, 1.0],
+            Color::Rgba(r, g, b, a) => [r, g, b, a]
+        });
+        let diffuse = color("diffuse").unwrap_or([1.0; 4]);
+        let opacity = color("opacity").map_or(1.0, |c| (c[0] + c[1] + c[2]) / 3.0);
+        let alpha = (diffuse[3] * opacity).clamp(0.0, 1.0);
+        let base_color = [diffuse[0].clamp(0.0, 1.0), diffuse[1].clamp(0.0, 1.0),
+            diffuse[2].clamp(0.0, 1.0), alpha];
+
+        let mut pbr = vec![("baseColorFactor", floats(&base_color))];
+        if let Some(texture) = material.texture.get("diffuse") {
+            pbr.push(("baseColorTexture", self.texture(texture)));
+        }
+        pbr.push(("metallicFactor", 0.0.into()));
+        // The usual conversion of a Blinn-Phong exponent to a roughness.
+        if let Some(&power) = material.param.get("specular_power") {
+            let roughness = (2.0 / (power.max(0.0) + 2.0)).sqrt();
+            pbr.push(("roughnessFactor", roughness.into()));
+        }
+
+        let mut fields = vec![];
+        if let Some(ref name) = material.name {
+            fields.push(("name", name.as_str().into()));
+        }
+        fields.push(("pbrMetallicRoughness", object(pbr)));
+        if let Some(texture) = material.texture.get("normal") {
+            fields.push(("normalTexture", self.texture(texture)));
+        }
+        let emission = color("emission");
+        if let Some(texture) = material.texture.get("emission") {
+            fields.push(("emissiveTexture", self.texture(texture)));
+        }
+        if emission.is_some() || material.texture.contains_key("emission") {
+            let e = emission.unwrap_or([1.0; 4]);
+            let e = [e[0].clamp(0.0, 1.0), e[1].clamp(0.0, 1.0), e[2].clamp(0.0, 1.0)];
+            fields.push(("emissiveFactor", floats(&e)));
+        }
+        if alpha < 1.0 {
+            fields.push(("alphaMode", "BLEND".into()));
+        }
+        if material.two_sided {
+            fields.push(("doubleSided", true.into()));
+        }
+        self.materials.push(object(fields));
+        self.material_keys.push(key);
+        self.materials.len() - 1
+    }
+
+    /// Returns the texture info of a material texture, writing its texture and image on their
+    /// first use.
+    fn texture(&mut self, texture: &Texture) -> Json {
+        let index = match self.image_keys.iter().position(|k| *k == texture.file_name) {
+            Some(index) => index,
+            None => {
+                self.images.push(object(vec![("uri", uri(&texture.file_name).into())]));
+                self.textures.push(object(vec![("source", self.image_keys.len().into())]));
+                self.image_keys.push(texture.file_name.clone());
+                self.image_keys.len() - 1
+            }
+        };
+        let mut fields = vec![("index", index.into())];
+        if texture.texcoord != 0 {
+            fields.push(("texCoord", texture.texcoord.into()));
+        }
+        // Only translations and scales can be written, as glTF and OpenGEX texture coordinates
+        // have their vertical axis in opposite directions.
+        let m = local_matrix(texture.transformations.iter().map(|t| &**t));
+        if m[1] == 0.0 && m[4] == 0.0 && (m[0], m[5], m[12], m[13]) != (1.0, 1.0, 0.0, 0.0) {
+            let offset = [m[12], 1.0 - m[5] - m[13]];
+            let scale = [m[0], m[5]];
+            fields.push(("extensions", object(vec![("KHR_texture_transform", object(vec![
+                ("offset", floats(&offset)),
+                ("scale", floats(&scale))
+            ]))])));
+            self.extension("KHR_texture_transform");
+        }
+        object(fields)
+    }
+
+    fn camera(&mut self, camera: &Arc<CameraObject>) -> usize {
+        let key = &**camera as *const CameraObject;
+        if let Some(index) = self.camera_keys.iter().position(|&k| k == key) {
+            return index;
+        }
+        let fov = camera.params.get("fov").cloned().filter(|&f| f > 0.0).unwrap_or(1.0);
+        let aspect_ratio = self.options.aspect_ratio;
+        let yfov = 2.0 * ((fov / 2.0).tan() / aspect_ratio).atan();
+        let znear = camera.params.get("near").cloned().filter(|&n| n > 0.0).unwrap_or(0.1);
+        let mut perspective = vec![
+            ("aspectRatio", aspect_ratio.into()),
+            ("yfov", yfov.into()),
+            ("znear", znear.into())
+        ];
+        if let Some(&zfar) = camera.params.get("far").filter(|&&f| f > znear) {
+            perspective.push(("zfar", zfar.into()));
+        }
+        self.cameras.push(object(vec![
+            ("type", "perspective".into()),
+            ("perspective", object(perspective))
+        ]));
+        self.camera_keys.push(key);
+        self.cameras.len() - 1
+    }
+
+    fn light(&mut self, light: &Arc<LightObject>) -> usize {
+        let key = &**light as *const LightObject;
+        if let Some(index) = self.light_keys.iter().position(|&k| k == key) {
+            return index;
+        }
+        let color = match light.colors.get("light") {
+            Some(&Color::Rgb(r, g, b)) | Some(&Color::Rgba(r, g, b, _)) => [r, g, b],
+            None => [1.0; 3]
+        };
+        // glTF light colors are at most 1, so brighter colors go into the intensity.
+        let brightest = color.iter().cloned().fold(1.0, f32::max);
+        let color = [color[0].max(0.0) / brightest, color[1].max(0.0) / brightest,
+            color[2].max(0.0) / brightest];
+        let intensity = light.params.get("intensity").cloned().unwrap_or(1.0).max(0.0);
+        let kind = match light.light_type {
+            LightType::Infinite => "directional",
+            LightType::Point => "point",
+            LightType::Spot => "spot"
+        };
+        let mut fields = vec![
+            ("type", kind.into()),
+            ("color", floats(&color)),
+            ("intensity", (intensity * brightest).into())
+        ];
+        let range = light.attenuations.iter()
+            .filter(|a| a.kind == AttenuationKind::Distance)
+            .filter_map(|a| a.params.get("end").cloned())
+            .find(|&end| end > 0.0);
+        if let (Some(range), false) = (range, light.light_type == LightType::Infinite) {
+            fields.push(("range", range.into()));
+        }
+        if light.light_type == LightType::Spot {
+            // The angles at which the light starts to fade out, and at which it is gone.
+            let (mut inner, mut outer) = (0.0, FRAC_PI_4);
+            for atten in &light.attenuations {
+                let angle = |value: f32| match atten.kind {
+                    AttenuationKind::CosAngle => value.clamp(-1.0, 1.0).acos(),
+                    _ => value
+                };
+                if atten.kind != AttenuationKind::Distance {
+                    if let Some(&begin) = atten.params.get("begin") {
+                        inner = angle(begin);
+                    }
+                    if let Some(&end) = atten.params.get("end") {
+                        outer = angle(end);
+                    }
+                }
+            }
+            let outer = outer.clamp(1e-3, ::std::f32::consts::FRAC_PI_2);
+            let inner = if inner >= 0.0 && inner < outer { inner } else { 0.0 };
+            fields.push(("spot", object(vec![
+                ("innerConeAngle", inner.into()),
+                ("outerConeAngle", outer.into())
+            ])));
+        }
+        self.lights.push(object(fields));
+        self.light_keys.push(key);
+        self.extension("KHR_lights_punctual");
+        self.lights.len() - 1
+    }
+
+    /// Converts the animations of a clip, or returns `None` if they animate nothing glTF can hold.
+    fn animation(&mut self, clip: &::animation::ClipAnimations) -> Option<Json> {
+        // The animations of every node, in the order of the nodes.
+        let mut nodes: Vec<(&Nodes, Vec<&Animation>)> = vec![];
+        for &(node, animation) in &clip.animations {
+            match nodes.iter_mut().find(|n| ::std::ptr::eq(n.0, node)) {
+                Some(n) => n.1.push(animation),
+                None => nodes.push((node, vec![animation]))
+            }
+        }
+        // glTF animations cannot start before 0.
+        let shift = if clip.begin < 0.0 { -clip.begin } else { 0.0 };
+        let mut samplers = vec![];
+        let mut channels = vec![];
+        for (node, animations) in nodes {
+            let tracks: Vec<&Track> = animations.iter().flat_map(|a| a.tracks.iter()).collect();
+            let transformations = tracks.iter()
+                .any(|t| matches!(t.target, TrackTarget::Transformation(_)));
+            let weights = tracks.iter().any(|t| matches!(t.target, TrackTarget::MorphWeight(_)));
+            let geometry = match *node {
+                Nodes::GeometryNode(ref g) if weights => self.geometry(&g.geometry)
+                    .filter(|g| !g.target_indices.is_empty())
+                    .map(|geometry| (g, geometry)),
+                _ => None
+            };
+            let range = animations.iter().filter_map(|a| a.time_range())
+                .fold(None, |range: Option<(f32, f32)>, (b, e)| Some(match range {
+                    Some((begin, end)) => (begin.min(b), end.max(e)),
+                    None => (b, e)
+                }));
+            let (begin, end) = match range {
+                Some(range) if transformations || geometry.is_some() => range,
+                _ => continue
+            };
+
+            let exact = is_exact(node.transformations(), &tracks);
+            let step = exact && tracks.iter().all(|t| matches!(t.value, Value::Constant(_)));
+            let mut times = vec![begin, end];
+            if exact {
+                for track in &tracks {
+                    times.extend((0..track.time.len()).map(|i| track.time.key(i))
+                        .filter(|&t| t > begin && t < end));
+                }
+            } else {
+                times.extend(::bake::key_times(begin, end, self.options.rate));
+            }
+            times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Equal));
+            let mut input: Vec<f32> = times.iter().map(|t| t + shift).collect();
+            input.dedup();
+            let times: Vec<f32> = input.iter().map(|t| t - shift).collect();
+            let poses: Vec<Pose> = times.iter().map(|&t| {
+                let mut pose = Pose::default();
+                for animation in &animations {
+                    animation.sample_into(t, &mut pose);
+                }
+                pose
+            }).collect();
+            let input = self.floats(&input, "SCALAR", None, true);
+            let interpolation = if step { "STEP" } else { "LINEAR" };
+            let target = self.nodes[&(node as *const Nodes)];
+            let channel = |path: &str, output: usize,
+                samplers: &mut Vec<Json>, channels: &mut Vec<Json>|
+            {
+                channels.push(object(vec![
+                    ("sampler", samplers.len().into()),
+                    ("target", object(vec![("node", target.into()), ("path", path.into())]))
+                ]));
+                samplers.push(object(vec![
+                    ("input", input.into()),
+                    ("interpolation", interpolation.into()),
+                    ("output", output.into())
+                ]));
+            };
+
+            if transformations {
+                let mut translations = vec![];
+                let mut rotations: Vec<f32> = vec![];
+                let mut scales = vec![];
+                for pose in &poses {
+                    let (t, mut r, s) = decompose(&pose.local_matrix(node.transformations()));
+                    // Successive rotations take the shortest path.
+                    let n = rotations.len();
+                    if n >= 4 && dot4(&rotations[n - 4..], &r) < 0.0 {
+                        r = [-r[0], -r[1], -r[2], -r[3]];
+                    }
+                    translations.extend_from_slice(&t);
+                    rotations.extend_from_slice(&r);
+                    scales.extend_from_slice(&s);
+                }
+                let output = self.floats(&translations, "VEC3", None, false);
+                channel("translation", output, &mut samplers, &mut channels);
+                let output = self.floats(&rotations, "VEC4", None, false);
+                channel("rotation", output, &mut samplers, &mut channels);
+                let output = self.floats(&scales, "VEC3", None, false);
+                channel("scale", output, &mut samplers, &mut channels);
+            }
+            if let Some((node, geometry)) = geometry {
+                let weights: Vec<f32> = poses.iter()
+                    .flat_map(|pose| morph_weights(node, &geometry, pose))
+                    .collect();
+                let output = self.floats(&weights, "SCALAR", None, false);
+                channel("weights", output, &mut samplers, &mut channels);
+            }
+        }
+        if channels.is_empty() {
+            return None;
+        }
+        let mut fields = vec![];
+        if let Some(name) = clip.name() {
+            fields.push(("name", name.into()));
+        }
+        fields.push(("channels", Json::Array(channels)));
+        fields.push(("samplers", Json::Array(samplers)));
+        Some(object(fields))
+    }
+
+    fn extension(&mut self, name: &'static str) {
+        if !self.extensions.contains(&name) {
+            self.extensions.push(name);
+        }
+    }
+
+    /// Appends data to the buffer, aligned to 4 bytes, and returns its buffer view.
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        pad(&mut self.buffer, 0);
+        let mut fields = vec![
+            ("buffer", 0usize.into()),
+            ("byteOffset", self.buffer.len().into()),
+            ("byteLength", bytes.len().into())
+        ];
+        if let Some(target) = target {
+            fields.push(("target", target.into()));
+        }
+        self.buffer.extend_from_slice(bytes);
+        self.buffer_views.push(object(fields));
+        self.buffer_views.len() - 1
+    }
+
+    fn accessor(&mut self, view: usize, component_type: u32, count: usize, kind: &str,
+        bounds: Option<(Vec<f32>, Vec<f32>)>) -> usize
+    {
+        let mut fields = vec![
+            ("bufferView", view.into()),
+            ("componentType", component_type.into()),
+            ("count", count.into()),
+            ("type", kind.into())
+        ];
+        if let Some((min, max)) = bounds {
+            fields.push(("min", floats(&min)));
+            fields.push(("max", floats(&max)));
+        }
+        self.accessors.push(object(fields));
+        self.accessors.len() - 1
+    }
+
+    /// Writes an accessor of floats, with the bounds of its components if `bounds` is set.
+    fn floats(&mut self, values: &[f32], kind: &str, target: Option<u32>, bounds: bool) -> usize {
+        let n = match kind {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            _ => 16
+        };
+        let mut bytes = Vec::with_capacity(values.len() * 4);
+        for v in values {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        let view = self.view(&bytes, target);
+        let bounds = if bounds {
+            let mut min = vec![f32::INFINITY; n];
+            let mut max = vec![f32::NEG_INFINITY; n];
+            for element in values.chunks(n) {
+                for k in 0..n {
+                    min[k] = min[k].min(element[k]);
+                    max[k] = max[k].max(element[k]);
+                }
+            }
+            Some((min, max))
+        } else {
+            None
+        };
+        self.accessor(view, FLOAT, values.len() / n, kind, bounds)
+    }
+
+    /// Writes an accessor of indices into `count` vertices, with 16 bits per index when they fit
+    /// without reaching the largest 16-bit value, which glTF forbids.
+    fn indices(&mut self, count: usize, indices: &[u64]) -> usize {
+        let (bytes, component_type) = if count <= u16::MAX as usize {
+            (indices.iter().flat_map(|&i| (i as u16).to_le_bytes().to_vec()).collect::<Vec<_>>(),
+                UNSIGNED_SHORT)
+        } else {
+            (indices.iter().flat_map(|&i| (i as u32).to_le_bytes().to_vec()).collect(),
+                UNSIGNED_INT)
+        };
+        let view = self.view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessor(view, component_type, indices.len(), "SCALAR", None)
+    }
+}
+
+/// Returns whether sampling the tracks of a node at their own keys reproduces them, once glTF
+/// interpolates the translation, rotation and scale of the node linearly between the keys.
+///
+/// This holds for linear and constant curves when the transformations of the node are
+/// translations, then rotations, then scales, each kind animated by at most one track. An animated
+/// rotation must turn about a fixed axis by less than half a turn between keys, as glTF takes the
+/// shortest path between rotations.
+fn is_exact(transformations: &[Arc<Transformation>], tracks: &[&Track]) -> bool {
+    let kind = |t: &Transformation| match *t {
+        Transformation::Translation(_) => Some(0),
+        Transformation::Rotation(_) => Some(1),
+        Transformation::Scale(_) => Some(2),
+        Transformation::Transform(_) => None
+    };
+    let kinds: Option<Vec<usize>> = transformations.iter().map(|t| kind(t)).collect();
+    let ordered = kinds.map_or(false, |kinds| kinds.windows(2).all(|k| k[0] <= k[1]));
+    let mut animated = [0; 3];
+    ordered && tracks.iter().all(|track| {
+        let linear = matches!(track.time, Time::Linear(_)) &&
+            matches!(track.value, Value::Linear(_) | Value::Constant(_));
+        let target = match track.target {
+            TrackTarget::Transformation(ref t) => t,
+            TrackTarget::MorphWeight(_) => return linear
+        };
+        let kind = match kind(target) {
+            Some(kind) => kind,
+            None => return false
+        };
+        animated[kind] += 1;
+        let turns = match (&**target, &track.value) {
+            (&Transformation::Rotation(Rotation::X(_)), &Value::Linear(ref angles)) |
+            (&Transformation::Rotation(Rotation::Y(_)), &Value::Linear(ref angles)) |
+            (&Transformation::Rotation(Rotation::Z(_)), &Value::Linear(ref angles)) => {
+                angles.windows(2).all(|a| (a[1] - a[0]).abs() < ::std::f32::consts::PI)
+            }
+            (&Transformation::Rotation(_), &Value::Linear(_)) => false,
+            _ => true
+        };
+        linear && turns && animated[kind] <= 1
+    })
+}
+
+/// Returns the weight of every glTF morph target of a geometry node in a pose.
+fn morph_weights(node: &GeometryNode, geometry: &Geometry, pose: &Pose) -> Vec<f32> {
+    geometry.target_indices.iter().map(|&index| {
+        node.morph_weights.iter().find(|w| w.target_index == index)
+            .map_or(0.0, |w| pose.morph_weight(w))
+    }).collect()
+}
+
+/// Splits a matrix into a translation, a rotation quaternion and a scale, applied in reverse
+/// order. Shears are lost.
+fn decompose(m: &Matrix) -> ([f32; 3], [f32; 4], [f32; 3]) {
+    let translation = [m[12], m[13], m[14]];
+    let columns = [[m[0], m[1], m[2]], [m[4], m[5], m[6]], [m[8], m[9], m[10]]];
+    let mut scale = [0.0; 3];
+    for k in 0..3 {
+        scale[k] = dot(columns[k], columns[k]).sqrt();
+    }
+    // A mirroring matrix gets a negative scale along x.
+    if dot(cross(columns[0], columns[1]), columns[2]) < 0.0 {
+        scale[0] = -scale[0];
+    }
+    if scale.contains(&0.0) {
+        return (translation, [0.0, 0.0, 0.0, 1.0], scale);
+    }
+    let r: Vec<[f32; 3]> = (0..3).map(|k| {
+        [columns[k][0] / scale[k], columns[k][1] / scale[k], columns[k][2] / scale[k]]
+    }).collect();
+    // The element in row i and column j of the rotation is r[j][i].
+    let trace = r[0][0] + r[1][1] + r[2][2];
+    let q = if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        [(r[1][2] - r[2][1]) / s, (r[2][0] - r[0][2]) / s, (r[0][1] - r[1][0]) / s, 0.25 * s]
+    } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+        let s = (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt() * 2.0;
+        [0.25 * s, (r[1][0] + r[0][1]) / s, (r[2][0] + r[0][2]) / s, (r[1][2] - r[2][1]) / s]
+    } else if r[1][1] > r[2][2] {
+        let s = (1.0 + r[1][1] - r[0][0] - r[2][2]).sqrt() * 2.0;
+        [(r[1][0] + r[0][1]) / s, 0.25 * s, (r[2][1] + r[1][2]) / s, (r[2][0] - r[0][2]) / s]
+    } else {
+        let s = (1.0 + r[2][2] - r[0][0] - r[1][1]).sqrt() * 2.0;
+        [(r[2][0] + r[0][2]) / s, (r[2][1] + r[1][2]) / s, 0.25 * s, (r[0][1] - r[1][0]) / s]
+    };
+    let length = dot4(&q, &q).sqrt();
+    let rotation = [q[0] / length, q[1] / length, q[2] / length, q[3] / length];
+    (translation, rotation, scale)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn dot4(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Returns the vector scaled to a length of 1, or `None` for the zero vector.
+fn normalize(a: [f32; 3]) -> Option<[f32; 3]> {
+    let length = dot(a, a).sqrt();
+    if length > 0.0 && length.is_finite() {
+        Some([a[0] / length, a[1] / length, a[2] / length])
+    } else {
+        None
+    }
+}
+
+/// Returns a unit vector perpendicular to a unit vector.
+fn perpendicular(n: [f32; 3]) -> [f32; 3] {
+    let axis = if n[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    let d = dot(n, axis);
+    normalize([axis[0] - n[0] * d, axis[1] - n[1] * d, axis[2] - n[2] * d])
+        .unwrap_or([1.0, 0.0, 0.0])
+}
+
+/// Pads data to a multiple of 4 bytes.
+fn pad(data: &mut Vec<u8>, byte: u8) {
+    while data.len() % 4 != 0 {
+        data.push(byte);
+    }
+}
+
+/// Returns a file name as a URI reference, with its path separators as slashes and the
+/// characters not allowed in URIs percent-encoded.
+fn uri(file_name: &str) -> String {
+    let mut uri = String::new();
+    for byte in file_name.replace('\\', "/").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    uri
+}
+
+/// A JSON value.
+#[derive(Clone, Debug, PartialEq)]
+enum Json {
+    Bool(bool),
+    Integer(u64),
+    Float(f32),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn floats(values: &[f32]) -> Json {
+    Json::Array(values.iter().map(|&v| v.into()).collect())
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Integer(value as u64)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Json {
+        Json::Integer(value as u64)
+    }
+}
+
+impl From<f32> for Json {
+    fn from(value: f32) -> Json {
+        Json::Float(value)
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(value: &'a str) -> Json {
+        Json::String(value.to_string())
+    }
+}
+
+impl ::std::iter::FromIterator<Json> for Json {
+    fn from_iter<I: IntoIterator<Item = Json>>(iter: I) -> Json {
+        Json::Array(iter.into_iter().collect())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Integer(i) => write!(f, "{}", i),
+            // JSON has no infinities or NaNs.
+            Json::Float(x) => write!(f, "{}", if x.is_finite() { x + 0.0 } else { 0.0 }),
+            Json::String(ref s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?
+                    }
+                }
+                f.write_str("\"")
+            }
+            Json::Array(ref values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(ref fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}:{}", Json::String(key.clone()), value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
@@ -4,5 +4,28 @@
 
 /// A library containing `VecMap`, a `HashMap`-like structure optimized for small integer keys.
 extern crate vec_map;
+/// A parser combinator library, used to parse the OpenDDL syntax OpenGEX is built on.
+#[macro_use]
+extern crate nom;
 
+pub use error::Error;
+
+pub mod animation;
+mod bake;
+pub mod ddl;
+pub mod error;
+pub mod gltf;
+mod morph;
+mod normalize;
+pub mod normals;
+mod obj;
+mod optimize;
+mod primitive;
+pub mod read;
+mod rewrite;
+pub mod skin;
 pub mod structure;
+pub mod transform;
+pub mod validate;
+mod weld;
+pub mod write;
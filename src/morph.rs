@@ -0,0 +1,100 @@
+//! Blending of morph targets.
+//!
+//! A mesh may hold vertex data for several morph targets, told apart by the `morph` property of
+//! each vertex array. The vertices of a geometry node are the weighted sum of its targets, using
+//! the MorphWeight structures of the node. An absolute target contributes its vertex data times its
+//! weight, while a relative target, one with a base target in its `Morph` structure, contributes
+//! the difference between its vertex data and the one of its base target.
+
+use std::collections::HashMap;
+
+use animation::Pose;
+use structure::*;
+
+/// The attributes blended between morph targets. Normals are normalized after blending.
+const BLENDED: &[&str] = &["position", "normal"];
+
+impl GeometryNode {
+    /// Returns the weight of every morph target of the node in a pose, keyed by target index.
+    ///
+    /// Target 0 has a weight of 1 unless the node has a MorphWeight structure for it, and the
+    /// other targets without a MorphWeight structure have a weight of 0.
+    pub fn morph_target_weights(&self, pose: &Pose) -> HashMap<u32, f32> {
+        let mut weights = HashMap::new();
+        weights.insert(0, 1.0);
+        for weight in &self.morph_weights {
+            weights.insert(weight.target_index, pose.morph_weight(weight));
+        }
+        weights
+    }
+
+    /// Blends the morph targets of the mesh with the given level of detail, with the morph weights
+    /// of the node sampled from all its animations at `time`, or with their own values without a
+    /// time. Returns `None` if the geometry has no mesh with that level of detail.
+    ///
+    /// The returned mesh only holds the vertex arrays of target 0, with their positions and
+    /// normals replaced by the blended ones. Targets without vertex data for an attribute, or whose
+    /// data does not have the same length as the one of target 0, are left out of the blending of
+    /// that attribute.
+    pub fn blend_morphs(&self, lod: usize, time: Option<f32>) -> Option<Mesh> {
+        let mut pose = Pose::default();
+        if let Some(time) = time {
+            for animation in &self.animations {
+                animation.sample_into(time, &mut pose);
+            }
+        }
+        self.blend_morphs_with(lod, &pose)
+    }
+
+    /// Blends the morph targets of the mesh with the given level of detail, with the morph weights
+    /// of the node in a pose, like `GeometryNode::blend_morphs`.
+    pub fn blend_morphs_with(&self, lod: usize, pose: &Pose) -> Option<Mesh> {
+        let mesh = self.geometry.meshes.get(lod)?;
+        let weights = self.morph_target_weights(pose);
+        let mut blended = Mesh {
+            vertex_arrays: mesh.vertex_arrays.iter().filter(|a| a.morph == 0).cloned().collect(),
+            ..mesh.clone()
+        };
+        for array in &mut blended.vertex_arrays {
+            if !BLENDED.contains(&array.attrib.as_str()) {
+                continue;
+            }
+            let target = |morph: u32| mesh.vertex_arrays.iter().find(|a| {
+                a.attrib == array.attrib && a.index == array.index && a.morph == morph &&
+                    a.components == array.components && a.data.len() == array.data.len()
+            });
+            let mut values = vec![0.0; array.data.len()];
+            for (&morph, &weight) in &weights {
+                let data = match target(morph) {
+                    Some(a) => a.data.to_f64(),
+                    None => continue
+                };
+                let base = self.geometry.morphs.get(morph as usize)
+                    .and_then(|m| m.base_target_index);
+                let base = match base {
+                    Some(base) => match target(base) {
+                        Some(a) => Some(a.data.to_f64()),
+                        None => continue
+                    },
+                    None => None
+                };
+                for (i, v) in values.iter_mut().enumerate() {
+                    let delta = data[i] - base.as_ref().map_or(0.0, |b| b[i]);
+                    *v += weight as f64 * delta;
+                }
+            }
+            if array.attrib == "normal" {
+                for n in values.chunks_mut(array.components) {
+                    let len = n.iter().map(|x| x * x).sum::<f64>().sqrt();
+                    if len > 0.0 {
+                        for x in n.iter_mut() {
+                            *x /= len;
+                        }
+                    }
+                }
+            }
+            array.data = array.data.with_values(values);
+        }
+        Some(blended)
+    }
+}
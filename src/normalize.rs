@@ -0,0 +1,446 @@
+//! Conversion of scenes between units and axis conventions.
+//!
+//! The Metric structures of a file tell in which units its values are expressed, and which
+//! directions are up and forward. `Scene::normalize` converts every value of a scene into the units
+//! and directions of another `Metric`, so that an application can load files from any exporter in
+//! its own conventions.
+//!
+//! Changing the up direction rotates the whole scene. The positions and normals of meshes are
+//! rotated, and node transformations are rotated along with them, so that the scene looks the same
+//! as before in its new coordinate system. As the object space of cameras and lights has a fixed
+//! orientation in OpenGEX, camera and light nodes get an extra `Transform` keeping them pointed in
+//! the same direction, and their children get the inverse of it.
+
+use std::sync::Arc;
+
+use rewrite::{ self, replace_targets, NodeKind, NodeParts, Rewrite };
+use structure::*;
+use transform::{ multiply, Matrix };
+
+impl Scene {
+    /// Converts the scene into the units and axis conventions of `target`, and replaces the metric
+    /// of the scene with it. The metrics with other keys are kept as they are.
+    ///
+    /// When both metrics have a forward direction, the scene is rotated so that both the up and the
+    /// forward directions match. Otherwise, only the up directions are matched, by the smallest
+    /// rotation turning one into the other.
+    ///
+    /// Bone nodes that are children of camera or light nodes are shared with skeletons, and do not
+    /// get the inverse of the extra camera or light orientation.
+    pub fn normalize(&mut self, target: &Metric) {
+        let basis = basis(&self.metric, target);
+        let angle = self.metric.angle / target.angle;
+        let time = self.metric.time / target.time;
+        let forward = self.metric.forward
+            .and_then(|f| Axis::from_vector(rotate(&basis, f.to_vector())));
+        let mut normalize = Normalize {
+            space: Convert {
+                basis,
+                distance: self.metric.distance / target.distance,
+                precise_distance: self.metric.distance as f64 / target.distance as f64,
+                angle,
+                time
+            },
+            texture: Convert { basis: IDENTITY, distance: 1.0, precise_distance: 1.0, angle, time }
+        };
+        rewrite::rewrite(self, &mut normalize);
+        self.metric = Metric {
+            distance: target.distance,
+            angle: target.angle,
+            time: target.time,
+            up: target.up,
+            forward,
+            others: self.metric.others.clone()
+        };
+    }
+}
+
+impl Axis {
+    /// Returns the unit vector pointing in the direction of the axis.
+    pub fn to_vector(self) -> [f32; 3] {
+        match self {
+            Axis::X => [1.0, 0.0, 0.0],
+            Axis::Y => [0.0, 1.0, 0.0],
+            Axis::Z => [0.0, 0.0, 1.0],
+            Axis::NegX => [-1.0, 0.0, 0.0],
+            Axis::NegY => [0.0, -1.0, 0.0],
+            Axis::NegZ => [0.0, 0.0, -1.0]
+        }
+    }
+
+    /// Returns the axis pointing in the direction of a unit vector, if there is one.
+    pub fn from_vector(v: [f32; 3]) -> Option<Axis> {
+        Some(match (v[0].round() as i32, v[1].round() as i32, v[2].round() as i32) {
+            (1, 0, 0) => Axis::X,
+            (0, 1, 0) => Axis::Y,
+            (0, 0, 1) => Axis::Z,
+            (-1, 0, 0) => Axis::NegX,
+            (0, -1, 0) => Axis::NegY,
+            (0, 0, -1) => Axis::NegZ,
+            _ => return None
+        })
+    }
+}
+
+/// A rotation, given by the images of the x, y and z axes.
+type Basis = [[f32; 3]; 3];
+
+const IDENTITY: Basis = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn rotate(basis: &Basis, v: [f32; 3]) -> [f32; 3] {
+    let mut r = [0.0; 3];
+    for (b, x) in basis.iter().zip(&v) {
+        for i in 0..3 {
+            r[i] += b[i] * x;
+        }
+    }
+    r
+}
+
+/// Returns the rotation turning the axis conventions of `from` into those of `to`.
+fn basis(from: &Metric, to: &Metric) -> Basis {
+    let (u, v) = (from.up.to_vector(), to.up.to_vector());
+    if let (Some(f), Some(g)) = (from.forward, to.forward) {
+        let (f, g) = (f.to_vector(), g.to_vector());
+        if dot(u, f) == 0.0 && dot(v, g) == 0.0 {
+            // Maps the frame (up, forward, up x forward) of `from` onto the one of `to`.
+            let (r, s) = (cross(u, f), cross(v, g));
+            let mut basis = IDENTITY;
+            for b in &mut basis {
+                let e = *b;
+                *b = rotate(&[v, g, s], [dot(e, u), dot(e, f), dot(e, r)]);
+            }
+            return basis;
+        }
+    }
+    let mut basis = IDENTITY;
+    let d = dot(u, v);
+    if d == 1.0 {
+        return basis;
+    }
+    // Rotates by 180 degrees about an axis perpendicular to both up directions, or by 90 degrees
+    // about their cross product.
+    let k = if d == -1.0 {
+        *IDENTITY.iter().find(|&&e| dot(e, u) == 0.0).unwrap()
+    } else {
+        cross(u, v)
+    };
+    for b in &mut basis {
+        let e = *b;
+        let (c, p) = (cross(k, e), dot(k, e));
+        for i in 0..3 {
+            b[i] = if d == -1.0 { 2.0 * k[i] * p - e[i] } else { k[i] * p + c[i] };
+        }
+    }
+    basis
+}
+
+/// The conversion of the values of one coordinate space.
+struct Convert {
+    basis: Basis,
+    distance: f32,
+    /// The distance scale in double precision, for double-precision vertex data.
+    precise_distance: f64,
+    angle: f32,
+    time: f32
+}
+
+impl Convert {
+    /// Returns the matrix rotating by the basis and scaling by `scale`.
+    fn matrix(&self, scale: f32) -> Matrix {
+        let mut m = [0.0; 16];
+        for c in 0..3 {
+            for r in 0..3 {
+                m[c * 4 + r] = self.basis[c][r] * scale;
+            }
+        }
+        m[15] = 1.0;
+        m
+    }
+
+    /// Returns the inverse of `matrix`.
+    fn inverse(&self, scale: f32) -> Matrix {
+        let mut m = [0.0; 16];
+        for c in 0..3 {
+            for r in 0..3 {
+                m[c * 4 + r] = self.basis[r][c] / scale;
+            }
+        }
+        m[15] = 1.0;
+        m
+    }
+
+    fn is_rotation(&self) -> bool {
+        self.basis != IDENTITY
+    }
+
+    /// Returns the axis the given axis is rotated onto, and whether it is flipped.
+    fn axis(&self, i: usize) -> (usize, f32) {
+        let b = self.basis[i];
+        let k = (0..3).max_by(|&a, &c| b[a].abs().partial_cmp(&b[c].abs()).unwrap()).unwrap();
+        (k, b[k].signum())
+    }
+
+    fn position(&self, v: [f32; 3]) -> [f32; 3] {
+        let v = rotate(&self.basis, v);
+        [v[0] * self.distance, v[1] * self.distance, v[2] * self.distance]
+    }
+
+    fn transform(&self, m: &Matrix) -> Matrix {
+        multiply(&multiply(&self.matrix(self.distance), m), &self.inverse(self.distance))
+    }
+
+    fn transformation(&self, t: &Transformation) -> Transformation {
+        match *t {
+            Transformation::Transform(Transform(ref m)) => {
+                Transformation::Transform(Transform(self.transform(m)))
+            }
+            Transformation::Translation(ref t) => Transformation::Translation(match *t {
+                Translation::Xyz(x, y, z) => {
+                    let v = self.position([x, y, z]);
+                    Translation::Xyz(v[0], v[1], v[2])
+                }
+                Translation::X(x) => self.translation(0, x),
+                Translation::Y(y) => self.translation(1, y),
+                Translation::Z(z) => self.translation(2, z)
+            }),
+            Transformation::Rotation(ref r) => Transformation::Rotation(match *r {
+                Rotation::X(a) => self.rotation(0, a),
+                Rotation::Y(a) => self.rotation(1, a),
+                Rotation::Z(a) => self.rotation(2, a),
+                Rotation::Axis(a, x, y, z) => {
+                    let v = rotate(&self.basis, [x, y, z]);
+                    Rotation::Axis(a * self.angle, v[0], v[1], v[2])
+                }
+                Rotation::Quaternion(x, y, z, w) => {
+                    let v = rotate(&self.basis, [x, y, z]);
+                    Rotation::Quaternion(v[0], v[1], v[2], w)
+                }
+            }),
+            Transformation::Scale(ref s) => Transformation::Scale(match *s {
+                Scale::X(x) => [Scale::X, Scale::Y, Scale::Z][self.axis(0).0](x),
+                Scale::Y(y) => [Scale::X, Scale::Y, Scale::Z][self.axis(1).0](y),
+                Scale::Z(z) => [Scale::X, Scale::Y, Scale::Z][self.axis(2).0](z),
+                Scale::Xyz(x, y, z) => {
+                    let mut v = [0.0; 3];
+                    for (i, &s) in [x, y, z].iter().enumerate() {
+                        v[self.axis(i).0] = s;
+                    }
+                    Scale::Xyz(v[0], v[1], v[2])
+                }
+            })
+        }
+    }
+
+    fn translation(&self, i: usize, x: f32) -> Translation {
+        let (k, sign) = self.axis(i);
+        [Translation::X, Translation::Y, Translation::Z][k](sign * x * self.distance)
+    }
+
+    fn rotation(&self, i: usize, a: f32) -> Rotation {
+        let (k, sign) = self.axis(i);
+        [Rotation::X, Rotation::Y, Rotation::Z][k](sign * a * self.angle)
+    }
+
+    /// Converts one key of a track targeting `target`, in the same way as the target itself.
+    fn key(&self, target: &Transformation, v: &mut [f32]) {
+        if let Some(t) = target.with_components(v) {
+            v.copy_from_slice(&self.transformation(&t).components());
+        }
+    }
+
+    /// Converts the key times and values of animations. This must be done before the targets of
+    /// their tracks are replaced, as the values are converted according to their target.
+    fn animations(&self, animations: &mut [Animation]) {
+        for animation in animations {
+            animation.begin = animation.begin.map(|t| t * self.time);
+            animation.end = animation.end.map(|t| t * self.time);
+            for track in &mut animation.tracks {
+                let keys = match track.time {
+                    Time::Linear(ref mut times) => {
+                        for t in times.iter_mut() {
+                            *t *= self.time;
+                        }
+                        times.len()
+                    }
+                    Time::Bezier(ref mut times) => {
+                        for t in times.iter_mut() {
+                            *t = (t.0 * self.time, t.1 * self.time, t.2 * self.time);
+                        }
+                        times.len()
+                    }
+                };
+                if let TrackTarget::Transformation(ref target) = track.target {
+                    self.values(target, &mut track.value, keys);
+                }
+            }
+        }
+    }
+
+    fn values(&self, target: &Transformation, value: &mut Value, keys: usize) {
+        let convert = |v: &mut Vec<f32>| {
+            if keys > 0 && v.len() % keys == 0 {
+                let components = v.len() / keys;
+                for key in v.chunks_mut(components) {
+                    self.key(target, key);
+                }
+            }
+        };
+        match *value {
+            Value::Constant(ref mut v) | Value::Linear(ref mut v) => convert(v),
+            Value::Bezier(ref mut v) => {
+                let mut values: Vec<f32> = v.iter().map(|k| k.0).collect();
+                let mut minus: Vec<f32> = v.iter().map(|k| k.1).collect();
+                let mut plus: Vec<f32> = v.iter().map(|k| k.2).collect();
+                convert(&mut values);
+                convert(&mut minus);
+                convert(&mut plus);
+                for (i, k) in v.iter_mut().enumerate() {
+                    *k = (values[i], minus[i], plus[i]);
+                }
+            }
+            Value::Tcb(ref mut v) => {
+                let mut values: Vec<f32> = v.iter().map(|k| k.0).collect();
+                convert(&mut values);
+                for (k, value) in v.iter_mut().zip(values) {
+                    k.0 = value;
+                }
+            }
+        }
+    }
+
+    fn textures<'a, I: Iterator<Item = &'a mut Texture>>(&self, textures: I) {
+        for texture in textures {
+            self.animations(&mut texture.animation);
+            replace_targets(&mut texture.transformations, &mut [], &mut texture.animation,
+                |t| self.transformation(t), |w| w.clone());
+        }
+    }
+}
+
+/// Converts a scene with a `Convert` for its space and one for the texture coordinate space.
+struct Normalize {
+    space: Convert,
+    texture: Convert
+}
+
+impl Rewrite for Normalize {
+    fn node(&mut self, node: NodeParts) {
+        let space = &self.space;
+        space.animations(node.animations);
+        let morph_weights = match node.morph_weights {
+            Some(morph_weights) => &mut morph_weights[..],
+            None => &mut []
+        };
+        replace_targets(node.transformations, morph_weights, node.animations,
+            |t| space.transformation(t), |w| w.clone());
+        if space.is_rotation() && (node.kind == NodeKind::CameraNode ||
+            node.kind == NodeKind::LightNode)
+        {
+            node.transformations.push(Arc::new(Transformation::Transform(Transform(
+                space.matrix(1.0)))));
+            for child in node.children.iter_mut() {
+                let transformations = match *child {
+                    Nodes::Node(ref mut node) => &mut node.transformations,
+                    Nodes::GeometryNode(ref mut node) => &mut node.transformations,
+                    Nodes::CameraNode(ref mut node) => &mut node.transformations,
+                    Nodes::LightNode(ref mut node) => &mut node.transformations,
+                    Nodes::BoneNode(_) => continue
+                };
+                transformations.insert(0, Arc::new(Transformation::Transform(Transform(
+                    space.inverse(1.0)))));
+            }
+        }
+    }
+
+    fn geometry_object(&mut self, object: &mut GeometryObject) {
+        for (_, mesh) in object.meshes.iter_mut() {
+            for array in &mut mesh.vertex_arrays {
+                let position = match array.attrib.as_str() {
+                    "position" => true,
+                    "normal" | "tangent" | "bitangent" => false,
+                    _ => continue
+                };
+                if array.components != 3 {
+                    continue;
+                }
+                let convert = |v: [f32; 3]| if position {
+                    self.space.position(v)
+                } else {
+                    rotate(&self.space.basis, v)
+                };
+                match array.data {
+                    VertexData::Half(ref mut data) | VertexData::Float(ref mut data) => {
+                        for v in data.chunks_mut(3) {
+                            let c = convert([v[0], v[1], v[2]]);
+                            v.copy_from_slice(&c);
+                        }
+                    }
+                    VertexData::Double(ref mut data) => {
+                        // Double-precision data is converted without going through `f32`.
+                        let scale = if position { self.space.precise_distance } else { 1.0 };
+                        for v in data.chunks_mut(3) {
+                            let mut c = [0.0; 3];
+                            for (b, x) in self.space.basis.iter().zip(v.iter()) {
+                                for (c, &b) in c.iter_mut().zip(b) {
+                                    *c += b as f64 * x;
+                                }
+                            }
+                            for (v, c) in v.iter_mut().zip(&c) {
+                                *v = c * scale;
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(ref mut skin) = mesh.skin {
+                if let Some(Transform(ref mut m)) = skin.transform {
+                    *m = self.space.transform(m);
+                }
+                for &mut Transform(ref mut m) in &mut skin.skeleton.transforms {
+                    *m = self.space.transform(m);
+                }
+            }
+        }
+    }
+
+    fn light_object(&mut self, object: &mut LightObject) {
+        for atten in &mut object.attenuations {
+            let (scale, params): (f32, &[&str]) = match atten.kind {
+                AttenuationKind::Distance => (self.space.distance, &["begin", "end", "scale",
+                    "offset"]),
+                AttenuationKind::Angle => (self.space.angle, &["begin", "end"]),
+                AttenuationKind::CosAngle => continue
+            };
+            for param in params {
+                if let Some(value) = atten.params.get_mut(*param) {
+                    *value *= scale;
+                }
+            }
+        }
+        self.texture.textures(object.textures.values_mut());
+    }
+
+    fn camera_object(&mut self, object: &mut CameraObject) {
+        for (param, scale) in &[("near", self.space.distance), ("far", self.space.distance),
+            ("fov", self.space.angle)]
+        {
+            if let Some(value) = object.params.get_mut(*param) {
+                *value *= *scale;
+            }
+        }
+        self.texture.textures(object.textures.values_mut());
+    }
+
+    fn material(&mut self, material: &mut Material) {
+        self.texture.textures(material.texture.values_mut());
+    }
+}
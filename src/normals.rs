@@ -0,0 +1,450 @@
+//! Generation of normals and tangents.
+//!
+//! Both are computed per corner of the triangles of a mesh, then averaged over the corners sharing
+//! a vertex. Corners are grouped by the values of their vertex, not by their index, so vertices
+//! duplicated by an exporter are smoothed as one. Every corner is first given a vertex of its own,
+//! and the corners ending up with the same values are welded back together, so a vertex is only
+//! split where the generated values differ.
+//!
+//! Tangents follow MikkTSpace, the convention of most tools baking normal maps, so that baked maps
+//! look right on the generated tangents. Around every vertex, the triangles connected through
+//! their edges whose texture coordinates have the same orientation are grouped, and the tangent of
+//! a group is the average of the tangents of its triangles, projected onto the plane of the
+//! normal and weighted by the angle of their corners. Vertices count as one where their position,
+//! normal and texture coordinates are the same. The bitangent is the cross product of the normal
+//! and the tangent, negated where the texture coordinates are mirrored.
+
+use std::collections::HashMap;
+
+use primitive::vertex_count;
+use structure::*;
+
+/// How the normals of the triangles around a vertex are weighted to give its normal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weighting {
+    /// Every triangle is weighted by its area.
+    Area,
+    /// Every triangle is weighted by the angle of its corner at the vertex. This does not depend
+    /// on how the surface around the vertex is split into triangles.
+    Angle
+}
+
+impl Mesh {
+    /// Generates the normals of the mesh, replacing its existing ones, for every morph target with
+    /// positions. Returns `None` if the primitives of the mesh are not triangles, or if it has no
+    /// positions.
+    ///
+    /// With a crease angle, in radians, a triangle only contributes to the normals of the corners
+    /// of the triangles facing at most that angle away from it, so that hard edges stay sharp.
+    ///
+    /// The returned mesh is a triangle list, like the one of `Mesh::to_triangle_list`.
+    pub fn generate_normals(&self, weighting: Weighting, crease_angle: Option<f32>)
+        -> Option<Mesh>
+    {
+        let mut mesh = self.to_triangle_list()?.unweld();
+        positions(&mesh, 0)?;
+        let triangles = triangles(&mesh);
+        let crease = crease_angle.map(|a| (a as f64).cos());
+        let targets: Vec<u32> = mesh.vertex_arrays.iter()
+            .filter(|a| a.attrib == "position" && a.components >= 3)
+            .map(|a| a.morph)
+            .collect();
+        for morph in targets {
+            let positions = positions(&mesh, morph)?;
+            // The face normal of the triangle of every corner, and the weighted normal it
+            // contributes to the corner.
+            let mut faces = vec![[0.0; 3]; positions.len()];
+            let mut weighted = vec![[0.0; 3]; positions.len()];
+            for t in &triangles {
+                let p = [positions[t[0]], positions[t[1]], positions[t[2]]];
+                let n = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+                for k in 0..3 {
+                    let angle = angle(sub(p[(k + 1) % 3], p[k]), sub(p[(k + 2) % 3], p[k]));
+                    faces[t[k]] = normalize(n);
+                    weighted[t[k]] = match weighting {
+                        Weighting::Area => n,
+                        Weighting::Angle => scale(normalize(n), angle)
+                    };
+                }
+            }
+            let mut normals = vec![0.0; positions.len() * 3];
+            for corners in group(positions.len(), |v| key(&positions[v])).values() {
+                for &c in corners {
+                    let mut sum = [0.0; 3];
+                    for &d in corners {
+                        if crease.map_or(true, |crease| dot(faces[c], faces[d]) >= crease - 1e-6) {
+                            sum = add(sum, weighted[d]);
+                        }
+                    }
+                    let n = normalize(sum);
+                    let n = if n == [0.0; 3] { faces[c] } else { n };
+                    normals[c * 3..c * 3 + 3].copy_from_slice(&n);
+                }
+            }
+            set_array(&mut mesh, "normal", 0, morph, normals);
+        }
+        Some(mesh.weld(0.0))
+    }
+
+    /// Generates the tangents and bitangents of the mesh from its normals and the texture
+    /// coordinates with the given array index, replacing its existing ones. They are added for
+    /// target 0 only. Returns `None` if the primitives of the mesh are not triangles, or if it
+    /// lacks positions, normals or texture coordinates.
+    ///
+    /// The tangents are those of MikkTSpace for the triangles of the mesh, so quads are split
+    /// before their tangents are generated. Where MikkTSpace leaves a corner without a tangent, or
+    /// gives it a zero one, it gets a tangent perpendicular to its normal instead.
+    ///
+    /// The returned mesh is a triangle list, like the one of `Mesh::to_triangle_list`.
+    pub fn generate_tangents(&self, texcoord: u32) -> Option<Mesh> {
+        let mut mesh = self.to_triangle_list()?.unweld();
+        let positions = positions(&mesh, 0)?;
+        let normals = mesh.vertex_arrays.iter()
+            .find(|a| a.attrib == "normal" && a.morph == 0 && a.components == 3)
+            .map(|a| a.data.to_f64())?;
+        let uvs = mesh.vertex_arrays.iter()
+            .find(|a| a.attrib == "texcoord" && a.index == texcoord && a.morph == 0 &&
+                a.components >= 2)
+            .map(|a| (a.components, a.data.to_f64()))?;
+        let count = vertex_count(&mesh);
+        if normals.len() < count * 3 || uvs.1.len() < count * uvs.0 {
+            return None;
+        }
+        let normals: Vec<[f64; 3]> = (0..count)
+            .map(|v| [normals[v * 3], normals[v * 3 + 1], normals[v * 3 + 2]])
+            .collect();
+        let uvs: Vec<[f64; 2]> = (0..count)
+            .map(|v| [uvs.1[v * uvs.0], uvs.1[v * uvs.0 + 1]])
+            .collect();
+
+        let spaces = tangent_spaces(&positions, &normals, &uvs, &triangles(&mesh));
+        let mut tangents = vec![0.0; count * 3];
+        let mut bitangents = vec![0.0; count * 3];
+        for (v, space) in spaces.into_iter().enumerate() {
+            let n = normals[v];
+            let (tangent, preserving) = match space {
+                Some((tangent, preserving)) if tangent != [0.0; 3] => (tangent, preserving),
+                _ => (perpendicular(normalize(n)), true)
+            };
+            let bitangent = scale(cross(n, tangent), if preserving { 1.0 } else { -1.0 });
+            tangents[v * 3..v * 3 + 3].copy_from_slice(&tangent);
+            bitangents[v * 3..v * 3 + 3].copy_from_slice(&bitangent);
+        }
+        set_array(&mut mesh, "tangent", 0, 0, tangents);
+        set_array(&mut mesh, "bitangent", 0, 0, bitangents);
+        Some(mesh.weld(0.0))
+    }
+}
+
+/// A triangle, as MikkTSpace sees it.
+struct Face {
+    /// The vertices of the corners.
+    vertices: [usize; 3],
+    /// The vertices of the corners, with the vertices of the same values counting as one.
+    corners: [usize; 3],
+    /// The direction in which the first texture coordinate increases, of length 1 or 0.
+    s: [f64; 3],
+    /// The direction in which the second texture coordinate increases, of length 1 or 0.
+    t: [f64; 3],
+    /// Whether the texture coordinates have the orientation of the corners.
+    preserving: bool,
+    /// Whether the triangle has no area in texture space, so that it joins any group.
+    any: bool,
+    /// The triangles on the other side of the edge starting at every corner.
+    neighbors: [Option<usize>; 3],
+    /// The group of every corner.
+    groups: [Option<usize>; 3]
+}
+
+/// Triangles around a vertex, connected through their edges, whose texture coordinates have the
+/// same orientation.
+struct Group {
+    vertex: usize,
+    preserving: bool,
+    faces: Vec<usize>
+}
+
+/// Returns the tangent of every corner of the triangles, and whether its texture coordinates
+/// preserve the orientation of the triangle, as MikkTSpace computes them.
+fn tangent_spaces(positions: &[[f64; 3]], normals: &[[f64; 3]], uvs: &[[f64; 2]],
+    triangles: &[[usize; 3]]) -> Vec<Option<([f64; 3], bool)>>
+{
+    let mut shared: Vec<usize> = (0..positions.len()).collect();
+    let groups = group(positions.len(), |v| {
+        let mut values = key(&positions[v]).to_vec();
+        values.extend_from_slice(&key(&normals[v]));
+        values.extend_from_slice(&[(uvs[v][0] + 0.0).to_bits(), (uvs[v][1] + 0.0).to_bits()]);
+        values
+    });
+    for vertices in groups.values() {
+        for &v in vertices {
+            shared[v] = vertices[0];
+        }
+    }
+
+    // Triangles with two corners at the same position are left out, and take the tangents of the
+    // other triangles at the end.
+    let mut faces = vec![];
+    let mut degenerate = vec![];
+    for &vertices in triangles {
+        let p = [positions[vertices[0]], positions[vertices[1]], positions[vertices[2]]];
+        if p[0] == p[1] || p[0] == p[2] || p[1] == p[2] {
+            degenerate.push(vertices);
+            continue;
+        }
+        let w = [uvs[vertices[0]], uvs[vertices[1]], uvs[vertices[2]]];
+        let (d1, d2) = (sub(p[1], p[0]), sub(p[2], p[0]));
+        let (t21, t31) = ([w[1][0] - w[0][0], w[1][1] - w[0][1]],
+            [w[2][0] - w[0][0], w[2][1] - w[0][1]]);
+        let area = t21[0] * t31[1] - t21[1] * t31[0];
+        let mut face = Face {
+            vertices,
+            corners: [shared[vertices[0]], shared[vertices[1]], shared[vertices[2]]],
+            s: [0.0; 3],
+            t: [0.0; 3],
+            preserving: area > 0.0,
+            any: true,
+            neighbors: [None; 3],
+            groups: [None; 3]
+        };
+        if not_zero(area) {
+            let s = sub(scale(d1, t31[1]), scale(d2, t21[1]));
+            let t = sub(scale(d2, t21[0]), scale(d1, t31[0]));
+            let sign = if face.preserving { 1.0 } else { -1.0 };
+            let (length_s, length_t) = (dot(s, s).sqrt(), dot(t, t).sqrt());
+            if not_zero(length_s) {
+                face.s = scale(s, sign / length_s);
+            }
+            if not_zero(length_t) {
+                face.t = scale(t, sign / length_t);
+            }
+            face.any = !(not_zero(length_s / area.abs()) && not_zero(length_t / area.abs()));
+        }
+        faces.push(face);
+    }
+
+    // Every edge is paired with the first edge running the other way that is not paired yet.
+    let mut edges = vec![];
+    for (f, face) in faces.iter().enumerate() {
+        for k in 0..3 {
+            let (a, b) = (face.corners[k], face.corners[(k + 1) % 3]);
+            edges.push((a.min(b), a.max(b), f));
+        }
+    }
+    edges.sort_unstable();
+    for (i, &(a, b, f)) in edges.iter().enumerate() {
+        let (k, from, to) = edge(&faces[f].corners, a, b);
+        if faces[f].neighbors[k].is_some() {
+            continue;
+        }
+        let found = edges[i + 1..].iter().take_while(|e| e.0 == a && e.1 == b).find_map(|e| {
+            let (l, other_from, other_to) = edge(&faces[e.2].corners, a, b);
+            let free = faces[e.2].neighbors[l].is_none();
+            if free && (other_from, other_to) == (to, from) { Some((e.2, l)) } else { None }
+        });
+        if let Some((g, l)) = found {
+            faces[f].neighbors[k] = Some(g);
+            faces[g].neighbors[l] = Some(f);
+        }
+    }
+
+    // Every corner not in a group yet starts one, which spreads through the edges at its vertex
+    // depth first, to the triangles of the same orientation. Triangles without area in texture
+    // space join the first group reaching them, and take its orientation.
+    let mut groups: Vec<Group> = vec![];
+    for f in 0..faces.len() {
+        for k in 0..3 {
+            if faces[f].any || faces[f].groups[k].is_some() {
+                continue;
+            }
+            let g = groups.len();
+            let (vertex, preserving) = (faces[f].corners[k], faces[f].preserving);
+            groups.push(Group { vertex, preserving, faces: vec![f] });
+            faces[f].groups[k] = Some(g);
+            let n = faces[f].neighbors;
+            let mut stack: Vec<usize> = [n[(k + 2) % 3], n[k]].iter().flatten().cloned().collect();
+            while let Some(t) = stack.pop() {
+                let face = &mut faces[t];
+                let i = match face.corners.iter().position(|&c| c == vertex) {
+                    Some(i) if face.groups[i].is_none() => i,
+                    _ => continue
+                };
+                if face.any && face.groups == [None; 3] {
+                    face.preserving = preserving;
+                }
+                if face.preserving != preserving {
+                    continue;
+                }
+                groups[g].faces.push(t);
+                face.groups[i] = Some(g);
+                stack.extend([face.neighbors[(i + 2) % 3], face.neighbors[i]].iter().flatten());
+            }
+        }
+    }
+
+    // The triangles of a group are split into subgroups whose tangent directions are not opposite,
+    // and every subgroup has the tangent averaged over its triangles.
+    let project = |v: [f64; 3], n: [f64; 3]| normalize(sub(v, scale(n, dot(n, v))));
+    let tangent = |members: &[usize], vertex: usize| {
+        let n = normals[vertex];
+        let sum = members.iter().filter(|&&f| !faces[f].any).fold([0.0; 3], |sum, &f| {
+            let face = &faces[f];
+            let i = face.corners.iter().position(|&c| c == vertex).unwrap_or(0);
+            let p = |k: usize| positions[face.corners[(i + k) % 3]];
+            let angle = angle(project(sub(p(2), p(0)), n), project(sub(p(1), p(0)), n));
+            add(sum, scale(project(face.s, n), angle))
+        });
+        normalize(sum)
+    };
+    let mut spaces = vec![None; positions.len()];
+    for (g, group) in groups.iter().enumerate() {
+        let n = normals[group.vertex];
+        let mut subgroups: Vec<(Vec<usize>, [f64; 3])> = vec![];
+        for &f in &group.faces {
+            let (s, t) = (project(faces[f].s, n), project(faces[f].t, n));
+            let mut members: Vec<usize> = group.faces.iter().cloned().filter(|&u| {
+                faces[f].any || faces[u].any || f == u ||
+                    (dot(s, project(faces[u].s, n)) > -1.0 && dot(t, project(faces[u].t, n)) > -1.0)
+            }).collect();
+            members.sort_unstable();
+            let tangent = match subgroups.iter().find(|s| s.0 == members) {
+                Some(subgroup) => subgroup.1,
+                None => {
+                    let tangent = tangent(&members, group.vertex);
+                    subgroups.push((members, tangent));
+                    tangent
+                }
+            };
+            if let Some(k) = faces[f].groups.iter().position(|&h| h == Some(g)) {
+                spaces[faces[f].vertices[k]] = Some((tangent, group.preserving));
+            }
+        }
+    }
+
+    // The corners of degenerate triangles take the tangent of the first corner of another triangle
+    // with the same vertex.
+    let mut first = HashMap::new();
+    for face in &faces {
+        for k in 0..3 {
+            first.entry(face.corners[k]).or_insert(face.vertices[k]);
+        }
+    }
+    for vertices in degenerate {
+        for v in vertices {
+            if let Some(&u) = first.get(&shared[v]) {
+                spaces[v] = spaces[u];
+            }
+        }
+    }
+    spaces
+}
+
+/// Returns the number of the edge of a triangle joining two of its corners, which is the number of
+/// the corner it starts at, and its two corners in the order of the triangle.
+fn edge(corners: &[usize; 3], a: usize, b: usize) -> (usize, usize, usize) {
+    if corners[0] != a && corners[0] != b {
+        (1, corners[1], corners[2])
+    } else if corners[1] == a || corners[1] == b {
+        (0, corners[0], corners[1])
+    } else {
+        (2, corners[2], corners[0])
+    }
+}
+
+/// Returns whether a number is not zero for MikkTSpace, which is whether it is larger than the
+/// smallest normal `f32` number.
+fn not_zero(x: f64) -> bool {
+    x.abs() > f32::MIN_POSITIVE as f64
+}
+
+/// Returns the positions of a morph target of a mesh.
+fn positions(mesh: &Mesh, morph: u32) -> Option<Vec<[f64; 3]>> {
+    let array = mesh.vertex_arrays.iter()
+        .find(|a| a.attrib == "position" && a.morph == morph && a.components >= 3)?;
+    let values = array.data.to_f64();
+    Some(values.chunks(array.components).filter(|v| v.len() == array.components)
+        .map(|v| [v[0], v[1], v[2]]).collect())
+}
+
+/// Returns the corners of the triangles of a triangle list, skipping those out of the vertices.
+fn triangles(mesh: &Mesh) -> Vec<[usize; 3]> {
+    let count = vertex_count(mesh);
+    mesh.index_arrays.iter().flat_map(|array| {
+        let indices = array.data.to_u64();
+        indices.chunks(3)
+            .filter(|t| t.len() == 3 && t.iter().all(|&i| (i as usize) < count))
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+            .collect::<Vec<_>>()
+    }).collect()
+}
+
+/// Groups the vertices of a mesh by a key.
+fn group<K, F>(count: usize, key: F) -> HashMap<K, Vec<usize>>
+    where K: ::std::hash::Hash + Eq, F: Fn(usize) -> K
+{
+    let mut groups: HashMap<K, Vec<usize>> = HashMap::new();
+    for v in 0..count {
+        groups.entry(key(v)).or_default().push(v);
+    }
+    groups
+}
+
+/// Replaces the values of a vertex array of a mesh, or adds a vertex array with them.
+fn set_array(mesh: &mut Mesh, attrib: &str, index: u32, morph: u32, values: Vec<f64>) {
+    let existing = mesh.vertex_arrays.iter_mut()
+        .find(|a| a.attrib == attrib && a.index == index && a.morph == morph);
+    match existing {
+        Some(array) => {
+            array.components = 3;
+            array.data = array.data.with_values(values);
+        }
+        None => mesh.vertex_arrays.push(VertexArray {
+            attrib: attrib.into(),
+            index,
+            morph,
+            components: 3,
+            data: VertexData::Float(values.into_iter().map(|x| x as f32).collect())
+        })
+    }
+}
+
+fn key(v: &[f64; 3]) -> [u64; 3] {
+    [(v[0] + 0.0).to_bits(), (v[1] + 0.0).to_bits(), (v[2] + 0.0).to_bits()]
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f64; 3], s: f64) -> [f64; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Returns the vector scaled to a length of 1, or the zero vector.
+fn normalize(a: [f64; 3]) -> [f64; 3] {
+    let len = dot(a, a).sqrt();
+    if len > 0.0 { scale(a, 1.0 / len) } else { [0.0; 3] }
+}
+
+/// Returns the angle between two vectors.
+fn angle(a: [f64; 3], b: [f64; 3]) -> f64 {
+    dot(normalize(a), normalize(b)).clamp(-1.0, 1.0).acos()
+}
+
+/// Returns a unit vector perpendicular to a unit vector.
+fn perpendicular(n: [f64; 3]) -> [f64; 3] {
+    let axis = if n[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    normalize(sub(axis, scale(n, dot(n, axis))))
+}
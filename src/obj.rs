@@ -0,0 +1,721 @@
+//! Import and export of Wavefront OBJ files and their MTL material libraries.
+//!
+//! Every object of an OBJ file, started by an `o` statement, or by a `g` statement outside of any
+//! object, becomes a `GeometryNode` referencing a `GeometryObject` with a single triangle mesh.
+//! Polygons are split into triangle fans. The faces of an object using the same material, selected
+//! by `usemtl`, are gathered into one index array, and every distinct combination of position,
+//! texture coordinates and normal used by a face corner becomes a vertex. Vertex colors following
+//! the coordinates of `v` statements, as in `v x y z r g b`, with an optional alpha, become a
+//! "color" vertex array.
+//!
+//! The materials of the MTL libraries named by `mtllib` statements get "diffuse", "specular" and
+//! "emission" colors from the `Kd`, `Ks` and `Ke` statements, a "specular_power" parameter from
+//! `Ns`, an "opacity" color from `d` or `Tr`, and textures from the matching `map_` statements,
+//! plus a "normal" texture from `norm` or `bump`. The `-s` and `-o` options of a texture become a
+//! scale of its texture coordinates followed by a translation. A library that cannot be found
+//! defines no materials, leaving the ones selected by `usemtl` with only a name.
+//!
+//! OBJ files have no units or axis conventions, but are mostly written with the y axis up, so the
+//! metric of the scene says so, and `Scene::normalize` can convert it. Lines, points, curves and
+//! smoothing groups are ignored.
+//!
+//! When a scene is written, the mesh of every geometry node is skinned and morphed in the pose
+//! given by the transformations of the nodes, then moved into the space of the scene by the world
+//! matrix of the node. Meshes of lines and points are written with `l` and `p` statements, and the
+//! other meshes are split into triangles. The primitives using every material index become a
+//! group, named after the node and the index. The colors, parameters and textures read above are
+//! written back to the MTL library, the rotations of the textures excepted.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use vec_map::VecMap;
+
+use animation::Pose;
+use error::{ Error, ErrorKind };
+use primitive::{ index_data, vertex_count };
+use structure::*;
+use transform::{ invert, local_matrix, transform_point, transform_vector, transpose, Matrix };
+
+impl Scene {
+    /// Loads a scene from Wavefront OBJ source text. The material libraries named by the source
+    /// are read by calling `mtl` with their names.
+    pub fn from_obj<F>(source: &str, mut mtl: F) -> Result<Scene, Error>
+        where F: FnMut(&str) -> io::Result<String>
+    {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut colors: Vec<Option<[f32; 4]>> = vec![];
+        // The number of components of the vertex colors, 4 once one of them has an alpha.
+        let mut color_components = 3;
+        let mut texcoords: Vec<[f32; 2]> = vec![];
+        let mut normals: Vec<[f32; 3]> = vec![];
+        let mut materials: Vec<Arc<Material>> = vec![];
+        let mut objects: Vec<Object> = vec![Object::default()];
+        let mut material = None;
+        let mut in_object = false;
+
+        for (offset, tokens) in lines(source) {
+            let (keyword, args) = (tokens[0].1, &tokens[1..]);
+            let object = objects.last_mut().unwrap();
+            match keyword {
+                "v" => {
+                    // Four numbers are a position with a weight, and six or seven a position
+                    // with a color.
+                    let v: [f32; 7] = numbers(source, args, 3, 7)?;
+                    if args.len() == 5 {
+                        return invalid(source, args[4].0, "expected 3, 4, 6 or 7 numbers");
+                    }
+                    positions.push([v[0], v[1], v[2]]);
+                    colors.push(match args.len() {
+                        6 => Some([v[3], v[4], v[5], 1.0]),
+                        7 => {
+                            color_components = 4;
+                            Some([v[3], v[4], v[5], v[6]])
+                        }
+                        _ => None
+                    });
+                }
+                "vt" => texcoords.push(numbers(source, args, 1, 3)?),
+                "vn" => normals.push(numbers(source, args, 3, 3)?),
+                "f" => {
+                    if args.len() < 3 {
+                        return invalid(source, offset, "face with fewer than 3 vertices");
+                    }
+                    let mut corners = vec![];
+                    for &(offset, arg) in args {
+                        let corner = corner(arg, positions.len(), texcoords.len(), normals.len())
+                            .ok_or_else(|| Error::new(ErrorKind::Invalid(
+                                format!("invalid face vertex `{}`", arg)), source, offset))?;
+                        corners.push(object.vertex(corner));
+                    }
+                    let indices = object.indices(material);
+                    for i in 1..corners.len() - 1 {
+                        indices.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                    }
+                }
+                // Groups split the objects of files without `o` statements, and are part of the
+                // current object otherwise.
+                "g" if in_object => {}
+                "o" | "g" => {
+                    in_object = keyword == "o";
+                    let name = rest(source, &tokens);
+                    if object.faces.is_empty() {
+                        object.name = name;
+                    } else {
+                        objects.push(Object { name, ..Object::default() });
+                    }
+                }
+                "usemtl" => {
+                    let name = rest(source, &tokens);
+                    let index = materials.iter()
+                        .position(|m| m.name.as_ref() == name.as_ref());
+                    material = Some(match index {
+                        Some(index) => index,
+                        None => {
+                            materials.push(Arc::new(Material {
+                                two_sided: false,
+                                name,
+                                color: HashMap::new(),
+                                param: ParamMap::new(),
+                                texture: HashMap::new()
+                            }));
+                            materials.len() - 1
+                        }
+                    });
+                }
+                "mtllib" => {
+                    for &(_, name) in args {
+                        let library = match mtl(name) {
+                            Ok(library) => library,
+                            Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                            Err(err) => return Err(err.into())
+                        };
+                        for m in read_mtl(&library)? {
+                            // A material used before its library is read gets its definition.
+                            match materials.iter().position(|x| x.name == m.name) {
+                                Some(i) => materials[i] = Arc::new(m),
+                                None => materials.push(Arc::new(m))
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut nodes = vec![];
+        let mut geometry_objects = vec![];
+        for object in objects.into_iter().filter(|o| !o.faces.is_empty()) {
+            let vertices = object.vertices.len();
+            let mut vertex_arrays = vec![VertexArray {
+                attrib: "position".to_string(),
+                index: 0,
+                morph: 0,
+                components: 3,
+                data: VertexData::Float(object.vertices.iter()
+                    .flat_map(|v| positions[v.0].iter().cloned()).collect())
+            }];
+            if object.vertices.iter().any(|v| v.1.is_some()) {
+                vertex_arrays.push(VertexArray {
+                    attrib: "texcoord".to_string(),
+                    index: 0,
+                    morph: 0,
+                    components: 2,
+                    data: VertexData::Float(object.vertices.iter()
+                        .flat_map(|v| v.1.map_or([0.0; 2], |t| texcoords[t]).to_vec()).collect())
+                });
+            }
+            if object.vertices.iter().any(|v| v.2.is_some()) {
+                vertex_arrays.push(VertexArray {
+                    attrib: "normal".to_string(),
+                    index: 0,
+                    morph: 0,
+                    components: 3,
+                    data: VertexData::Float(object.vertices.iter()
+                        .flat_map(|v| v.2.map_or([0.0; 3], |n| normals[n]).to_vec()).collect())
+                });
+            }
+            if object.vertices.iter().any(|v| colors[v.0].is_some()) {
+                vertex_arrays.push(VertexArray {
+                    attrib: "color".to_string(),
+                    index: 0,
+                    morph: 0,
+                    components: color_components,
+                    data: VertexData::Float(object.vertices.iter()
+                        .flat_map(|v| colors[v.0].unwrap_or([1.0; 4])[..color_components].to_vec())
+                        .collect())
+                });
+            }
+            let mut node_materials = VecMap::new();
+            let index_arrays = object.faces.into_iter().enumerate().map(|(i, (m, indices))| {
+                if let Some(m) = m {
+                    node_materials.insert(i, materials[m].clone());
+                }
+                IndexArray {
+                    material: i as u32,
+                    restart: None,
+                    front_face: FrontFace::CounterClockwise,
+                    data: index_data(vertices, indices)
+                }
+            }).collect();
+            let mut meshes = VecMap::new();
+            meshes.insert(0, Mesh {
+                primitive: GeometricPrimitive::Triangles,
+                vertex_arrays,
+                index_arrays,
+                skin: None
+            });
+            let geometry = Arc::new(GeometryObject {
+                visible: true,
+                casts_shadows: true,
+                motion_blur: true,
+                meshes,
+                morphs: VecMap::new()
+            });
+            geometry_objects.push(geometry.clone());
+            nodes.push(Nodes::GeometryNode(GeometryNode {
+                name: object.name,
+                transformations: vec![],
+                animations: vec![],
+                children: vec![],
+                visibile: None,
+                casts_shadows: None,
+                motion_blur: None,
+                geometry,
+                materials: node_materials,
+                morph_weights: vec![]
+            }));
+        }
+
+        Ok(Scene {
+            metric: Metric { up: Axis::Y, ..Metric::default() },
+            nodes,
+            geometry_objects,
+            light_objects: vec![],
+            camera_objects: vec![],
+            materials,
+            clip_structures: VecMap::new()
+        })
+    }
+
+    /// Loads a scene from a Wavefront OBJ file, reading its material libraries from the directory
+    /// of the file.
+    pub fn from_obj_file<P: AsRef<Path>>(path: P) -> Result<Scene, Error> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        Scene::from_obj(&source, |name| fs::read_to_string(directory.join(name)))
+    }
+
+    /// Writes the scene as Wavefront OBJ source text naming the MTL material library `mtllib`,
+    /// and returns it along with the source text of the library.
+    pub fn to_obj(&self, mtllib: &str) -> (String, String) {
+        let mut materials = Materials::new(self);
+        let mut obj = String::new();
+        writeln!(obj, "mtllib {}", mtllib).unwrap();
+        // The number of positions, texture coordinates and normals written so far.
+        let mut counts = [0; 3];
+        let pose = Pose::default();
+        for (node, world) in self.world_matrices() {
+            let node = match *node {
+                Nodes::GeometryNode(ref node) => node,
+                _ => continue
+            };
+            if let Some(mesh) = node.skin(self, &pose, 0) {
+                write_mesh(&mut obj, &mut counts, &mut materials, node, &mesh, &world);
+            }
+        }
+        (obj, materials.to_mtl())
+    }
+
+    /// Writes the scene to a Wavefront OBJ file, and its materials to an MTL file with the same
+    /// name and the extension "mtl".
+    pub fn to_obj_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mtl_path = path.with_extension("mtl");
+        let mtllib = mtl_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let (obj, mtl) = self.to_obj(mtllib);
+        fs::write(path, obj)?;
+        fs::write(&mtl_path, mtl)
+    }
+}
+
+/// A position index, with optional texture coordinate and normal indices.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// An object being read.
+#[derive(Default)]
+struct Object {
+    name: Option<Name>,
+    /// The distinct face corners, which become the vertices of the mesh.
+    vertices: Vec<Corner>,
+    /// The vertex of every distinct face corner.
+    lookup: HashMap<Corner, u64>,
+    /// The triangles using every material, in the order the materials are first used.
+    faces: Vec<(Option<usize>, Vec<u64>)>
+}
+
+impl Object {
+    fn vertex(&mut self, corner: Corner) -> u64 {
+        let vertices = &mut self.vertices;
+        *self.lookup.entry(corner).or_insert_with(|| {
+            vertices.push(corner);
+            vertices.len() as u64 - 1
+        })
+    }
+
+    fn indices(&mut self, material: Option<usize>) -> &mut Vec<u64> {
+        let i = match self.faces.iter().position(|f| f.0 == material) {
+            Some(i) => i,
+            None => {
+                self.faces.push((material, vec![]));
+                self.faces.len() - 1
+            }
+        };
+        &mut self.faces[i].1
+    }
+}
+
+/// Splits the source into lines of tokens, along with their byte offsets, skipping comments and
+/// blank lines.
+fn lines(source: &str) -> Vec<(usize, Vec<(usize, &str)>)> {
+    let mut lines = vec![];
+    let mut start = 0;
+    for line in source.split('\n') {
+        let content = &line[..line.find('#').unwrap_or(line.len())];
+        let mut tokens = vec![];
+        let mut token: Option<usize> = None;
+        for (i, c) in content.char_indices().chain(Some((content.len(), ' '))) {
+            match (c.is_whitespace(), token) {
+                (true, Some(t)) => {
+                    tokens.push((start + t, &content[t..i]));
+                    token = None;
+                }
+                (false, None) => token = Some(i),
+                _ => {}
+            }
+        }
+        if !tokens.is_empty() {
+            lines.push((tokens[0].0, tokens));
+        }
+        start += line.len() + 1;
+    }
+    lines
+}
+
+/// Returns the text of a line after its keyword, or `None` if there is none.
+fn rest(source: &str, tokens: &[(usize, &str)]) -> Option<String> {
+    let (first, last) = (tokens.get(1)?, tokens.last()?);
+    Some(source[first.0..last.0 + last.1.len()].to_string())
+}
+
+fn invalid<T>(source: &str, offset: usize, message: &str) -> Result<T, Error> {
+    Err(Error::new(ErrorKind::Invalid(message.to_string()), source, offset))
+}
+
+/// Reads between `min` and `max` numbers, keeping the first `N` and filling the missing ones with
+/// zeros.
+fn numbers<const N: usize>(source: &str, args: &[(usize, &str)], min: usize, max: usize)
+    -> Result<[f32; N], Error>
+{
+    if args.len() < min || args.len() > max {
+        let offset = args.get(max).or(args.last()).map_or(source.len(), |a| a.0);
+        return invalid(source, offset, &format!("expected {} to {} numbers", min, max));
+    }
+    let mut values = [0.0; N];
+    for (i, &(offset, arg)) in args.iter().enumerate() {
+        let value = arg.parse().or_else(|_| invalid(source, offset,
+            &format!("invalid number `{}`", arg)))?;
+        if i < N {
+            values[i] = value;
+        }
+    }
+    Ok(values)
+}
+
+/// Parses a face corner like `1/2/3`, `1//3` or `-1`, resolving negative indices relative to the
+/// number of elements read so far. Returns `None` if an index is invalid or out of range.
+fn corner(arg: &str, positions: usize, texcoords: usize, normals: usize) -> Option<Corner> {
+    let index = |s: &str, count: usize| -> Option<usize> {
+        let i: i64 = s.parse().ok()?;
+        let i = if i < 0 { count as i64 + i } else { i - 1 };
+        if i >= 0 && (i as usize) < count { Some(i as usize) } else { None }
+    };
+    let mut parts = arg.split('/');
+    let position = index(parts.next()?, positions)?;
+    let texcoord = match parts.next() {
+        Some("") | None => None,
+        Some(s) => Some(index(s, texcoords)?)
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(s) => Some(index(s, normals)?)
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((position, texcoord, normal))
+}
+
+/// Reads the materials of an MTL material library.
+fn read_mtl(source: &str) -> Result<Vec<Material>, Error> {
+    let mut materials: Vec<Material> = vec![];
+    for (offset, tokens) in lines(source) {
+        let (keyword, args) = (tokens[0].1, &tokens[1..]);
+        if keyword == "newmtl" {
+            materials.push(Material {
+                two_sided: false,
+                name: rest(source, &tokens),
+                color: HashMap::new(),
+                param: ParamMap::new(),
+                texture: HashMap::new()
+            });
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return invalid(source, offset, "material statement before `newmtl`")
+        };
+        let color = match keyword {
+            "Kd" => "diffuse",
+            "Ks" => "specular",
+            "Ke" => "emission",
+            _ => ""
+        };
+        let texture = match keyword {
+            "map_Kd" => "diffuse",
+            "map_Ks" => "specular",
+            "map_Ke" => "emission",
+            "map_d" => "opacity",
+            "norm" | "bump" | "map_bump" | "map_Bump" => "normal",
+            _ => ""
+        };
+        if !color.is_empty() {
+            // Colors given as spectral curves or CIE XYZ values are not supported.
+            if args.first().map_or(false, |a| a.1 == "spectral" || a.1 == "xyz") {
+                continue;
+            }
+            let [r, g, b] = numbers(source, args, 1, 3)?;
+            let (g, b) = if args.len() == 1 { (r, r) } else { (g, b) };
+            material.color.insert(color.to_string(), Color::Rgb(r, g, b));
+        } else if !texture.is_empty() {
+            material.texture.insert(texture.to_string(), map(source, offset, args)?);
+        } else if keyword == "Ns" {
+            let [power] = numbers(source, args, 1, 1)?;
+            material.param.insert("specular_power".to_string(), power);
+        } else if keyword == "d" {
+            let halo = args.first().map_or(false, |a| a.1 == "-halo");
+            let args = if halo { &args[1..] } else { args };
+            let [d] = numbers(source, args, 1, 1)?;
+            material.color.insert("opacity".to_string(), Color::Rgb(d, d, d));
+        } else if keyword == "Tr" {
+            let [t] = numbers(source, args, 1, 1)?;
+            let d = 1.0 - t;
+            material.color.insert("opacity".to_string(), Color::Rgb(d, d, d));
+        }
+    }
+    Ok(materials)
+}
+
+/// Reads the options and file name of a texture map statement.
+fn map(source: &str, offset: usize, args: &[(usize, &str)]) -> Result<Texture, Error> {
+    let (mut offset_option, mut scale_option) = (None, None);
+    let mut i = 0;
+    while i < args.len() && args[i].1.starts_with('-') && args[i].1.parse::<f32>().is_err() {
+        let option = args[i].1;
+        i += 1;
+        // The numbers following the option, up to three.
+        let values: Vec<f32> = args[i..].iter().take(3).map(|a| a.1.parse())
+            .take_while(Result::is_ok)
+            .flatten()
+            .collect();
+        match option {
+            "-o" | "-s" | "-t" | "-mm" => i += values.len().max(1),
+            _ => i += 1
+        }
+        let value = |k: usize, default: f32| values.get(k).cloned().unwrap_or(default);
+        match option {
+            "-o" => offset_option = Some(Translation::Xyz(value(0, 0.0), value(1, 0.0),
+                value(2, 0.0))),
+            "-s" => scale_option = Some(Scale::Xyz(value(0, 1.0), value(1, 1.0), value(2, 1.0))),
+            _ => {}
+        }
+    }
+    // Texture coordinates are scaled before they are offset, whatever the order of the options.
+    let transformations = offset_option.map(Transformation::Translation).into_iter()
+        .chain(scale_option.map(Transformation::Scale))
+        .map(Arc::new)
+        .collect();
+    match (args.get(i), args.last()) {
+        (Some(first), Some(last)) => Ok(Texture {
+            texcoord: 0,
+            file_name: source[first.0..last.0 + last.1.len()].to_string(),
+            transformations,
+            animation: vec![]
+        }),
+        _ => invalid(source, offset, "texture map without a file name")
+    }
+}
+
+/// The materials of a scene being written, with unique names.
+struct Materials {
+    materials: Vec<(Arc<Material>, String)>,
+    /// The name of the material given to faces without one, if any.
+    default: Option<String>
+}
+
+impl Materials {
+    /// Names the materials of the scene and the ones referenced by its nodes.
+    fn new(scene: &Scene) -> Materials {
+        let mut materials = Materials { materials: vec![], default: None };
+        for material in &scene.materials {
+            materials.name(material);
+        }
+        for (node, _) in scene.world_matrices() {
+            if let Nodes::GeometryNode(ref node) = *node {
+                for (_, material) in &node.materials {
+                    materials.name(material);
+                }
+            }
+        }
+        materials
+    }
+
+    /// Returns the name of a material, giving it a unique one if it has none yet.
+    fn name(&mut self, material: &Arc<Material>) -> String {
+        if let Some(m) = self.materials.iter().find(|m| Arc::ptr_eq(&m.0, material)) {
+            return m.1.clone();
+        }
+        let name = self.unique(material.name.as_ref().map_or("material", |n| n.trim()));
+        self.materials.push((material.clone(), name.clone()));
+        name
+    }
+
+    /// Returns the name of the default material.
+    fn default_name(&mut self) -> String {
+        if self.default.is_none() {
+            self.default = Some(self.unique("default"));
+        }
+        self.default.clone().unwrap()
+    }
+
+    /// Returns the name, or the name followed by a number, that no material has yet.
+    fn unique(&self, name: &str) -> String {
+        let name = if name.is_empty() { "material" } else { name };
+        let taken = |n: &str| self.materials.iter().any(|m| m.1 == n) ||
+            self.default.as_ref().map_or(false, |d| d == n);
+        (1..).map(|i| if i == 1 { name.to_string() } else { format!("{}_{}", name, i) })
+            .find(|n| !taken(n))
+            .unwrap()
+    }
+
+    /// Writes the MTL source text of the materials.
+    fn to_mtl(&self) -> String {
+        let mut mtl = String::new();
+        for (material, name) in &self.materials {
+            if !mtl.is_empty() {
+                mtl.push('\n');
+            }
+            writeln!(mtl, "newmtl {}", name).unwrap();
+            for &(key, statement) in &[("diffuse", "Kd"), ("specular", "Ks"), ("emission", "Ke")] {
+                if let Some(color) = material.color.get(key) {
+                    let [r, g, b] = rgb(color);
+                    writeln!(mtl, "{} {} {} {}", statement, r, g, b).unwrap();
+                }
+            }
+            if let Some(power) = material.param.get("specular_power") {
+                writeln!(mtl, "Ns {}", power).unwrap();
+            }
+            if let Some(color) = material.color.get("opacity") {
+                let [r, g, b] = rgb(color);
+                writeln!(mtl, "d {}", (r + g + b) / 3.0).unwrap();
+            }
+            for &(key, statement) in &[("diffuse", "map_Kd"), ("specular", "map_Ks"),
+                ("emission", "map_Ke"), ("opacity", "map_d"), ("specular_power", "map_Ns"),
+                ("normal", "norm")]
+            {
+                if let Some(texture) = material.texture.get(key) {
+                    write!(mtl, "{}", statement).unwrap();
+                    // Only the scale and translation of the texture coordinates can be written.
+                    let m = local_matrix(texture.transformations.iter().map(|t| &**t));
+                    if [m[0], m[5], m[10]] != [1.0; 3] {
+                        write!(mtl, " -s {} {} {}", m[0], m[5], m[10]).unwrap();
+                    }
+                    if [m[12], m[13], m[14]] != [0.0; 3] {
+                        write!(mtl, " -o {} {} {}", m[12], m[13], m[14]).unwrap();
+                    }
+                    writeln!(mtl, " {}", texture.file_name).unwrap();
+                }
+            }
+        }
+        if let Some(ref name) = self.default {
+            if !mtl.is_empty() {
+                mtl.push('\n');
+            }
+            writeln!(mtl, "newmtl {}", name).unwrap();
+        }
+        mtl
+    }
+}
+
+fn rgb(color: &Color) -> [f32; 3] {
+    match *color {
+        Color::Rgb(r, g, b) | Color::Rgba(r, g, b, _) => [r, g, b]
+    }
+}
+
+/// Writes the vertices and faces of the mesh of a geometry node, in the space of the scene.
+fn write_mesh(obj: &mut String, counts: &mut [usize; 3], materials: &mut Materials,
+    node: &GeometryNode, mesh: &Mesh, world: &Matrix)
+{
+    // Meshes of lines and points are written as such, and the other meshes as triangles.
+    let (statement, corners, mesh) = match (mesh.to_triangle_list(), mesh.to_line_list()) {
+        (Some(triangles), _) => ("f", 3, triangles),
+        (None, Some(lines)) => ("l", 2, lines),
+        (None, None) => ("p", 1, mesh.clone())
+    };
+    let array = |attrib: &str| mesh.vertex_arrays.iter()
+        .find(|a| a.attrib == attrib && a.index == 0 && a.morph == 0 && a.components > 0)
+        .map(|a| (a.components, a.data.to_f32()));
+    let positions = match array("position") {
+        Some(positions) => positions,
+        None => return
+    };
+    let count = vertex_count(&mesh);
+    let value = |array: &(usize, Vec<f32>), v: usize, k: usize| {
+        if k < array.0 { array.1.get(v * array.0 + k).cloned().unwrap_or(0.0) } else { 0.0 }
+    };
+    let texcoords = array("texcoord");
+    let normals = array("normal");
+    let name = node.name.as_ref().map_or("object", |n| n.trim());
+    let name = if name.is_empty() { "object" } else { name };
+
+    writeln!(obj, "o {}", name).unwrap();
+    for v in 0..count {
+        let p = transform_point(world, [value(&positions, v, 0), value(&positions, v, 1),
+            value(&positions, v, 2)]);
+        writeln!(obj, "v {} {} {}", p[0] + 0.0, p[1] + 0.0, p[2] + 0.0).unwrap();
+    }
+    if let Some(ref texcoords) = texcoords {
+        for v in 0..count {
+            writeln!(obj, "vt {} {}", value(texcoords, v, 0), value(texcoords, v, 1)).unwrap();
+        }
+    }
+    if let Some(ref normals) = normals {
+        // Normals are transformed by the inverse transpose of the world matrix.
+        let matrix = invert(world).map_or(*world, |inverse| transpose(&inverse));
+        for v in 0..count {
+            let n = transform_vector(&matrix, [value(normals, v, 0), value(normals, v, 1),
+                value(normals, v, 2)]);
+            let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            let n = if length > 0.0 { [n[0] / length, n[1] / length, n[2] / length] } else { n };
+            writeln!(obj, "vn {} {} {}", n[0] + 0.0, n[1] + 0.0, n[2] + 0.0).unwrap();
+        }
+    }
+
+    // A world matrix with a negative determinant mirrors the mesh, which reverses its winding.
+    let mirrored = world[0] * (world[5] * world[10] - world[9] * world[6]) -
+        world[4] * (world[1] * world[10] - world[9] * world[2]) +
+        world[8] * (world[1] * world[6] - world[5] * world[2]) < 0.0;
+    let arrays: Vec<(u32, FrontFace, Vec<u64>)> = if mesh.index_arrays.is_empty() {
+        vec![(0, FrontFace::CounterClockwise, (0..count as u64).collect())]
+    } else {
+        mesh.index_arrays.iter().map(|a| {
+            let indices = a.data.to_u64().into_iter().filter(|&i| Some(i) != a.restart).collect();
+            (a.material, a.front_face, indices)
+        }).collect()
+    };
+    // The primitives of every material, in the order the materials are first used, wound
+    // counterclockwise.
+    let mut groups: Vec<(u32, Vec<u64>)> = vec![];
+    for (material, front_face, mut indices) in arrays {
+        if corners == 3 && (front_face == FrontFace::Clockwise) != mirrored {
+            for triangle in indices.chunks_mut(3).filter(|t| t.len() == 3) {
+                triangle.swap(1, 2);
+            }
+        }
+        match groups.iter_mut().find(|g| g.0 == material) {
+            Some(group) => group.1.extend(indices),
+            None => groups.push((material, indices))
+        }
+    }
+
+    for (material, indices) in groups {
+        writeln!(obj, "g {}_{}", name, material).unwrap();
+        let material = match node.materials.get(material as usize) {
+            Some(material) => materials.name(material),
+            None => materials.default_name()
+        };
+        writeln!(obj, "usemtl {}", material).unwrap();
+        for primitive in indices.chunks(corners) {
+            if primitive.len() < corners || primitive.iter().any(|&i| i as usize >= count) {
+                continue;
+            }
+            write!(obj, "{}", statement).unwrap();
+            for &i in primitive {
+                let i = i as usize;
+                let v = counts[0] + i + 1;
+                match (texcoords.is_some(), normals.is_some()) {
+                    (false, false) => write!(obj, " {}", v),
+                    (true, false) => write!(obj, " {}/{}", v, counts[1] + i + 1),
+                    (false, true) => write!(obj, " {}//{}", v, counts[2] + i + 1),
+                    (true, true) => {
+                        write!(obj, " {}/{}/{}", v, counts[1] + i + 1, counts[2] + i + 1)
+                    }
+                }.unwrap();
+            }
+            obj.push('\n');
+        }
+    }
+    counts[0] += count;
+    if texcoords.is_some() {
+        counts[1] += count;
+    }
+    if normals.is_some() {
+        counts[2] += count;
+    }
+}
@@ -0,0 +1,165 @@
+//! Optimization of meshes for the vertex cache of the GPU.
+//!
+//! The triangles of every index array are reordered with Tom Forsyth's linear-speed vertex cache
+//! optimization. Every vertex is scored by its position in a simulated cache of recently used
+//! vertices, and by the number of triangles still using it, so that vertices are reused while they
+//! are cached and lone vertices are finished off early. The triangle with the highest score is
+//! emitted next, and the scores of the vertices it touches are updated. When no triangle left
+//! uses a cached vertex, the best one is picked among the next few triangles in the original
+//! order, rather than among all of them, which keeps the optimization linear in the number of
+//! triangles.
+//!
+//! The vertices are then reordered in the order the triangles first use them, so that they are
+//! fetched from memory in sequence. Every vertex array, of every morph target, and the skin are
+//! reordered together.
+//!
+//! Overdraw is not taken into account: the triangles are ordered for the vertex cache only, not
+//! to draw the ones in front first, which depends on the view.
+
+use primitive::vertex_count;
+use structure::*;
+use weld::with_vertices;
+
+/// The size of the simulated vertex cache.
+const CACHE_SIZE: usize = 32;
+/// The score of the vertices of the last emitted triangle. It is lower than the score of the next
+/// vertices in the cache, as the last triangle is unlikely to share two vertices with the next one.
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+/// How fast the score of a vertex drops as it moves back in the cache.
+const CACHE_DECAY_POWER: f32 = 1.5;
+/// The score given to vertices used by few remaining triangles.
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+/// How fast the valence boost drops as the number of remaining triangles grows.
+const VALENCE_BOOST_POWER: f32 = 0.5;
+/// The number of triangles, from the first one not emitted yet, among which the next triangle is
+/// picked when none uses a cached vertex.
+const FALLBACK_WINDOW: usize = 64;
+
+impl Mesh {
+    /// Reorders the triangles of every index array for the locality of the vertex cache, then the
+    /// vertices for the locality of their fetches. Returns `None` if the primitives of the mesh
+    /// are not triangles.
+    ///
+    /// The returned mesh is a triangle list, like the one of `Mesh::to_triangle_list`. Vertices
+    /// not used by any triangle are placed after all the others.
+    pub fn optimize(&self) -> Option<Mesh> {
+        let mesh = self.to_triangle_list()?;
+        let count = vertex_count(&mesh);
+        let arrays: Vec<Vec<u64>> = mesh.index_arrays.iter().map(|array| {
+            let indices = array.data.to_u64();
+            let triangles: Vec<[usize; 3]> = indices.chunks(3)
+                .filter(|t| t.len() == 3 && t.iter().all(|&i| (i as usize) < count))
+                .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+                .collect();
+            reorder(count, &triangles).iter().flat_map(|t| t.iter()).map(|&i| i as u64).collect()
+        }).collect();
+
+        // The vertices in the order of their first use.
+        let mut remap = vec![None; count];
+        let mut vertices = vec![];
+        for &i in arrays.iter().flat_map(|a| a.iter()) {
+            if remap[i as usize].is_none() {
+                remap[i as usize] = Some(vertices.len() as u64);
+                vertices.push(i as usize);
+            }
+        }
+        for (v, r) in remap.iter_mut().enumerate().filter(|r| r.1.is_none()) {
+            *r = Some(vertices.len() as u64);
+            vertices.push(v);
+        }
+        let index_arrays = mesh.index_arrays.iter().zip(arrays).map(|(array, indices)| {
+            let indices = indices.into_iter().map(|i| remap[i as usize].unwrap()).collect();
+            IndexArray { data: array.data.with_values(indices), ..array.clone() }
+        }).collect();
+        Some(with_vertices(&mesh, &vertices, index_arrays))
+    }
+}
+
+/// Returns the score of a vertex, given its position in the cache and the number of triangles
+/// still using it.
+fn score(position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache = match position {
+        Some(p) if p < 3 => LAST_TRIANGLE_SCORE,
+        Some(p) if p < CACHE_SIZE => {
+            (1.0 - (p - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER)
+        }
+        _ => 0.0
+    };
+    cache + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Returns the triangles in the order they are emitted for the vertex cache.
+fn reorder(count: usize, triangles: &[[usize; 3]]) -> Vec<[usize; 3]> {
+    // The triangles using every vertex, with the ones already emitted removed.
+    let mut adjacent = vec![vec![]; count];
+    for (t, triangle) in triangles.iter().enumerate() {
+        for &v in triangle {
+            adjacent[v].push(t);
+        }
+    }
+    let mut scores: Vec<f32> = adjacent.iter().map(|a| score(None, a.len())).collect();
+    let triangle_score = |scores: &[f32], t: usize| {
+        triangles[t].iter().map(|&v| scores[v]).sum()
+    };
+    let mut triangle_scores: Vec<f32> = (0..triangles.len())
+        .map(|t| triangle_score(&scores, t))
+        .collect();
+    let mut emitted = vec![false; triangles.len()];
+    let mut cache: Vec<usize> = vec![];
+    let mut order = Vec::with_capacity(triangles.len());
+    // Where to look for the best triangle when none is adjacent to the cache.
+    let mut next = 0;
+    let mut best = best_of(0..FALLBACK_WINDOW.min(triangles.len()), &triangle_scores, &emitted);
+
+    while let Some(t) = best {
+        emitted[t] = true;
+        order.push(triangles[t]);
+        for &v in &triangles[t] {
+            adjacent[v].retain(|&a| a != t);
+        }
+
+        // The vertices of the triangle move to the front of the cache, pushing the others back.
+        let mut updated: Vec<usize> = triangles[t].to_vec();
+        updated.extend(cache.iter().filter(|v| !triangles[t].contains(v)));
+        let evicted: Vec<usize> = updated.split_off(CACHE_SIZE.min(updated.len()));
+        for &v in &evicted {
+            scores[v] = score(None, adjacent[v].len());
+        }
+        for (p, &v) in updated.iter().enumerate() {
+            scores[v] = score(Some(p), adjacent[v].len());
+        }
+        cache = updated;
+
+        for &v in cache.iter().chain(&evicted) {
+            for &a in &adjacent[v] {
+                triangle_scores[a] = triangle_score(&scores, a);
+            }
+        }
+        best = best_of(cache.iter().flat_map(|&v| adjacent[v].iter().cloned()),
+            &triangle_scores, &emitted);
+        if best.is_none() {
+            while next < triangles.len() && emitted[next] {
+                next += 1;
+            }
+            let end = (next + FALLBACK_WINDOW).min(triangles.len());
+            best = best_of(next..end, &triangle_scores, &emitted);
+        }
+    }
+    order
+}
+
+/// Returns the triangle with the highest score among the ones not emitted yet.
+fn best_of<I>(triangles: I, scores: &[f32], emitted: &[bool]) -> Option<usize>
+    where I: Iterator<Item = usize>
+{
+    let mut best: Option<usize> = None;
+    for t in triangles.filter(|&t| !emitted[t]) {
+        if best.map_or(true, |b| scores[t] > scores[b]) {
+            best = Some(t);
+        }
+    }
+    best
+}
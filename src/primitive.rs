@@ -0,0 +1,135 @@
+//! Conversion of geometric primitives into lists.
+//!
+//! Strips, quads and meshes without index arrays are convenient to store, but most renderers only
+//! take lists of independent triangles or lines. The conversions keep the vertex arrays of the
+//! mesh as they are, and rebuild its index arrays, one for every index array of the original mesh
+//! so that each part keeps its material.
+//!
+//! Restart indices start a new strip, and are honored by every primitive: a list that is cut short
+//! by a restart index drops its incomplete primitive, like a list that ends early. Triangles of
+//! strips using the same vertex twice, which only serve to join strips together, are dropped.
+
+use structure::*;
+
+impl Mesh {
+    /// Converts the mesh into a list of independent triangles, splitting quads into two triangles
+    /// and unrolling triangle strips. Returns `None` if the primitives of the mesh are points or
+    /// lines.
+    ///
+    /// The odd triangles of a strip have their vertices swapped as documented by
+    /// `GeometricPrimitive::TriangleStrip`, so every triangle keeps the winding of its index array.
+    pub fn to_triangle_list(&self) -> Option<Mesh> {
+        let primitive = self.primitive;
+        let triangles = |indices: &[u64], out: &mut Vec<u64>| match primitive {
+            GeometricPrimitive::Triangles => {
+                for t in indices.chunks(3).filter(|t| t.len() == 3) {
+                    out.extend_from_slice(t);
+                }
+            }
+            GeometricPrimitive::TriangleStrip => {
+                for i in 0..indices.len().saturating_sub(2) {
+                    let (a, b, c) = (indices[i], indices[i + 1], indices[i + 2]);
+                    if a == b || b == c || a == c {
+                        continue;
+                    }
+                    if i % 2 == 0 {
+                        out.extend_from_slice(&[a, b, c]);
+                    } else {
+                        out.extend_from_slice(&[a, c, b]);
+                    }
+                }
+            }
+            GeometricPrimitive::Quads => {
+                for q in indices.chunks(4).filter(|q| q.len() == 4) {
+                    out.extend_from_slice(&[q[0], q[1], q[2], q[0], q[2], q[3]]);
+                }
+            }
+            _ => {}
+        };
+        match primitive {
+            GeometricPrimitive::Triangles |
+            GeometricPrimitive::TriangleStrip |
+            GeometricPrimitive::Quads => {
+                Some(self.to_list(GeometricPrimitive::Triangles, triangles))
+            }
+            _ => None
+        }
+    }
+
+    /// Converts the mesh into a list of independent lines, unrolling line strips. Returns `None`
+    /// if the primitives of the mesh are not lines.
+    pub fn to_line_list(&self) -> Option<Mesh> {
+        let primitive = self.primitive;
+        let lines = |indices: &[u64], out: &mut Vec<u64>| match primitive {
+            GeometricPrimitive::Lines => {
+                for l in indices.chunks(2).filter(|l| l.len() == 2) {
+                    out.extend_from_slice(l);
+                }
+            }
+            GeometricPrimitive::LineStrip => {
+                for l in indices.windows(2) {
+                    out.extend_from_slice(l);
+                }
+            }
+            _ => {}
+        };
+        match primitive {
+            GeometricPrimitive::Lines | GeometricPrimitive::LineStrip => {
+                Some(self.to_list(GeometricPrimitive::Lines, lines))
+            }
+            _ => None
+        }
+    }
+
+    /// Rebuilds the index arrays of the mesh, converting every run of indices between restart
+    /// indices with `convert`.
+    fn to_list<F>(&self, primitive: GeometricPrimitive, convert: F) -> Mesh
+        where F: Fn(&[u64], &mut Vec<u64>)
+    {
+        let index_arrays = if self.index_arrays.is_empty() {
+            let count = vertex_count(self);
+            let mut indices = vec![];
+            convert(&(0..count as u64).collect::<Vec<_>>(), &mut indices);
+            vec![IndexArray {
+                material: 0,
+                restart: None,
+                front_face: FrontFace::default(),
+                data: index_data(count, indices)
+            }]
+        } else {
+            self.index_arrays.iter().map(|array| {
+                let mut indices = vec![];
+                let values = array.data.to_u64();
+                for run in values.split(|&i| Some(i) == array.restart) {
+                    convert(run, &mut indices);
+                }
+                IndexArray {
+                    restart: None,
+                    data: array.data.with_values(indices),
+                    ..array.clone()
+                }
+            }).collect()
+        };
+        Mesh {
+            primitive,
+            index_arrays,
+            ..self.clone()
+        }
+    }
+}
+
+/// Returns the number of vertices of a mesh, from its positions if it has some.
+pub fn vertex_count(mesh: &Mesh) -> usize {
+    let arrays = || mesh.vertex_arrays.iter().filter(|a| a.morph == 0);
+    arrays().find(|a| a.attrib == "position").or_else(|| arrays().next()).map_or(0, |a| a.len())
+}
+
+/// Returns index data for a mesh with `count` vertices, using 16-bit indices if they fit with
+/// room left for a restart index, and 32-bit indices otherwise.
+pub fn index_data(count: usize, indices: Vec<u64>) -> IndexData {
+    if count <= u16::MAX as usize {
+        IndexData::U16(indices.into_iter().map(|i| i as u16).collect())
+    } else {
+        IndexData::U32(indices.into_iter().map(|i| i as u32).collect())
+    }
+}
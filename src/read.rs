@@ -0,0 +1,1019 @@
+//! Loading of OpenGEX files into the structures of the `structure` module.
+//!
+//! The source text is parsed with the native OpenDDL parser in the `ddl` module, after which every
+//! OpenGEX structure is converted into its typed counterpart. Structures that are not part of the
+//! OpenGEX specification are ignored, as the specification allows applications to define their
+//! own structures.
+//!
+//! References between structures are resolved following the OpenDDL naming rules, and every
+//! referenced object is loaded once and shared through an `Arc`.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+use vec_map::VecMap;
+
+use ddl::{ self, Data, DerivedStructure, Literal, Reference, Structure };
+use error::{ Error, ErrorKind, ReferenceError };
+use structure::*;
+
+/// An error at a byte offset of the source text. It is turned into an `Error` once loading has
+/// failed, as locating it requires the source text.
+struct Failure {
+    offset: usize,
+    kind: ErrorKind
+}
+
+impl FromStr for Scene {
+    type Err = Error;
+
+    /// Loads a scene from OpenGEX source text.
+    fn from_str(source: &str) -> Result<Scene, Error> {
+        let structures = ddl::parse(source).map_err(|err| Error::parse(err, source))?;
+        Loader::new(&structures).and_then(|loader| loader.scene(&structures))
+            .map_err(|err| Error::new(err.kind, source, err.offset))
+    }
+}
+
+impl Scene {
+    /// Loads a scene by reading OpenGEX source text from a reader.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Scene, Error> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        source.parse()
+    }
+}
+
+fn invalid<T>(s: &DerivedStructure, message: String) -> Result<T, Failure> {
+    Err(Failure { offset: s.offset, kind: ErrorKind::Invalid(message) })
+}
+
+fn reference_error<T>(offset: usize, error: ReferenceError) -> Result<T, Failure> {
+    Err(Failure { offset, kind: ErrorKind::Reference(error) })
+}
+
+/// Iterates over the derived substructures of a list of structures.
+fn derived(structures: &[Structure]) -> impl Iterator<Item = &DerivedStructure> {
+    structures.iter().filter_map(|s| match *s {
+        Structure::Derived(ref s) => Some(s),
+        Structure::Primitive(_) => None
+    })
+}
+
+/// Returns the data of the single primitive substructure of a structure.
+fn data(s: &DerivedStructure) -> Result<(&Data, Option<usize>), Failure> {
+    let mut primitives = s.children.iter().filter_map(|s| match *s {
+        Structure::Primitive(ref p) => Some(p),
+        Structure::Derived(_) => None
+    });
+    match (primitives.next(), primitives.next()) {
+        (Some(p), None) => Ok((&p.data, p.array_size)),
+        (None, _) => invalid(s, format!("{} must contain a primitive structure", s.identifier)),
+        (Some(_), Some(_)) => {
+            invalid(s, format!("{} must contain a single primitive structure", s.identifier))
+        }
+    }
+}
+
+/// Describes the data of a primitive structure, for error messages.
+fn found(data: &Data, array_size: Option<usize>) -> String {
+    match array_size {
+        Some(n) => format!("{}[{}] data", data.data_type(), n),
+        None => format!("{} data", data.data_type())
+    }
+}
+
+/// Returns the floating-point data of a structure, checking its subarray size.
+fn floats(s: &DerivedStructure, array_size: Option<usize>) -> Result<Vec<f32>, Failure> {
+    let (data, size) = data(s)?;
+    let values = match *data {
+        Data::Half(ref v) | Data::Float(ref v) => v.clone(),
+        Data::Double(ref v) => v.iter().map(|&x| x as f32).collect(),
+        _ => {
+            return invalid(s, format!("{} must contain float data, found {}",
+                s.identifier, found(data, size)));
+        }
+    };
+    if size != array_size && !(size == Some(1) && array_size.is_none()) {
+        return invalid(s, match array_size {
+            Some(n) => format!("{} must contain float[{}] data, found {}",
+                s.identifier, n, found(data, size)),
+            None => format!("{} must not contain subarrays, found {}",
+                s.identifier, found(data, size))
+        });
+    }
+    Ok(values)
+}
+
+/// Returns the only floating-point value of a structure.
+fn float(s: &DerivedStructure) -> Result<f32, Failure> {
+    let values = floats(s, None)?;
+    if values.len() != 1 {
+        return invalid(s, format!("{} must contain a single float, found {} values",
+            s.identifier, values.len()));
+    }
+    Ok(values[0])
+}
+
+/// Returns the only string value of a structure.
+fn string(s: &DerivedStructure) -> Result<String, Failure> {
+    match data(s)? {
+        (Data::String(v), None) if v.len() == 1 => Ok(v[0].clone()),
+        (Data::String(v), None) => invalid(s, format!("{} must contain a single string, found {}",
+            s.identifier, v.len())),
+        (data, size) => invalid(s, format!("{} must contain a single string, found {}",
+            s.identifier, found(data, size)))
+    }
+}
+
+/// Returns the only reference value of a structure.
+fn reference(s: &DerivedStructure) -> Result<&Reference, Failure> {
+    match data(s)? {
+        (Data::Ref(v), None) if v.len() == 1 => Ok(&v[0]),
+        (Data::Ref(v), None) => invalid(s, format!("{} must contain a single reference, found {}",
+            s.identifier, v.len())),
+        (data, size) => invalid(s, format!("{} must contain a single reference, found {}",
+            s.identifier, found(data, size)))
+    }
+}
+
+fn string_property<'a>(s: &'a DerivedStructure, id: &str) -> Result<Option<&'a str>, Failure> {
+    match s.property(id) {
+        Some(Literal::String(v)) => Ok(Some(v)),
+        None => Ok(None),
+        Some(_) => invalid(s, format!("property `{}` must be a string", id))
+    }
+}
+
+fn bool_property(s: &DerivedStructure, id: &str) -> Result<Option<bool>, Failure> {
+    match s.property(id) {
+        Some(&Literal::Bool(v)) => Ok(Some(v)),
+        None => Ok(None),
+        Some(_) => invalid(s, format!("property `{}` must be a boolean", id))
+    }
+}
+
+fn u32_property(s: &DerivedStructure, id: &str) -> Result<Option<u32>, Failure> {
+    match s.property(id) {
+        Some(&Literal::Integer(v)) if v >= 0 && v <= u32::MAX as i64 => Ok(Some(v as u32)),
+        None => Ok(None),
+        Some(_) => invalid(s, format!("property `{}` must be an unsigned integer", id))
+    }
+}
+
+fn u64_property(s: &DerivedStructure, id: &str) -> Result<Option<u64>, Failure> {
+    match s.property(id) {
+        Some(&Literal::Integer(v)) if v >= 0 => Ok(Some(v as u64)),
+        Some(&Literal::Unsigned(v)) => Ok(Some(v)),
+        None => Ok(None),
+        Some(_) => invalid(s, format!("property `{}` must be an unsigned integer", id))
+    }
+}
+
+fn float_property(s: &DerivedStructure, id: &str) -> Result<Option<f32>, Failure> {
+    match s.property(id) {
+        Some(&Literal::Float(v)) => Ok(Some(v as f32)),
+        Some(&Literal::Integer(v)) => Ok(Some(v as f32)),
+        Some(&Literal::Unsigned(v)) => Ok(Some(v as f32)),
+        None => Ok(None),
+        Some(_) => invalid(s, format!("property `{}` must be a number", id))
+    }
+}
+
+/// Returns the `attrib` property every Color, Param and Texture structure must have.
+fn attrib(s: &DerivedStructure) -> Result<String, Failure> {
+    match string_property(s, "attrib")? {
+        Some(attrib) => Ok(attrib.to_string()),
+        None => invalid(s, format!("{} must have an `attrib` property", s.identifier))
+    }
+}
+
+fn color(s: &DerivedStructure) -> Result<Color, Failure> {
+    let size = data(s)?.1;
+    let v = floats(s, size)?;
+    match (size, v.len()) {
+        (Some(3), 3) => Ok(Color::Rgb(v[0], v[1], v[2])),
+        (Some(4), 4) => Ok(Color::Rgba(v[0], v[1], v[2], v[3])),
+        _ => invalid(s, "Color must contain a single float[3] or float[4]".to_string())
+    }
+}
+
+/// Loads a Transform structure holding a single matrix.
+fn transform(s: &DerivedStructure) -> Result<Transform, Failure> {
+    let v = floats(s, Some(16))?;
+    if v.len() != 16 {
+        return invalid(s, "Transform must contain a single matrix".to_string());
+    }
+    let mut matrix = [0.0; 16];
+    matrix.copy_from_slice(&v);
+    Ok(Transform(matrix))
+}
+
+fn transformation(s: &DerivedStructure) -> Result<Option<Transformation>, Failure> {
+    let kind = string_property(s, "kind")?;
+    Ok(Some(match s.identifier.as_str() {
+        "Transform" => Transformation::Transform(transform(s)?),
+        "Translation" => Transformation::Translation(match kind.unwrap_or("xyz") {
+            "x" => Translation::X(float(s)?),
+            "y" => Translation::Y(float(s)?),
+            "z" => Translation::Z(float(s)?),
+            "xyz" => {
+                let v = vector(s, 3)?;
+                Translation::Xyz(v[0], v[1], v[2])
+            }
+            kind => return invalid(s, format!("unknown Translation kind \"{}\"", kind))
+        }),
+        "Rotation" => Transformation::Rotation(match kind.unwrap_or("axis") {
+            "x" => Rotation::X(float(s)?),
+            "y" => Rotation::Y(float(s)?),
+            "z" => Rotation::Z(float(s)?),
+            "axis" => {
+                let v = vector(s, 4)?;
+                Rotation::Axis(v[0], v[1], v[2], v[3])
+            }
+            "quaternion" => {
+                let v = vector(s, 4)?;
+                Rotation::Quaternion(v[0], v[1], v[2], v[3])
+            }
+            kind => return invalid(s, format!("unknown Rotation kind \"{}\"", kind))
+        }),
+        "Scale" => Transformation::Scale(match kind.unwrap_or("xyz") {
+            "x" => Scale::X(float(s)?),
+            "y" => Scale::Y(float(s)?),
+            "z" => Scale::Z(float(s)?),
+            "xyz" => {
+                let v = vector(s, 3)?;
+                Scale::Xyz(v[0], v[1], v[2])
+            }
+            kind => return invalid(s, format!("unknown Scale kind \"{}\"", kind))
+        }),
+        _ => return Ok(None)
+    }))
+}
+
+/// Returns the single vector with `n` components contained in a structure.
+fn vector(s: &DerivedStructure, n: usize) -> Result<Vec<f32>, Failure> {
+    let v = floats(s, Some(n))?;
+    if v.len() != n {
+        return invalid(s, format!("{} must contain a single float[{}]", s.identifier, n));
+    }
+    Ok(v)
+}
+
+/// Collects the key data of a Time or Value structure by the `kind` property of each Key.
+fn keys(s: &DerivedStructure) -> Result<HashMap<String, Vec<f32>>, Failure> {
+    let mut keys = HashMap::new();
+    for key in derived(&s.children).filter(|k| k.identifier == "Key") {
+        let kind = string_property(key, "kind")?.unwrap_or("value").to_string();
+        let size = data(key)?.1;
+        if keys.insert(kind, floats(key, size)?).is_some() {
+            return invalid(key, format!("duplicate Key kind in {}", s.identifier));
+        }
+    }
+    Ok(keys)
+}
+
+/// Removes the key data of the given kind, checking that it holds `len` values.
+fn take_key(s: &DerivedStructure, keys: &mut HashMap<String, Vec<f32>>, kind: &str, len: usize)
+    -> Result<Vec<f32>, Failure>
+{
+    match keys.remove(kind) {
+        Some(ref v) if v.len() == len => Ok(v.clone()),
+        Some(_) => invalid(s, format!("Key (kind = \"{}\") must hold {} values", kind, len)),
+        None => invalid(s, format!("{} is missing a Key (kind = \"{}\")", s.identifier, kind))
+    }
+}
+
+fn time(s: &DerivedStructure) -> Result<Time, Failure> {
+    let mut keys = keys(s)?;
+    let value = match keys.get("value") {
+        Some(value) => value.clone(),
+        None => return invalid(s, "Time is missing a Key".to_string())
+    };
+    let len = value.len();
+    match string_property(s, "curve")?.unwrap_or("linear") {
+        "linear" => Ok(Time::Linear(value)),
+        "bezier" => {
+            let minus = take_key(s, &mut keys, "-control", len)?;
+            let plus = take_key(s, &mut keys, "+control", len)?;
+            Ok(Time::Bezier((0..len).map(|i| (value[i], minus[i], plus[i])).collect()))
+        }
+        curve => invalid(s, format!("unknown Time curve \"{}\"", curve))
+    }
+}
+
+fn value(s: &DerivedStructure, key_count: usize) -> Result<Value, Failure> {
+    let mut keys = keys(s)?;
+    let value = match keys.get("value") {
+        Some(value) => value.clone(),
+        None => return invalid(s, "Value is missing a Key".to_string())
+    };
+    let len = value.len();
+    if key_count == 0 || len % key_count != 0 {
+        return invalid(s, "Value must hold a value for every key time".to_string());
+    }
+    let components = len / key_count;
+    match string_property(s, "curve")?.unwrap_or("linear") {
+        "constant" => Ok(Value::Constant(value)),
+        "linear" => Ok(Value::Linear(value)),
+        "bezier" => {
+            let minus = take_key(s, &mut keys, "-control", len)?;
+            let plus = take_key(s, &mut keys, "+control", len)?;
+            Ok(Value::Bezier((0..len).map(|i| (value[i], minus[i], plus[i])).collect()))
+        }
+        "tcb" => {
+            let tension = take_key(s, &mut keys, "tension", key_count)?;
+            let continuity = take_key(s, &mut keys, "continuity", key_count)?;
+            let bias = take_key(s, &mut keys, "bias", key_count)?;
+            Ok(Value::Tcb((0..len).map(|i| {
+                let k = i / components;
+                (value[i], tension[k], bias[k], continuity[k])
+            }).collect()))
+        }
+        curve => invalid(s, format!("unknown Value curve \"{}\"", curve))
+    }
+}
+
+/// The structures a Track may target.
+const TRACK_TARGETS: &[&str] = &["Translation", "Rotation", "Scale", "Transform", "MorphWeight"];
+
+/// Loads an Animation structure. The tracks are resolved against `targets`, which maps the offsets
+/// of the Transformation and MorphWeight structures of the animated node to their shared values.
+fn animation(s: &DerivedStructure, names: &Names, targets: &HashMap<usize, TrackTarget>)
+    -> Result<Animation, Failure>
+{
+    let mut tracks = vec![];
+    for track in derived(&s.children).filter(|t| t.identifier == "Track") {
+        let r = match track.property("target") {
+            Some(Literal::Ref(r)) => r,
+            _ => return invalid(track, "Track must have a `target` property".to_string())
+        };
+        let structure = names.resolve(track, r)?;
+        let target = match targets.get(&structure.offset()) {
+            Some(target) => target.clone(),
+            None if TRACK_TARGETS.contains(&identifier(structure).as_str()) => {
+                return invalid(track,
+                    format!("Track target {} does not belong to the animated structure", r));
+            }
+            None => return reference_error(track.offset, ReferenceError::WrongType {
+                reference: r.clone(),
+                expected: TRACK_TARGETS,
+                found: identifier(structure)
+            })
+        };
+        let time = match derived(&track.children).find(|t| t.identifier == "Time") {
+            Some(t) => time(t)?,
+            None => return invalid(track, "Track is missing a Time structure".to_string())
+        };
+        let key_count = time.len();
+        let value = match derived(&track.children).find(|t| t.identifier == "Value") {
+            Some(v) => value(v, key_count)?,
+            None => return invalid(track, "Track is missing a Value structure".to_string())
+        };
+        tracks.push(Track { target, time, value });
+    }
+    Ok(Animation {
+        clip: u32_property(s, "clip")?.unwrap_or(0),
+        begin: float_property(s, "begin")?,
+        end: float_property(s, "end")?,
+        tracks
+    })
+}
+
+/// Loads the transformations among `children` and registers them as track targets.
+fn transformations(children: &[Structure], targets: &mut HashMap<usize, TrackTarget>)
+    -> Result<Vec<Arc<Transformation>>, Failure>
+{
+    let mut transformations = vec![];
+    for s in derived(children) {
+        if let Some(t) = transformation(s)? {
+            let t = Arc::new(t);
+            targets.insert(s.offset, TrackTarget::Transformation(t.clone()));
+            transformations.push(t);
+        }
+    }
+    Ok(transformations)
+}
+
+fn texture(s: &DerivedStructure, names: &Names) -> Result<Texture, Failure> {
+    let mut targets = HashMap::new();
+    let transformations = transformations(&s.children, &mut targets)?;
+    let mut animation = vec![];
+    for a in derived(&s.children).filter(|a| a.identifier == "Animation") {
+        animation.push(self::animation(a, names, &targets)?);
+    }
+    Ok(Texture {
+        texcoord: u32_property(s, "texcoord")?.unwrap_or(0),
+        file_name: string(s)?,
+        transformations,
+        animation
+    })
+}
+
+/// The colors, parameters and textures of a material, light or camera.
+type Attributes = (HashMap<String, Color>, ParamMap, HashMap<String, Texture>);
+
+/// Collects the Color, Param and Texture structures among `children`.
+fn attributes(children: &[Structure], names: &Names) -> Result<Attributes, Failure> {
+    let mut colors = HashMap::new();
+    let mut params = HashMap::new();
+    let mut textures = HashMap::new();
+    for s in derived(children) {
+        match s.identifier.as_str() {
+            "Color" => { colors.insert(attrib(s)?, color(s)?); }
+            "Param" => { params.insert(attrib(s)?, float(s)?); }
+            "Texture" => { textures.insert(attrib(s)?, texture(s, names)?); }
+            _ => {}
+        }
+    }
+    Ok((colors, params, textures))
+}
+
+/// Returns the string of the Name substructure among `children`, if there is one.
+fn name(children: &[Structure]) -> Result<Option<Name>, Failure> {
+    match derived(children).find(|s| s.identifier == "Name") {
+        Some(s) => Ok(Some(string(s)?)),
+        None => Ok(None)
+    }
+}
+
+fn material(s: &DerivedStructure, names: &Names) -> Result<Material, Failure> {
+    let (color, param, texture) = attributes(&s.children, names)?;
+    Ok(Material {
+        two_sided: bool_property(s, "two_sided")?.unwrap_or(false),
+        name: name(&s.children)?,
+        color,
+        param,
+        texture
+    })
+}
+
+fn clip(s: &DerivedStructure, names: &Names) -> Result<Clip, Failure> {
+    Ok(Clip { name: name(&s.children)?, param: attributes(&s.children, names)?.1 })
+}
+
+fn camera_object(s: &DerivedStructure, names: &Names) -> Result<CameraObject, Failure> {
+    let (colors, params, textures) = attributes(&s.children, names)?;
+    Ok(CameraObject { params, colors, textures })
+}
+
+fn atten(s: &DerivedStructure, names: &Names) -> Result<Atten, Failure> {
+    let kind = match string_property(s, "kind")?.unwrap_or("distance") {
+        "distance" => AttenuationKind::Distance,
+        "angle" => AttenuationKind::Angle,
+        "cos_angle" => AttenuationKind::CosAngle,
+        kind => return invalid(s, format!("unknown Atten kind \"{}\"", kind))
+    };
+    let curve = match string_property(s, "curve")?.unwrap_or("linear") {
+        "linear" => AttenuationCurve::Linear,
+        "smooth" => AttenuationCurve::Cubic,
+        "inverse" => AttenuationCurve::Inverse,
+        "inverse_square" => AttenuationCurve::InverseSquare,
+        curve => return invalid(s, format!("unknown Atten curve \"{}\"", curve))
+    };
+    Ok(Atten { kind, curve, params: attributes(&s.children, names)?.1 })
+}
+
+fn light_object(s: &DerivedStructure, names: &Names) -> Result<LightObject, Failure> {
+    let light_type = match string_property(s, "type")? {
+        Some("infinite") => LightType::Infinite,
+        Some("point") => LightType::Point,
+        Some("spot") => LightType::Spot,
+        Some(t) => return invalid(s, format!("unknown LightObject type \"{}\"", t)),
+        None => return invalid(s, "LightObject must have a `type` property".to_string())
+    };
+    let (colors, params, textures) = attributes(&s.children, names)?;
+    let mut attenuations = vec![];
+    for a in derived(&s.children).filter(|a| a.identifier == "Atten") {
+        attenuations.push(atten(a, names)?);
+    }
+    Ok(LightObject {
+        light_type,
+        casts_shadows: bool_property(s, "shadow")?.unwrap_or(true),
+        colors,
+        params,
+        textures,
+        attenuations
+    })
+}
+
+fn vertex_array(s: &DerivedStructure) -> Result<VertexArray, Failure> {
+    let attrib = self::attrib(s)?;
+    // An array index may be appended to the attribute, as in "texcoord[1]".
+    let (attrib, index) = match attrib.find('[') {
+        Some(n) if attrib.ends_with(']') => match attrib[n + 1..attrib.len() - 1].parse() {
+            Ok(index) => (attrib[..n].to_string(), index),
+            Err(_) => return invalid(s, format!("invalid attribute \"{}\"", attrib))
+        },
+        _ => (attrib, 0)
+    };
+    let (data, size) = data(s)?;
+    let data = match *data {
+        Data::Half(ref v) => VertexData::Half(v.clone()),
+        Data::Float(ref v) => VertexData::Float(v.clone()),
+        Data::Double(ref v) => VertexData::Double(v.clone()),
+        _ => {
+            return invalid(s, format!("VertexArray must contain floating-point data, found {}",
+                found(data, size)));
+        }
+    };
+    Ok(VertexArray {
+        attrib,
+        index,
+        morph: u32_property(s, "morph")?.unwrap_or(0),
+        components: size.unwrap_or(1),
+        data
+    })
+}
+
+/// Returns the unsigned integer data of a structure, along with its subarray size.
+fn unsigned(s: &DerivedStructure) -> Result<(IndexData, Option<usize>), Failure> {
+    let (data, size) = data(s)?;
+    let data = match *data {
+        Data::UnsignedInt8(ref v) => IndexData::U8(v.clone()),
+        Data::UnsignedInt16(ref v) => IndexData::U16(v.clone()),
+        Data::UnsignedInt32(ref v) => IndexData::U32(v.clone()),
+        Data::UnsignedInt64(ref v) => IndexData::U64(v.clone()),
+        _ => {
+            return invalid(s, format!("{} must contain unsigned integer data, found {}",
+                s.identifier, found(data, size)));
+        }
+    };
+    Ok((data, size))
+}
+
+fn index_array(s: &DerivedStructure) -> Result<IndexArray, Failure> {
+    let front_face = match string_property(s, "front")?.unwrap_or("ccw") {
+        "ccw" => FrontFace::CounterClockwise,
+        "cw" => FrontFace::Clockwise,
+        front => return invalid(s, format!("unknown IndexArray front \"{}\"", front))
+    };
+    Ok(IndexArray {
+        material: u32_property(s, "material")?.unwrap_or(0),
+        restart: u64_property(s, "restart")?,
+        front_face,
+        data: unsigned(s)?.0
+    })
+}
+
+fn primitive(s: &DerivedStructure) -> Result<GeometricPrimitive, Failure> {
+    Ok(match string_property(s, "primitive")?.unwrap_or("triangles") {
+        "points" => GeometricPrimitive::Points,
+        "lines" => GeometricPrimitive::Lines,
+        "line_strip" => GeometricPrimitive::LineStrip,
+        "triangles" => GeometricPrimitive::Triangles,
+        "triangle_strip" => GeometricPrimitive::TriangleStrip,
+        "quads" => GeometricPrimitive::Quads,
+        primitive => return invalid(s, format!("unknown Mesh primitive \"{}\"", primitive))
+    })
+}
+
+/// Loads a Metric structure into the metrics of a scene.
+fn metric(s: &DerivedStructure, metric: &mut Metric) -> Result<(), Failure> {
+    let axis = |s| Ok(match string(s)?.as_str() {
+        "x" => Axis::X,
+        "y" => Axis::Y,
+        "z" => Axis::Z,
+        "-x" => Axis::NegX,
+        "-y" => Axis::NegY,
+        "-z" => Axis::NegZ,
+        axis => return invalid(s, format!("unknown Metric axis \"{}\"", axis))
+    });
+    match string_property(s, "key")? {
+        Some("distance") => metric.distance = float(s)?,
+        Some("angle") => metric.angle = float(s)?,
+        Some("time") => metric.time = float(s)?,
+        Some("up") => metric.up = axis(s)?,
+        Some("forward") => metric.forward = Some(axis(s)?),
+        Some(key) => {
+            let value = match data(s)?.0 {
+                Data::String(_) => MetricValue::String(string(s)?),
+                _ => MetricValue::Float(float(s)?)
+            };
+            metric.others.insert(key.to_string(), value);
+        }
+        None => return invalid(s, "Metric must have a `key` property".to_string())
+    }
+    Ok(())
+}
+
+/// Returns the single substructure with the given identifier.
+fn child<'a>(s: &'a DerivedStructure, id: &str) -> Result<&'a DerivedStructure, Failure> {
+    let mut children = derived(&s.children).filter(|c| c.identifier == id);
+    match (children.next(), children.next()) {
+        (Some(c), None) => Ok(c),
+        (None, _) => invalid(s, format!("{} is missing a {} structure", s.identifier, id)),
+        (Some(_), Some(c)) => invalid(c, format!("{} must contain a single {}", s.identifier, id))
+    }
+}
+
+/// The names of the structures of a file, following the OpenDDL scoping rules: global names are
+/// unique in the whole file, while local names are unique among the substructures of a structure.
+struct Names<'a> {
+    globals: HashMap<&'a str, &'a Structure>,
+    /// The local names, keyed by the offset of the structure containing them, or `None` for the
+    /// top level of the file.
+    locals: HashMap<(Option<usize>, &'a str), &'a Structure>,
+    /// The offset of the structure containing each derived structure.
+    parents: HashMap<usize, Option<usize>>
+}
+
+impl<'a> Names<'a> {
+    /// Collects the names of a file, checking that they are unique.
+    fn new(structures: &'a [Structure]) -> Result<Names<'a>, Failure> {
+        let mut names = Names {
+            globals: HashMap::new(),
+            locals: HashMap::new(),
+            parents: HashMap::new()
+        };
+        names.add(structures, None)?;
+        Ok(names)
+    }
+
+    fn add(&mut self, structures: &'a [Structure], parent: Option<usize>) -> Result<(), Failure> {
+        for s in structures {
+            let duplicate = match s.name() {
+                Some(ddl::Name::Global(id)) => self.globals.insert(id, s).is_some(),
+                Some(ddl::Name::Local(id)) => self.locals.insert((parent, id), s).is_some(),
+                None => false
+            };
+            if duplicate {
+                return reference_error(s.offset(),
+                    ReferenceError::Duplicate(s.name().unwrap().clone()));
+            }
+            if let Structure::Derived(ref d) = *s {
+                self.parents.insert(d.offset, parent);
+                self.add(&d.children, Some(d.offset))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the structure a reference made in the structure `s` refers to.
+    ///
+    /// A reference starting with a local name is looked up among the substructures of `s`, then
+    /// among the substructures of each enclosing structure in turn, up to the top level.
+    fn resolve(&self, s: &DerivedStructure, r: &Reference) -> Result<&'a Structure, Failure> {
+        let mut names = r.0.iter();
+        let mut target = match names.next() {
+            Some(ddl::Name::Global(id)) => self.globals.get(id.as_str()).cloned(),
+            Some(ddl::Name::Local(id)) => {
+                let mut scope = Some(s.offset);
+                loop {
+                    if let Some(&target) = self.locals.get(&(scope, id.as_str())) {
+                        break Some(target);
+                    }
+                    match scope {
+                        Some(offset) => scope = self.parents[&offset],
+                        None => break None
+                    }
+                }
+            }
+            None => None
+        };
+        for name in names {
+            target = match (target, name) {
+                (Some(t), ddl::Name::Local(id)) => {
+                    self.locals.get(&(Some(t.offset()), id.as_str())).cloned()
+                }
+                _ => None
+            };
+        }
+        match target {
+            Some(target) => Ok(target),
+            None => reference_error(s.offset, ReferenceError::Unresolved(r.clone()))
+        }
+    }
+}
+
+/// Describes the type of a structure: its identifier, or the data type of a primitive structure.
+fn identifier(s: &Structure) -> String {
+    match *s {
+        Structure::Derived(ref d) => d.identifier.clone(),
+        Structure::Primitive(ref p) => p.data.data_type().to_string()
+    }
+}
+
+/// Loads the objects of a scene. Objects and bone nodes are loaded the first time they are
+/// referenced, and shared by every structure referencing them afterwards.
+struct Loader<'a> {
+    names: Names<'a>,
+    // The loaded objects, keyed by the offset of their structure.
+    geometry_objects: HashMap<usize, Arc<GeometryObject>>,
+    light_objects: HashMap<usize, Arc<LightObject>>,
+    camera_objects: HashMap<usize, Arc<CameraObject>>,
+    materials: HashMap<usize, Arc<Material>>,
+    bone_nodes: HashMap<usize, Arc<BoneNode>>,
+    /// The offsets of the objects currently being loaded, used to detect cyclic references.
+    loading: Vec<usize>
+}
+
+/// Selects the cache of loaded objects of one type.
+type Cache<'a, T> = for<'l> fn(&'l mut Loader<'a>) -> &'l mut HashMap<usize, Arc<T>>;
+
+impl<'a> Loader<'a> {
+    fn new(structures: &'a [Structure]) -> Result<Loader<'a>, Failure> {
+        Ok(Loader {
+            names: Names::new(structures)?,
+            geometry_objects: HashMap::new(),
+            light_objects: HashMap::new(),
+            camera_objects: HashMap::new(),
+            materials: HashMap::new(),
+            bone_nodes: HashMap::new(),
+            loading: vec![]
+        })
+    }
+
+    fn scene(mut self, structures: &'a [Structure]) -> Result<Scene, Failure> {
+        let mut scene = Scene::default();
+        for s in derived(structures) {
+            match s.identifier.as_str() {
+                "Metric" => metric(s, &mut scene.metric)?,
+                "GeometryObject" => scene.geometry_objects.push(self.geometry_object(s)?),
+                "LightObject" => scene.light_objects.push(self.light_object(s)?),
+                "CameraObject" => scene.camera_objects.push(self.camera_object(s)?),
+                "Material" => scene.materials.push(self.material(s)?),
+                "Clip" => {
+                    let index = u32_property(s, "index")?.unwrap_or(0);
+                    let clip = clip(s, &self.names)?;
+                    if scene.clip_structures.insert(index as usize, clip).is_some() {
+                        return invalid(s, format!("duplicate Clip index {}", index));
+                    }
+                }
+                _ => {}
+            }
+        }
+        scene.nodes = self.nodes(structures)?;
+        Ok(scene)
+    }
+
+    /// Returns the shared object loaded from a structure, loading it if needed.
+    fn shared<T>(&mut self, s: &'a DerivedStructure, cache: Cache<'a, T>,
+        load: fn(&mut Loader<'a>, &'a DerivedStructure) -> Result<T, Failure>)
+        -> Result<Arc<T>, Failure>
+    {
+        if let Some(object) = cache(self).get(&s.offset) {
+            return Ok(object.clone());
+        }
+        if self.loading.contains(&s.offset) {
+            return reference_error(s.offset, ReferenceError::Cyclic(s.identifier.clone()));
+        }
+        self.loading.push(s.offset);
+        let object = Arc::new(load(self, s)?);
+        self.loading.pop();
+        cache(self).insert(s.offset, object.clone());
+        Ok(object)
+    }
+
+    fn geometry_object(&mut self, s: &'a DerivedStructure) -> Result<Arc<GeometryObject>, Failure> {
+        self.shared(s, |l| &mut l.geometry_objects, |l, s| l.load_geometry_object(s))
+    }
+
+    fn light_object(&mut self, s: &'a DerivedStructure) -> Result<Arc<LightObject>, Failure> {
+        self.shared(s, |l| &mut l.light_objects, |l, s| light_object(s, &l.names))
+    }
+
+    fn camera_object(&mut self, s: &'a DerivedStructure) -> Result<Arc<CameraObject>, Failure> {
+        self.shared(s, |l| &mut l.camera_objects, |l, s| camera_object(s, &l.names))
+    }
+
+    fn material(&mut self, s: &'a DerivedStructure) -> Result<Arc<Material>, Failure> {
+        self.shared(s, |l| &mut l.materials, |l, s| material(s, &l.names))
+    }
+
+    fn bone_node(&mut self, s: &'a DerivedStructure) -> Result<Arc<BoneNode>, Failure> {
+        self.shared(s, |l| &mut l.bone_nodes, |l, s| {
+            let (name, transformations, animations, children) = l.node_content(s)?.common;
+            Ok(BoneNode { name, transformations, animations, children })
+        })
+    }
+
+    /// Looks up the structure a reference made in `s` refers to, checking its identifier.
+    fn target(&self, s: &DerivedStructure, r: &Reference, expected: &'static [&'static str])
+        -> Result<&'a DerivedStructure, Failure>
+    {
+        match *self.names.resolve(s, r)? {
+            Structure::Derived(ref target) if expected.contains(&target.identifier.as_str()) => {
+                Ok(target)
+            }
+            ref target => reference_error(s.offset, ReferenceError::WrongType {
+                reference: r.clone(),
+                expected,
+                found: identifier(target)
+            })
+        }
+    }
+
+    fn load_geometry_object(&mut self, s: &'a DerivedStructure) -> Result<GeometryObject, Failure> {
+        let mut meshes = VecMap::new();
+        let mut morphs = VecMap::new();
+        for child in derived(&s.children) {
+            match child.identifier.as_str() {
+                "Mesh" => {
+                    let lod = u32_property(child, "lod")?.unwrap_or(0) as usize;
+                    if meshes.insert(lod, self.mesh(child)?).is_some() {
+                        return invalid(child,
+                            format!("duplicate Mesh for level of detail {}", lod));
+                    }
+                }
+                "Morph" => {
+                    let index = u32_property(child, "index")?.unwrap_or(0) as usize;
+                    let morph = Morph {
+                        base_target_index: u32_property(child, "base")?,
+                        name: name(&child.children)?
+                    };
+                    if morphs.insert(index, morph).is_some() {
+                        return invalid(child,
+                            format!("duplicate Morph for target index {}", index));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(GeometryObject {
+            visible: bool_property(s, "visible")?.unwrap_or(true),
+            casts_shadows: bool_property(s, "shadow")?.unwrap_or(true),
+            motion_blur: bool_property(s, "motion_blur")?.unwrap_or(true),
+            meshes,
+            morphs
+        })
+    }
+
+    fn mesh(&mut self, s: &'a DerivedStructure) -> Result<Mesh, Failure> {
+        let primitive = primitive(s)?;
+        let mut vertex_arrays = vec![];
+        let mut index_arrays = vec![];
+        let mut skin = None;
+        for child in derived(&s.children) {
+            match child.identifier.as_str() {
+                "VertexArray" => vertex_arrays.push(vertex_array(child)?),
+                "IndexArray" => index_arrays.push(index_array(child)?),
+                "Skin" if skin.is_none() => skin = Some(self.skin(child)?),
+                "Skin" => return invalid(child, "Mesh must contain at most one Skin".to_string()),
+                _ => {}
+            }
+        }
+        Ok(Mesh { primitive, vertex_arrays, index_arrays, skin })
+    }
+
+    fn skin(&mut self, s: &'a DerivedStructure) -> Result<Skin, Failure> {
+        let transform = match derived(&s.children).find(|c| c.identifier == "Transform") {
+            Some(t) => Some(transform(t)?),
+            None => None
+        };
+        let skeleton = self.skeleton(child(s, "Skeleton")?)?;
+        let (bone_count_array, _) = unsigned(child(s, "BoneCountArray")?)?;
+        let (bone_index_array, _) = unsigned(child(s, "BoneIndexArray")?)?;
+        let bone_weight_array = floats(child(s, "BoneWeightArray")?, None)?;
+        Ok(Skin { transform, skeleton, bone_count_array, bone_index_array, bone_weight_array })
+    }
+
+    fn skeleton(&mut self, s: &'a DerivedStructure) -> Result<Skeleton, Failure> {
+        let refs = child(s, "BoneRefArray")?;
+        let mut bone_ref_array = vec![];
+        match data(refs)? {
+            (Data::Ref(v), None) => for r in v {
+                let target = self.target(refs, r, &["BoneNode"])?;
+                bone_ref_array.push(self.bone_node(target)?);
+            },
+            (data, size) => {
+                return invalid(refs,
+                    format!("BoneRefArray must contain references, found {}", found(data, size)));
+            }
+        }
+        let transforms = child(s, "Transform")?;
+        let matrices = floats(transforms, Some(16))?;
+        if matrices.len() != 16 * bone_ref_array.len() {
+            return invalid(transforms,
+                "Skeleton must contain a Transform matrix for every bone".to_string());
+        }
+        let transforms = matrices.chunks(16).map(|m| {
+            let mut matrix = [0.0; 16];
+            matrix.copy_from_slice(m);
+            Transform(matrix)
+        }).collect();
+        Ok(Skeleton { bone_ref_array, transforms })
+    }
+
+    fn nodes(&mut self, structures: &'a [Structure]) -> Result<Vec<Nodes>, Failure> {
+        let mut nodes = vec![];
+        for s in derived(structures) {
+            if let Some(node) = self.node(s)? {
+                nodes.push(node);
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Loads the substructures of a node structure.
+    fn node_content(&mut self, s: &'a DerivedStructure) -> Result<NodeContent<'a>, Failure> {
+        let mut targets = HashMap::new();
+        let transformations = transformations(&s.children, &mut targets)?;
+        let mut object_ref = None;
+        let mut materials = VecMap::new();
+        let mut morph_weights = vec![];
+        for child in derived(&s.children) {
+            match child.identifier.as_str() {
+                "ObjectRef" => object_ref = Some((child, reference(child)?)),
+                "MaterialRef" => {
+                    let index = u32_property(child, "index")?.unwrap_or(0) as usize;
+                    let target = self.target(child, reference(child)?, &["Material"])?;
+                    if materials.insert(index, self.material(target)?).is_some() {
+                        return invalid(child, format!("duplicate MaterialRef index {}", index));
+                    }
+                }
+                "MorphWeight" => {
+                    let weight = Arc::new(MorphWeight {
+                        target_index: u32_property(child, "index")?.unwrap_or(0),
+                        weight: float(child)?
+                    });
+                    targets.insert(child.offset, TrackTarget::MorphWeight(weight.clone()));
+                    morph_weights.push(weight);
+                }
+                _ => {}
+            }
+        }
+        let mut animations = vec![];
+        for a in derived(&s.children).filter(|a| a.identifier == "Animation") {
+            animations.push(animation(a, &self.names, &targets)?);
+        }
+        let name = name(&s.children)?;
+        let children = self.nodes(&s.children)?;
+        Ok(NodeContent {
+            common: (name, transformations, animations, children),
+            object_ref,
+            materials,
+            morph_weights
+        })
+    }
+
+    fn node(&mut self, s: &'a DerivedStructure) -> Result<Option<Nodes>, Failure> {
+        let kind = s.identifier.as_str();
+        match kind {
+            "BoneNode" => return Ok(Some(Nodes::BoneNode(self.bone_node(s)?))),
+            "Node" | "GeometryNode" | "LightNode" | "CameraNode" => {}
+            _ => return Ok(None)
+        }
+        let content = self.node_content(s)?;
+        let (name, transformations, animations, children) = content.common;
+        let (object_ref, r) = match (kind, content.object_ref) {
+            ("Node", _) => return Ok(Some(Nodes::Node(Node {
+                name,
+                transformations,
+                animations,
+                children
+            }))),
+            (_, Some(object_ref)) => object_ref,
+            (_, None) => return invalid(s, format!("{} must contain an ObjectRef", kind))
+        };
+        Ok(Some(match kind {
+            "GeometryNode" => Nodes::GeometryNode(GeometryNode {
+                name,
+                transformations,
+                animations,
+                children,
+                visibile: bool_property(s, "visible")?,
+                casts_shadows: bool_property(s, "shadow")?,
+                motion_blur: bool_property(s, "motion_blur")?,
+                geometry: {
+                    let target = self.target(object_ref, r, &["GeometryObject"])?;
+                    self.geometry_object(target)?
+                },
+                materials: content.materials,
+                morph_weights: content.morph_weights
+            }),
+            "LightNode" => Nodes::LightNode(LightNode {
+                name,
+                transformations,
+                animations,
+                children,
+                visibile: bool_property(s, "visible")?,
+                casts_shadows: bool_property(s, "shadow")?,
+                light: {
+                    let target = self.target(object_ref, r, &["LightObject"])?;
+                    self.light_object(target)?
+                }
+            }),
+            _ => Nodes::CameraNode(CameraNode {
+                name,
+                transformations,
+                animations,
+                children,
+                camera: {
+                    let target = self.target(object_ref, r, &["CameraObject"])?;
+                    self.camera_object(target)?
+                }
+            })
+        }))
+    }
+}
+
+/// The substructures of a node structure, before they are assembled into a node.
+struct NodeContent<'a> {
+    /// The name, transformations, animations and children every node has.
+    common: (Option<Name>, Vec<Arc<Transformation>>, Vec<Animation>, Vec<Nodes>),
+    object_ref: Option<(&'a DerivedStructure, &'a Reference)>,
+    materials: VecMap<Arc<Material>>,
+    morph_weights: Vec<Arc<MorphWeight>>
+}
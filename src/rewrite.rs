@@ -0,0 +1,232 @@
+//! Rebuilding of scenes while preserving the sharing of objects.
+//!
+//! Objects, bone nodes, transformations and morph weights are shared through `Arc`s, so changing
+//! one of them means building a new `Arc` and pointing every user of the old one at it. A
+//! `Rewrite` changes the parts of a scene, and `rewrite` takes care of the sharing: each object
+//! and bone node is rewritten once, and every reference to it is updated, including the node
+//! hierarchy, the object lists of the scene and the skeletons of skinned meshes.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use structure::*;
+
+/// Changes the parts of a scene. Every method does nothing by default.
+pub trait Rewrite {
+    /// Changes the parts of a node. Its children have already been rewritten. If a transformation
+    /// or morph weight is replaced, the animation tracks targeting it must be updated as well.
+    fn node(&mut self, _node: NodeParts) {}
+
+    /// Changes a geometry object. The bones of its skeletons have already been rewritten.
+    fn geometry_object(&mut self, _object: &mut GeometryObject) {}
+
+    /// Changes a light object.
+    fn light_object(&mut self, _object: &mut LightObject) {}
+
+    /// Changes a camera object.
+    fn camera_object(&mut self, _object: &mut CameraObject) {}
+
+    /// Changes a material.
+    fn material(&mut self, _material: &mut Material) {}
+}
+
+/// The kinds of nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Node,
+    BoneNode,
+    GeometryNode,
+    CameraNode,
+    LightNode
+}
+
+/// The parts of a node a `Rewrite` may change.
+pub struct NodeParts<'a> {
+    /// The kind of the node.
+    pub kind: NodeKind,
+    /// The transformations of the node.
+    pub transformations: &'a mut Vec<Arc<Transformation>>,
+    /// The animations of the node.
+    pub animations: &'a mut Vec<Animation>,
+    /// The morph weights of the node, for geometry nodes.
+    pub morph_weights: Option<&'a mut Vec<Arc<MorphWeight>>>,
+    /// The children of the node, which have already been rewritten.
+    pub children: &'a mut Vec<Nodes>
+}
+
+/// Rewrites a scene.
+pub fn rewrite<R: Rewrite>(scene: &mut Scene, rewrite: &mut R) {
+    let mut rebuild = Rebuild {
+        rewrite,
+        geometry_objects: HashMap::new(),
+        light_objects: HashMap::new(),
+        camera_objects: HashMap::new(),
+        materials: HashMap::new(),
+        bone_nodes: HashMap::new()
+    };
+    scene.nodes = scene.nodes.iter().map(|node| rebuild.node(node)).collect();
+    scene.geometry_objects = scene.geometry_objects.iter()
+        .map(|o| rebuild.geometry_object(o)).collect();
+    scene.light_objects = scene.light_objects.iter().map(|o| rebuild.light_object(o)).collect();
+    scene.camera_objects = scene.camera_objects.iter().map(|o| rebuild.camera_object(o)).collect();
+    scene.materials = scene.materials.iter().map(|m| rebuild.material(m)).collect();
+}
+
+/// The rewritten objects, keyed by the address of the object they replace.
+struct Rebuild<'a, R: 'a> {
+    rewrite: &'a mut R,
+    geometry_objects: HashMap<*const GeometryObject, Arc<GeometryObject>>,
+    light_objects: HashMap<*const LightObject, Arc<LightObject>>,
+    camera_objects: HashMap<*const CameraObject, Arc<CameraObject>>,
+    materials: HashMap<*const Material, Arc<Material>>,
+    bone_nodes: HashMap<*const BoneNode, Arc<BoneNode>>
+}
+
+/// Returns the rewritten version of a shared object, rewriting it the first time.
+macro_rules! shared {
+    ($rebuild:expr, $cache:ident, $object:expr, |$copy:ident| $change:expr) => ({
+        let key = &**$object as *const _;
+        match $rebuild.$cache.get(&key) {
+            Some(object) => return object.clone(),
+            None => {}
+        }
+        let mut $copy = (**$object).clone();
+        $change;
+        let object = Arc::new($copy);
+        $rebuild.$cache.insert(key, object.clone());
+        object
+    })
+}
+
+impl<'a, R: Rewrite> Rebuild<'a, R> {
+    fn node(&mut self, node: &Nodes) -> Nodes {
+        match *node {
+            Nodes::Node(ref node) => {
+                let mut node = node.clone();
+                node.children = self.children(&node.children);
+                self.rewrite.node(NodeParts {
+                    kind: NodeKind::Node,
+                    transformations: &mut node.transformations,
+                    animations: &mut node.animations,
+                    morph_weights: None,
+                    children: &mut node.children
+                });
+                Nodes::Node(node)
+            }
+            Nodes::BoneNode(ref node) => Nodes::BoneNode(self.bone_node(node)),
+            Nodes::GeometryNode(ref node) => {
+                let mut node = node.clone();
+                node.geometry = self.geometry_object(&node.geometry);
+                for (_, material) in node.materials.iter_mut() {
+                    *material = self.material(material);
+                }
+                node.children = self.children(&node.children);
+                self.rewrite.node(NodeParts {
+                    kind: NodeKind::GeometryNode,
+                    transformations: &mut node.transformations,
+                    animations: &mut node.animations,
+                    morph_weights: Some(&mut node.morph_weights),
+                    children: &mut node.children
+                });
+                Nodes::GeometryNode(node)
+            }
+            Nodes::CameraNode(ref node) => {
+                let mut node = node.clone();
+                node.camera = self.camera_object(&node.camera);
+                node.children = self.children(&node.children);
+                self.rewrite.node(NodeParts {
+                    kind: NodeKind::CameraNode,
+                    transformations: &mut node.transformations,
+                    animations: &mut node.animations,
+                    morph_weights: None,
+                    children: &mut node.children
+                });
+                Nodes::CameraNode(node)
+            }
+            Nodes::LightNode(ref node) => {
+                let mut node = node.clone();
+                node.light = self.light_object(&node.light);
+                node.children = self.children(&node.children);
+                self.rewrite.node(NodeParts {
+                    kind: NodeKind::LightNode,
+                    transformations: &mut node.transformations,
+                    animations: &mut node.animations,
+                    morph_weights: None,
+                    children: &mut node.children
+                });
+                Nodes::LightNode(node)
+            }
+        }
+    }
+
+    fn children(&mut self, children: &[Nodes]) -> Vec<Nodes> {
+        children.iter().map(|child| self.node(child)).collect()
+    }
+
+    fn bone_node(&mut self, node: &Arc<BoneNode>) -> Arc<BoneNode> {
+        shared!(self, bone_nodes, node, |node| {
+            node.children = self.children(&node.children);
+            self.rewrite.node(NodeParts {
+                kind: NodeKind::BoneNode,
+                transformations: &mut node.transformations,
+                animations: &mut node.animations,
+                morph_weights: None,
+                children: &mut node.children
+            })
+        })
+    }
+
+    fn geometry_object(&mut self, object: &Arc<GeometryObject>) -> Arc<GeometryObject> {
+        shared!(self, geometry_objects, object, |object| {
+            for (_, mesh) in object.meshes.iter_mut() {
+                if let Some(ref mut skin) = mesh.skin {
+                    for bone in &mut skin.skeleton.bone_ref_array {
+                        *bone = self.bone_node(bone);
+                    }
+                }
+            }
+            self.rewrite.geometry_object(&mut object)
+        })
+    }
+
+    fn light_object(&mut self, object: &Arc<LightObject>) -> Arc<LightObject> {
+        shared!(self, light_objects, object, |object| self.rewrite.light_object(&mut object))
+    }
+
+    fn camera_object(&mut self, object: &Arc<CameraObject>) -> Arc<CameraObject> {
+        shared!(self, camera_objects, object, |object| self.rewrite.camera_object(&mut object))
+    }
+
+    fn material(&mut self, material: &Arc<Material>) -> Arc<Material> {
+        shared!(self, materials, material, |material| self.rewrite.material(&mut material))
+    }
+}
+
+/// Replaces the transformations and morph weights of a node or texture, updating the animation
+/// tracks targeting them. `transformation` and `morph_weight` return the replacement of each.
+pub fn replace_targets<T, M>(transformations: &mut [Arc<Transformation>],
+    morph_weights: &mut [Arc<MorphWeight>], animations: &mut [Animation],
+    mut transformation: T, mut morph_weight: M)
+    where T: FnMut(&Transformation) -> Transformation, M: FnMut(&MorphWeight) -> MorphWeight
+{
+    let mut targets: HashMap<*const (), TrackTarget> = HashMap::new();
+    for t in transformations.iter_mut() {
+        let new = Arc::new(transformation(t));
+        targets.insert(&**t as *const _ as *const (), TrackTarget::Transformation(new.clone()));
+        *t = new;
+    }
+    for w in morph_weights.iter_mut() {
+        let new = Arc::new(morph_weight(w));
+        targets.insert(&**w as *const _ as *const (), TrackTarget::MorphWeight(new.clone()));
+        *w = new;
+    }
+    for track in animations.iter_mut().flat_map(|a| a.tracks.iter_mut()) {
+        let key = match track.target {
+            TrackTarget::Transformation(ref t) => &**t as *const _ as *const (),
+            TrackTarget::MorphWeight(ref w) => &**w as *const _ as *const ()
+        };
+        if let Some(target) = targets.get(&key) {
+            track.target = target.clone();
+        }
+    }
+}
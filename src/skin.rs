@@ -0,0 +1,222 @@
+//! Skinning of meshes on the CPU.
+//!
+//! The vertices of a skinned mesh are first transformed by the bind-pose transform of the skin,
+//! into the space of the skeleton. Each bone then moves them from its own bind pose, given by the
+//! transforms of the skeleton, to its current pose. The skinning matrix of a bone is thus the
+//! current transform of the bone, times the inverse of its bind-pose transform, times the transform
+//! of the skin. A vertex is moved by the sum of the skinning matrices of its bones, weighted by
+//! the bone weights of the vertex.
+//!
+//! The transforms of the skeleton are in the object space of the skinned geometry node, so the
+//! current transforms of the bones are their world matrices relative to the world matrix of the
+//! node, and the skinned vertices stay in the object space of the node.
+//!
+//! OpenGEX places no limit on the number of bones influencing a vertex, while skinning on the GPU
+//! usually takes a fixed number of them. `Skin::limit_influences` keeps the strongest influences
+//! of every vertex, and tells how much weight was dropped to do so.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use animation::Pose;
+use structure::*;
+use transform::{ invert, multiply, transform_point, transform_vector, transpose, Matrix,
+    IDENTITY };
+
+impl Skin {
+    /// Returns the skinning matrix of every bone of the skeleton, given the current transform of
+    /// every bone in the object space of the skinned node.
+    ///
+    /// Bones whose bind-pose transform cannot be inverted, or which have no bind-pose transform,
+    /// use the identity in its place.
+    pub fn bone_matrices<F>(&self, mut current: F) -> Vec<Matrix>
+        where F: FnMut(&Arc<BoneNode>) -> Matrix
+    {
+        let skin = self.transform.as_ref().map_or(IDENTITY, |t| t.0);
+        self.skeleton.bone_ref_array.iter().enumerate().map(|(i, bone)| {
+            let bind = self.skeleton.transforms.get(i).and_then(|t| invert(&t.0))
+                .unwrap_or(IDENTITY);
+            multiply(&current(bone), &multiply(&bind, &skin))
+        }).collect()
+    }
+
+    /// Returns the skinned positions and normals of a mesh, given the skinning matrices of the
+    /// bones. Normals are transformed by the inverse transpose of the blended matrix of each
+    /// vertex, and normalized.
+    ///
+    /// Vertices beyond the bone count array, and bone indices out of the skeleton, are left as
+    /// they are.
+    pub fn deform(&self, mesh: &Mesh, matrices: &[Matrix]) -> Mesh {
+        // The blended matrix of every vertex.
+        let mut blended = vec![];
+        let mut k = 0;
+        for v in 0..self.bone_count_array.len() {
+            let mut m = [0.0; 16];
+            let mut total = 0.0;
+            for _ in 0..self.bone_count_array.get(v) {
+                if k >= self.bone_index_array.len() || k >= self.bone_weight_array.len() {
+                    break;
+                }
+                let weight = self.bone_weight_array[k];
+                if let Some(bone) = matrices.get(self.bone_index_array.get(k) as usize) {
+                    for (x, b) in m.iter_mut().zip(bone) {
+                        *x += weight * b;
+                    }
+                    total += weight;
+                }
+                k += 1;
+            }
+            blended.push(if total == 0.0 { IDENTITY } else { m });
+        }
+
+        let mut mesh = mesh.clone();
+        for array in &mut mesh.vertex_arrays {
+            let normal = match array.attrib.as_str() {
+                "position" => false,
+                "normal" => true,
+                _ => continue
+            };
+            if array.components != 3 {
+                continue;
+            }
+            let mut values = array.data.to_f64();
+            for (v, m) in values.chunks_mut(3).zip(&blended) {
+                let p = [v[0] as f32, v[1] as f32, v[2] as f32];
+                let p = if normal {
+                    let n = match invert(m) {
+                        Some(inverse) => transform_vector(&transpose(&inverse), p),
+                        None => transform_vector(m, p)
+                    };
+                    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+                    if len > 0.0 { [n[0] / len, n[1] / len, n[2] / len] } else { n }
+                } else {
+                    transform_point(m, p)
+                };
+                for i in 0..3 {
+                    v[i] = p[i] as f64;
+                }
+            }
+            array.data = array.data.with_values(values);
+        }
+        mesh
+    }
+
+    /// Limits every vertex to the `N` bones with the largest weights, and normalizes the weights
+    /// kept so that they sum to 1. Weights of the same bone are added together first.
+    ///
+    /// Vertices with fewer than `N` influences are padded with bone 0 and a weight of 0. Bone
+    /// indices that do not fit in 16 bits count as dropped.
+    pub fn limit_influences<const N: usize>(&self) -> BoneInfluences<N> {
+        let mut influences = BoneInfluences {
+            indices: Vec::with_capacity(self.bone_count_array.len()),
+            weights: Vec::with_capacity(self.bone_count_array.len()),
+            dropped: Vec::with_capacity(self.bone_count_array.len())
+        };
+        let mut k = 0;
+        for v in 0..self.bone_count_array.len() {
+            let count = self.bone_count_array.get(v) as usize;
+            let end = (k + count).min(self.bone_index_array.len())
+                .min(self.bone_weight_array.len()).max(k);
+            let mut bones: Vec<(u64, f32)> = vec![];
+            for i in k..end {
+                let bone = self.bone_index_array.get(i);
+                match bones.iter_mut().find(|b| b.0 == bone) {
+                    Some(b) => b.1 += self.bone_weight_array[i],
+                    None => bones.push((bone, self.bone_weight_array[i]))
+                }
+            }
+            k += count;
+
+            let total: f32 = bones.iter().map(|b| b.1).sum();
+            bones.retain(|b| b.0 <= u16::MAX as u64);
+            bones.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+            bones.truncate(N);
+            let kept: f32 = bones.iter().map(|b| b.1).sum();
+
+            let mut indices = [0; N];
+            let mut weights = [0.0; N];
+            for (i, &(bone, weight)) in bones.iter().enumerate() {
+                indices[i] = bone as u16;
+                weights[i] = if kept != 0.0 { weight / kept } else { 0.0 };
+            }
+            influences.indices.push(indices);
+            influences.weights.push(weights);
+            influences.dropped.push(if total != 0.0 { 1.0 - kept / total } else { 0.0 });
+        }
+        influences
+    }
+}
+
+/// The bone influences of the vertices of a skin, limited to `N` bones per vertex, as returned by
+/// `Skin::limit_influences`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoneInfluences<const N: usize> {
+    /// The indices of the bones influencing every vertex, in the skeleton of the skin.
+    pub indices: Vec<[u16; N]>,
+    /// The normalized weights of the bones influencing every vertex, from largest to smallest.
+    pub weights: Vec<[f32; N]>,
+    /// The fraction of the total weight of every vertex that was dropped, between 0 and 1.
+    pub dropped: Vec<f32>
+}
+
+impl<const N: usize> BoneInfluences<N> {
+    /// Returns the largest fraction of weight dropped from a vertex, or 0 without vertices.
+    pub fn max_dropped(&self) -> f32 {
+        self.dropped.iter().cloned().fold(0.0, f32::max)
+    }
+
+    /// Returns the number of vertices that lost some of their weight.
+    pub fn pruned_vertices(&self) -> usize {
+        self.dropped.iter().filter(|&&d| d > 0.0).count()
+    }
+}
+
+impl GeometryNode {
+    /// Returns the skinning matrices of the mesh with the given level of detail, with the node
+    /// hierarchy of a scene in a pose. Returns `None` if the geometry has no such mesh, or if it
+    /// is not skinned.
+    ///
+    /// Bones missing from the node hierarchy of the scene stay in their bind pose. If the node
+    /// itself is missing from the scene, its world matrix is taken to be the identity.
+    pub fn skinning_matrices(&self, scene: &Scene, pose: &Pose, lod: usize)
+        -> Option<Vec<Matrix>>
+    {
+        let skin = self.geometry.meshes.get(lod)?.skin.as_ref()?;
+        let mut node = IDENTITY;
+        let mut bones = HashMap::new();
+        for (n, world) in scene.posed_world_matrices(pose) {
+            match *n {
+                Nodes::BoneNode(ref bone) => { bones.insert(&**bone as *const BoneNode, world); }
+                Nodes::GeometryNode(ref n) if ::std::ptr::eq(n, self) => node = world,
+                _ => {}
+            }
+        }
+        let inverse = invert(&node).unwrap_or(IDENTITY);
+        let bind: HashMap<_, _> = skin.skeleton.bone_ref_array.iter()
+            .zip(&skin.skeleton.transforms)
+            .map(|(bone, t)| (&**bone as *const BoneNode, t.0))
+            .collect();
+        Some(skin.bone_matrices(|bone| {
+            let key = &**bone as *const BoneNode;
+            match bones.get(&key) {
+                Some(world) => multiply(&inverse, world),
+                None => bind.get(&key).cloned().unwrap_or(IDENTITY)
+            }
+        }))
+    }
+
+    /// Returns the mesh with the given level of detail, with its morph targets blended and its
+    /// skin applied in a pose of the node hierarchy of a scene. The positions and normals of the
+    /// returned mesh are in the object space of the node. Returns `None` if the geometry has no
+    /// such mesh.
+    ///
+    /// Unskinned meshes only have their morph targets blended.
+    pub fn skin(&self, scene: &Scene, pose: &Pose, lod: usize) -> Option<Mesh> {
+        let mesh = self.blend_morphs_with(lod, pose)?;
+        match (mesh.skin.as_ref(), self.skinning_matrices(scene, pose, lod)) {
+            (Some(skin), Some(matrices)) => Some(skin.deform(&mesh, &matrices)),
+            _ => Some(mesh)
+        }
+    }
+}
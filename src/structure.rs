@@ -9,9 +9,106 @@ use std::default::Default;
 use std::sync::Arc;
 use vec_map::VecMap;
 
+/// The `Scene` structure is the root of an OpenGEX file. It holds the top-level nodes, the
+/// metrics of the file and all objects and materials that can be shared between nodes.
+///
+/// A `Scene` can be loaded from OpenGEX text with `Scene::from_str` or `Scene::from_reader`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    /// The units and axis conventions of the scene, from the Metric structures.
+    pub metric: Metric,
+    /// All top-level nodes of the scene.
+    pub nodes: Vec<Nodes>,
+    /// All geometry objects in the scene, in the order they appear in the file.
+    pub geometry_objects: Vec<Arc<GeometryObject>>,
+    /// All light objects in the scene, in the order they appear in the file.
+    pub light_objects: Vec<Arc<LightObject>>,
+    /// All camera objects in the scene, in the order they appear in the file.
+    pub camera_objects: Vec<Arc<CameraObject>>,
+    /// All materials in the scene, in the order they appear in the file.
+    pub materials: Vec<Arc<Material>>,
+    /// The Clip structures of the scene, keyed by their index. `Scene::clips` also includes the
+    /// clips that animations refer to without a Clip structure.
+    pub clip_structures: VecMap<Clip>
+}
+
+/// The units and axis conventions of a scene, from the Metric structures of the file.
+///
+/// Every value in the file is expressed in these units. `Scene::normalize` converts a scene into
+/// other units and conventions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    /// The number of meters in one distance unit, from the "distance" metric. Defaults to 1.
+    pub distance: f32,
+    /// The number of radians in one angle unit, from the "angle" metric. Defaults to 1.
+    pub angle: f32,
+    /// The number of seconds in one time unit, from the "time" metric. Defaults to 1.
+    pub time: f32,
+    /// The up direction, from the "up" metric. Defaults to the positive z axis.
+    pub up: Axis,
+    /// The forward direction, from the "forward" metric, if the file has one.
+    pub forward: Option<Axis>,
+    /// The metrics with other keys, which are kept as they are.
+    pub others: MetricMap
+}
+
+impl Default for Metric {
+    fn default() -> Metric {
+        Metric {
+            distance: 1.0,
+            angle: 1.0,
+            time: 1.0,
+            up: Axis::Z,
+            forward: None,
+            others: MetricMap::new()
+        }
+    }
+}
+
+/// A coordinate axis direction, as used by the "up" and "forward" metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// The positive x axis, "x".
+    X,
+    /// The positive y axis, "y".
+    Y,
+    /// The positive z axis, "z".
+    Z,
+    /// The negative x axis, "-x".
+    NegX,
+    /// The negative y axis, "-y".
+    NegY,
+    /// The negative z axis, "-z".
+    NegZ
+}
+
+/// The Metric structure holds a single value describing a global property of the file, like the
+/// distance scale or the up direction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricValue {
+    /// A floating-point metric, like the "distance", "angle" and "time" keys.
+    Float(f32),
+    /// A string metric, like the "up" and "forward" keys.
+    String(String)
+}
+
+/// This is a map of different Metric structures, keyed by their `key` property.
+pub type MetricMap = HashMap<String, MetricValue>;
+
+/// The Clip structure contains information about a single animation clip. Animation structures
+/// belong to a clip through their `clip` property, which matches the index of the clip.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Clip {
+    /// An optional name, like "walk" or "run".
+    pub name: Option<Name>,
+    /// Any number of parameters, like a frame rate exported by a modeling tool.
+    pub param: ParamMap
+}
+
 /// The Material structure contains information about a material. Material structures are
 /// referenced by geometry nodes through `Arc<Material>` structures belonging to `GeometryNode`
 /// structures.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Material {
     /// Whether the material is two-sided.
     pub two_sided: bool,
@@ -26,6 +123,7 @@ pub struct Material {
 }
 
 /// A Color structure must contain an RGB or RGBA color value.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Color {
     /// An RGB color value.
     Rgb(f32, f32, f32),
@@ -44,19 +142,22 @@ pub type Name = String;
 
 /// The Texture structure holds information about a single texture map, and how it is accessed with
 /// texture coordinates.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Texture {
     /// The index of the texture coordinate set associated with the texture.
     pub texcoord: u32,
     /// A substructure holding the file name of the texture.
     pub file_name: String,
     /// Any number of transformations that are applied to the texture coordinates of a mesh when
-    /// they are used to fetch from the texture map.
-    pub transformations: Vec<Transformation>,
+    /// they are used to fetch from the texture map. The transformations are shared with the
+    /// animation tracks targeting them.
+    pub transformations: Vec<Arc<Transformation>>,
     /// Any number of animation tracks that are applied to the texture coordinate transformations.
     pub animation: Vec<Animation>
 }
 
 /// Helper enum to contain all different kinds of Transformations.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Transformation {
     /// A Transform structure.
     Transform(Transform),
@@ -76,7 +177,8 @@ pub enum Transformation {
 ///
 /// When contained inside a node structure, a Transform structure can be the target of a track
 /// stored inside an Animation structure.
-pub struct Transform([f32; 16]);
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform(pub [f32; 16]);
 
 /// The Translation structure holds a translation transformation in one of several possible
 /// variants.
@@ -85,6 +187,7 @@ pub struct Transform([f32; 16]);
 ///
 /// When contained inside a node structure, a Translation structure can be the target of a track
 /// stored inside an Animation structure.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Translation {
     /// The translation occurs along only the X axis.
     X(f32),
@@ -102,6 +205,7 @@ pub enum Translation {
 ///
 /// When contained inside a node structure, a Rotation structure can be the target of a track
 /// stored in an Animation structure.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Rotation {
     /// The rotation occurs about the X axis.
     X(f32),
@@ -124,6 +228,7 @@ pub enum Rotation {
 ///
 /// When contained inside a node structure, a Scale structure can be the target of a strack stored
 /// inside an Animation structure.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Scale {
     /// The scaling occurs along only the X axis.
     X(f32),
@@ -141,6 +246,7 @@ pub enum Scale {
 /// MorphWeight structures over time.
 ///
 /// More detailed information can be found in the official OpenGEX specification.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Animation {
     /// Specifies the animation clip index.
     pub clip: u32,
@@ -158,6 +264,7 @@ pub struct Animation {
 
 /// The Track structure contains animation key data for a single Transformation or MorphWeight
 /// structure.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Track {
     /// The target transformation or MorphWeight this track applies to.
     pub target: TrackTarget,
@@ -168,6 +275,7 @@ pub struct Track {
 }
 
 /// Enum wrapping over all possible animation track targets.
+#[derive(Clone, Debug, PartialEq)]
 pub enum TrackTarget {
     /// A Transformation structure. See enum to see possibilities.
     Transformation(Arc<Transformation>),
@@ -180,6 +288,7 @@ pub enum TrackTarget {
 /// There are two different kinds of this structure; one for every curve kind.
 ///
 /// The variants in this enum contain vectors. One vector item represents on key time.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Time {
     /// The times are interpolated linearly.
     Linear(Vec<f32>),
@@ -190,11 +299,30 @@ pub enum Time {
     Bezier(Vec<(f32, f32, f32)>)
 }
 
+impl Time {
+    /// Returns the number of keys.
+    pub fn len(&self) -> usize {
+        match *self {
+            Time::Linear(ref v) => v.len(),
+            Time::Bezier(ref v) => v.len()
+        }
+    }
+
+    /// Whether the time curve contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 /// The Value structure contains key value data in an animation track.
 ///
 /// There are two different kinds of this structure; one for every curve kind.
 ///
-/// The variants in this enum contain vectors. One vector item represents on key value.
+/// The variants in this enum contain vectors. One vector item represents on key value. Values with
+/// multiple components, like the ones targeting a `Translation::Xyz` structure, are stored
+/// flattened: the components of each key follow each other, so the number of components equals
+/// the length of the vector divided by the number of keys in the `Time` structure.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     /// The values are not interpolated, but remain constant until the next key time.
     Constant(Vec<f32>),
@@ -217,6 +345,7 @@ pub enum Value {
 /// references a GeometryObject strucure containing vertex data for multiple morph targets.
 ///
 /// A MorphWeight structure can be the target of a track stored inside an Animation structure.
+#[derive(Clone, Debug, PartialEq)]
 pub struct MorphWeight {
     /// Specifies the morph target index to which this morph weight applies. If the GeometryObject
     /// structure contains no vertex data corresponding to this target index, then this structure
@@ -229,6 +358,7 @@ pub struct MorphWeight {
 }
 
 /// The Atten structure specifies an attenuation function for a light object.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Atten {
     /// The kind of attenuation.
     pub kind: AttenuationKind,
@@ -242,6 +372,7 @@ pub struct Atten {
 }
 
 /// A helper enum representing different kinds of attenuation functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AttenuationKind {
     /// The input to the attenuation function is the radial distance from the LightObject the
     /// parent Atten structure is associated with.
@@ -263,6 +394,7 @@ pub enum AttenuationKind {
 /// A helper enum representing different kinds of curves for an attenuation function.
 ///
 /// For exact formulas, please refer to the offical OpenGEX documentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AttenuationCurve {
     /// The attenuation is a linear function.
     Linear,
@@ -275,11 +407,12 @@ pub enum AttenuationCurve {
 }
 
 /// Helper enum to represent all different types of Nodes.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Nodes {
     /// A `Node`.
     Node(Node),
-    /// A `BoneNode`.
-    BoneNode(BoneNode),
+    /// A `BoneNode`. Bone nodes are shared with the `Skeleton` structures referencing them.
+    BoneNode(Arc<BoneNode>),
     /// A `GeometryNode`
     GeometryNode(GeometryNode),
     /// A `CameraNode`
@@ -288,6 +421,39 @@ pub enum Nodes {
     LightNode(LightNode)
 }
 
+/// Evaluates an expression for the node inside a `Nodes` value, whatever its kind.
+macro_rules! with_node {
+    ($nodes:expr, |$node:ident| $e:expr) => (match *$nodes {
+        Nodes::Node(ref $node) => $e,
+        Nodes::BoneNode(ref $node) => $e,
+        Nodes::GeometryNode(ref $node) => $e,
+        Nodes::CameraNode(ref $node) => $e,
+        Nodes::LightNode(ref $node) => $e
+    })
+}
+
+impl Nodes {
+    /// Returns the name of the node.
+    pub fn name(&self) -> Option<&str> {
+        with_node!(self, |node| node.name.as_deref())
+    }
+
+    /// Returns the local transformations of the node.
+    pub fn transformations(&self) -> &[Arc<Transformation>] {
+        with_node!(self, |node| &node.transformations)
+    }
+
+    /// Returns the animations of the node.
+    pub fn animations(&self) -> &[Animation] {
+        with_node!(self, |node| &node.animations)
+    }
+
+    /// Returns the child nodes of the node.
+    pub fn children(&self) -> &[Nodes] {
+        with_node!(self, |node| &node.children)
+    }
+}
+
 /// Macro to do away with the redundancy of the different kinds of Node structures. The common Node
 /// properties are placed at the start of the generated structure.
 ///
@@ -304,12 +470,14 @@ macro_rules! node {
         }
     ) => (
         $(#[$attr])*
+        #[derive(Clone, Debug, PartialEq)]
         pub struct $name {
             /// The optional name for this node. This property is generic to all types of nodes.
             pub name: Option<Name>,
-            /// Any local transformations to be applied to this node. This property is generic to
-            /// all types of nodes.
-            pub transformations: Vec<Transformation>,
+            /// Any local transformations to be applied to this node. The transformations are
+            /// shared with the animation tracks targeting them. This property is generic to all
+            /// types of nodes.
+            pub transformations: Vec<Arc<Transformation>>,
             /// Any animations for this node. This property is generic to all types of nodes.
             pub animations: Vec<Animation>,
             /// Any sub-nodes of this node. This property is generic to all types of nodes.
@@ -354,7 +522,7 @@ node! {
         /// targets, then the node may contain one or more `MorphWeight` structures that specify
         /// the blending weight for each target. Each `MorphWeight` structure may be the target of
         /// a Track structure in the animation belonging to the node.
-        pub morph_weights: Vec<MorphWeight>
+        pub morph_weights: Vec<Arc<MorphWeight>>
     }
 }
 
@@ -373,6 +541,9 @@ node! {
         /// Whether this light is visible. Overrides the visibility of the referenced `LightObject`
         /// structure.
         pub visibile: Option<bool>,
+        /// Whether this light casts shadows. If unset, this `LightNode` inherits the shadow
+        /// casting of the referenced `LightObject` structure.
+        pub casts_shadows: Option<bool>,
         /// A reference to a `LightObject` that contains the information neccesary to construct the
         /// proper type of light.
         pub light: Arc<LightObject>
@@ -384,6 +555,7 @@ node! {
 /// instances of the same geometry with different transforms and materials.
 ///
 /// The `colors` and `textures` properties are for application-specfic use.
+#[derive(Clone, Debug, PartialEq)]
 pub struct GeometryObject {
     /// Whether this geometry is visible. Can be overriden by any `GeometryNode` structure
     /// referencing this geometry.
@@ -402,6 +574,7 @@ pub struct GeometryObject {
 }
 
 /// A `CameraObject` structure contains data for a camera object.
+#[derive(Clone, Debug, PartialEq)]
 pub struct CameraObject {
     /// A map of parameters associated with this camera.
     ///
@@ -419,6 +592,7 @@ pub struct CameraObject {
 /// The LightObject struture contains data for a light object. Multiple LightNode structures may
 /// reference a single LightObject. This allows a scene to contain multiple instances of the same
 /// light, with different transformations.
+#[derive(Clone, Debug, PartialEq)]
 pub struct LightObject {
     /// The type of light emitted by this LightObject.
     pub light_type: LightType,
@@ -453,10 +627,11 @@ pub struct LightObject {
     /// Any number of attenuation functions to be applied to the LightObject. The values produced
     /// by all of them are multiplied together to determine the intensity of the light reaching
     /// any particular point in space.
-    pub attenuations: Atten
+    pub attenuations: Vec<Atten>
 }
 
 /// This is an helper-enum representing all different types of lights that a LightObject can emit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LightType {
     /// The light souArce is to be treated as if it were infinitely far away so its rays are
     /// parallel. In object space, the rays point in the direction of the negative z-axis.
@@ -470,6 +645,7 @@ pub enum LightType {
 
 /// The `Morph` structure holds information about a morph target belonging to a `GeometryObject`
 /// structure.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Morph {
     /// The base morph target index for a relative morph target.
     pub base_target_index: Option<u32>,
@@ -482,12 +658,231 @@ pub struct Morph {
 ///
 /// A mesh may contain vertex data for multiple morph targets. The morph target to which the vertex
 /// array belongs is determined by the value op its `morph` property.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Mesh {
     /// Specifies the type of geometric primitive used by the mesh. This must be the same for each
     /// level of detail. See the helper-enum `GeometricPrimitive` for more details about the
     /// different kinds of primitives.
-    pub primitive: GeometricPrimitive
-    // TODO: Finish this
+    pub primitive: GeometricPrimitive,
+    /// The arrays of per-vertex data, for every attribute and morph target.
+    pub vertex_arrays: Vec<VertexArray>,
+    /// The index arrays of this mesh. Each index array applies a different material to a part of
+    /// the mesh. If there are no index arrays, the vertices are used in order.
+    pub index_arrays: Vec<IndexArray>,
+    /// The skinning data of this mesh, if it is a skinned mesh.
+    pub skin: Option<Skin>
+}
+
+/// The `VertexArray` structure contains an array of per-vertex data for a single attribute of a
+/// `Mesh` structure.
+///
+/// The OpenGEX specification defines the attributes "position", "normal", "tangent",
+/// "bitangent", "color" and "texcoord". Any other attributes are application-specific.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexArray {
+    /// The name of the attribute, without an array index.
+    pub attrib: String,
+    /// The array index of the attribute. This is 1 for an attribute written as "texcoord[1]", and
+    /// 0 if no array index is given.
+    pub index: u32,
+    /// The morph target index this vertex data belongs to.
+    pub morph: u32,
+    /// The number of components per vertex.
+    pub components: usize,
+    /// The vertex data, with the components of each vertex following each other.
+    pub data: VertexData
+}
+
+impl VertexArray {
+    /// Returns the number of vertices in this array.
+    pub fn len(&self) -> usize {
+        self.data.len() / self.components
+    }
+
+    /// Whether this array contains no vertices.
+    pub fn is_empty(&self) -> bool {
+        self.data.len() == 0
+    }
+}
+
+/// Helper enum containing the per-vertex data of a `VertexArray` structure in one of the
+/// floating-point types OpenGEX allows.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VertexData {
+    /// Half-precision data, widened to `f32`.
+    Half(Vec<f32>),
+    /// Single-precision data.
+    Float(Vec<f32>),
+    /// Double-precision data.
+    Double(Vec<f64>)
+}
+
+impl VertexData {
+    /// Returns the number of values, not vertices, in this data.
+    pub fn len(&self) -> usize {
+        match *self {
+            VertexData::Half(ref v) | VertexData::Float(ref v) => v.len(),
+            VertexData::Double(ref v) => v.len()
+        }
+    }
+
+    /// Whether this data contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value at the given position as an `f64`.
+    pub fn get(&self, i: usize) -> f64 {
+        match *self {
+            VertexData::Half(ref v) | VertexData::Float(ref v) => v[i] as f64,
+            VertexData::Double(ref v) => v[i]
+        }
+    }
+
+    /// Returns a copy of the data converted to `f32` values.
+    pub fn to_f32(&self) -> Vec<f32> {
+        match *self {
+            VertexData::Half(ref v) | VertexData::Float(ref v) => v.clone(),
+            VertexData::Double(ref v) => v.iter().map(|&x| x as f32).collect()
+        }
+    }
+
+    /// Returns a copy of the data converted to `f64` values.
+    pub fn to_f64(&self) -> Vec<f64> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+
+    /// Returns data of the same floating-point type holding the given values.
+    pub fn with_values(&self, values: Vec<f64>) -> VertexData {
+        match *self {
+            VertexData::Half(_) => VertexData::Half(values.into_iter().map(|x| x as f32).collect()),
+            VertexData::Float(_) => {
+                VertexData::Float(values.into_iter().map(|x| x as f32).collect())
+            }
+            VertexData::Double(_) => VertexData::Double(values)
+        }
+    }
+}
+
+/// The `IndexArray` structure contains an array of vertex indices for a part of a `Mesh`
+/// structure. How the indices form primitives is determined by the `primitive` of the mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexArray {
+    /// The index of the material applied to the primitives of this array. This matches the keys
+    /// in the `materials` map of the `GeometryNode` structures referencing the mesh.
+    pub material: u32,
+    /// An optional primitive restart index. When this index is encountered in a strip, a new
+    /// strip is started.
+    pub restart: Option<u64>,
+    /// The winding of the front face of the primitives of this array.
+    pub front_face: FrontFace,
+    /// The indices.
+    pub data: IndexData
+}
+
+/// Helper enum representing the winding of the front face of a primitive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontFace {
+    /// Primitives whose vertices are ordered counterclockwise face the viewer.
+    CounterClockwise,
+    /// Primitives whose vertices are ordered clockwise face the viewer.
+    Clockwise
+}
+
+impl Default for FrontFace {
+    fn default() -> FrontFace {
+        FrontFace::CounterClockwise
+    }
+}
+
+/// Helper enum containing unsigned integer data in one of the widths OpenGEX allows. It is used
+/// for the data of `IndexArray` structures, and for the bone count and bone index arrays of a
+/// `Skin` structure.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IndexData {
+    /// 8-bit unsigned integers.
+    U8(Vec<u8>),
+    /// 16-bit unsigned integers.
+    U16(Vec<u16>),
+    /// 32-bit unsigned integers.
+    U32(Vec<u32>),
+    /// 64-bit unsigned integers.
+    U64(Vec<u64>)
+}
+
+impl IndexData {
+    /// Returns the number of values in this data.
+    pub fn len(&self) -> usize {
+        match *self {
+            IndexData::U8(ref v) => v.len(),
+            IndexData::U16(ref v) => v.len(),
+            IndexData::U32(ref v) => v.len(),
+            IndexData::U64(ref v) => v.len()
+        }
+    }
+
+    /// Whether this data contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value at the given position.
+    pub fn get(&self, i: usize) -> u64 {
+        match *self {
+            IndexData::U8(ref v) => v[i] as u64,
+            IndexData::U16(ref v) => v[i] as u64,
+            IndexData::U32(ref v) => v[i] as u64,
+            IndexData::U64(ref v) => v[i]
+        }
+    }
+
+    /// Returns a copy of the data converted to `u64` values.
+    pub fn to_u64(&self) -> Vec<u64> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+
+    /// Returns data of the same integer type holding the given values, which must fit in it.
+    pub fn with_values(&self, values: Vec<u64>) -> IndexData {
+        match *self {
+            IndexData::U8(_) => IndexData::U8(values.into_iter().map(|x| x as u8).collect()),
+            IndexData::U16(_) => IndexData::U16(values.into_iter().map(|x| x as u16).collect()),
+            IndexData::U32(_) => IndexData::U32(values.into_iter().map(|x| x as u32).collect()),
+            IndexData::U64(_) => IndexData::U64(values)
+        }
+    }
+}
+
+/// The `Skin` structure contains information about a skeleton and the per-vertex bone influence
+/// data of a skinned `Mesh` structure.
+///
+/// Every vertex is influenced by the number of bones given in `bone_count_array`. The bone
+/// indices and weights of all vertices are stored one after another in `bone_index_array` and
+/// `bone_weight_array`, so both arrays hold as many values as the sum of all bone counts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skin {
+    /// The optional bind-pose transform of the mesh. If absent, the identity is used.
+    pub transform: Option<Transform>,
+    /// The skeleton used by this skin.
+    pub skeleton: Skeleton,
+    /// The number of bones influencing each vertex, from the BoneCountArray structure.
+    pub bone_count_array: IndexData,
+    /// The indices of the bones influencing the vertices, from the BoneIndexArray structure. The
+    /// indices refer to the bones of the skeleton.
+    pub bone_index_array: IndexData,
+    /// The weights of the bones influencing the vertices, from the BoneWeightArray structure.
+    pub bone_weight_array: Vec<f32>
+}
+
+/// The `Skeleton` structure contains the bones of a skinned mesh, and their transforms in the bind
+/// pose of the mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skeleton {
+    /// The bone nodes of the skeleton, from the BoneRefArray structure. The bones are shared with
+    /// the node hierarchy of the scene. The bone indices of a `Skin` structure index this array.
+    pub bone_ref_array: Vec<Arc<BoneNode>>,
+    /// The object-space transform of every bone in the bind pose, from the Transform structure of
+    /// the skeleton. There is one matrix for each bone in `bone_ref_array`.
+    pub transforms: Vec<Transform>
 }
 
 /// Helper enum for the `Mesh` structure, representing different geometric primitives supported by
@@ -496,6 +891,7 @@ pub struct Mesh {
 /// In the documentation, `n` refers to the number of indices if an `IndexArray` structure is
 /// present, and otherwise, the number of vertices in every `VertexArray` structure. Primitives are
 /// indexed by the letter `i`, starting at zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GeometricPrimitive {
     /// The mesh is composed of a set of independent points. The number of points is `n`, and point
     ///  `i` is given by vertex `i`.
@@ -0,0 +1,215 @@
+//! Evaluation of node transformations as matrices.
+//!
+//! Matrices are 4 x 4 and stored in column-major order, like the Transform structure of OpenGEX,
+//! and they transform column vectors. The transformations of a node are multiplied in the order
+//! they appear, so the last one is the first applied to a vector. The world matrix of a node is the
+//! world matrix of its parent multiplied by its local matrix.
+
+use animation::Pose;
+use structure::*;
+
+/// A 4 x 4 matrix in column-major order.
+pub type Matrix = [f32; 16];
+
+/// The identity matrix.
+pub const IDENTITY: Matrix = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0
+];
+
+/// Multiplies two matrices. The result applies `b` first, then `a`.
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [0.0; 16];
+    for c in 0..4 {
+        for r in 0..4 {
+            m[c * 4 + r] = (0..4).map(|i| a[i * 4 + r] * b[c * 4 + i]).sum();
+        }
+    }
+    m
+}
+
+/// Returns the inverse of a matrix, or `None` if it is singular.
+pub fn invert(m: &Matrix) -> Option<Matrix> {
+    // The inverse is the adjugate divided by the determinant, with the cofactors computed from
+    // the 2 x 2 minors of the upper and lower halves.
+    let s0 = m[0] * m[5] - m[4] * m[1];
+    let s1 = m[0] * m[9] - m[8] * m[1];
+    let s2 = m[0] * m[13] - m[12] * m[1];
+    let s3 = m[4] * m[9] - m[8] * m[5];
+    let s4 = m[4] * m[13] - m[12] * m[5];
+    let s5 = m[8] * m[13] - m[12] * m[9];
+    let c5 = m[10] * m[15] - m[14] * m[11];
+    let c4 = m[6] * m[15] - m[14] * m[7];
+    let c3 = m[6] * m[11] - m[10] * m[7];
+    let c2 = m[2] * m[15] - m[14] * m[3];
+    let c1 = m[2] * m[11] - m[10] * m[3];
+    let c0 = m[2] * m[7] - m[6] * m[3];
+    let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+    if det == 0.0 {
+        return None;
+    }
+    let d = 1.0 / det;
+    Some([
+        (m[5] * c5 - m[9] * c4 + m[13] * c3) * d,
+        (-m[1] * c5 + m[9] * c2 - m[13] * c1) * d,
+        (m[1] * c4 - m[5] * c2 + m[13] * c0) * d,
+        (-m[1] * c3 + m[5] * c1 - m[9] * c0) * d,
+        (-m[4] * c5 + m[8] * c4 - m[12] * c3) * d,
+        (m[0] * c5 - m[8] * c2 + m[12] * c1) * d,
+        (-m[0] * c4 + m[4] * c2 - m[12] * c0) * d,
+        (m[0] * c3 - m[4] * c1 + m[8] * c0) * d,
+        (m[7] * s5 - m[11] * s4 + m[15] * s3) * d,
+        (-m[3] * s5 + m[11] * s2 - m[15] * s1) * d,
+        (m[3] * s4 - m[7] * s2 + m[15] * s0) * d,
+        (-m[3] * s3 + m[7] * s1 - m[11] * s0) * d,
+        (-m[6] * s5 + m[10] * s4 - m[14] * s3) * d,
+        (m[2] * s5 - m[10] * s2 + m[14] * s1) * d,
+        (-m[2] * s4 + m[6] * s2 - m[14] * s0) * d,
+        (m[2] * s3 - m[6] * s1 + m[10] * s0) * d
+    ])
+}
+
+/// Returns the transpose of a matrix.
+pub fn transpose(m: &Matrix) -> Matrix {
+    let mut t = [0.0; 16];
+    for c in 0..4 {
+        for r in 0..4 {
+            t[c * 4 + r] = m[r * 4 + c];
+        }
+    }
+    t
+}
+
+/// Transforms a point by a matrix, including its translation.
+pub fn transform_point(m: &Matrix, p: [f32; 3]) -> [f32; 3] {
+    let v = transform_vector(m, p);
+    [v[0] + m[12], v[1] + m[13], v[2] + m[14]]
+}
+
+/// Transforms a direction by the upper 3 x 3 part of a matrix.
+pub fn transform_vector(m: &Matrix, v: [f32; 3]) -> [f32; 3] {
+    [
+        m[0] * v[0] + m[4] * v[1] + m[8] * v[2],
+        m[1] * v[0] + m[5] * v[1] + m[9] * v[2],
+        m[2] * v[0] + m[6] * v[1] + m[10] * v[2]
+    ]
+}
+
+/// Returns the matrix with the given columns for its upper 3 x 3 part and the given translation.
+fn affine(x: [f32; 3], y: [f32; 3], z: [f32; 3], t: [f32; 3]) -> Matrix {
+    [
+        x[0], x[1], x[2], 0.0,
+        y[0], y[1], y[2], 0.0,
+        z[0], z[1], z[2], 0.0,
+        t[0], t[1], t[2], 1.0
+    ]
+}
+
+/// Returns the matrix rotating by `angle` radians about a unit axis.
+fn rotation(angle: f32, x: f32, y: f32, z: f32) -> Matrix {
+    let (s, c) = angle.sin_cos();
+    let d = 1.0 - c;
+    affine(
+        [c + d * x * x, d * x * y + s * z, d * x * z - s * y],
+        [d * x * y - s * z, c + d * y * y, d * y * z + s * x],
+        [d * x * z + s * y, d * y * z - s * x, c + d * z * z],
+        [0.0; 3]
+    )
+}
+
+impl Transformation {
+    /// Returns the matrix of the transformation.
+    ///
+    /// Angles are in the units of the angle metric of the scene, which are radians unless the scene
+    /// says otherwise. The axis of an axis rotation and a quaternion are normalized first.
+    pub fn to_matrix(&self) -> Matrix {
+        match *self {
+            Transformation::Transform(Transform(m)) => m,
+            Transformation::Translation(ref t) => {
+                let t = match *t {
+                    Translation::X(x) => [x, 0.0, 0.0],
+                    Translation::Y(y) => [0.0, y, 0.0],
+                    Translation::Z(z) => [0.0, 0.0, z],
+                    Translation::Xyz(x, y, z) => [x, y, z]
+                };
+                affine([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], t)
+            }
+            Transformation::Rotation(ref r) => match *r {
+                Rotation::X(a) => rotation(a, 1.0, 0.0, 0.0),
+                Rotation::Y(a) => rotation(a, 0.0, 1.0, 0.0),
+                Rotation::Z(a) => rotation(a, 0.0, 0.0, 1.0),
+                Rotation::Axis(a, x, y, z) => {
+                    let len = (x * x + y * y + z * z).sqrt();
+                    if len == 0.0 {
+                        return IDENTITY;
+                    }
+                    rotation(a, x / len, y / len, z / len)
+                }
+                Rotation::Quaternion(x, y, z, w) => {
+                    let len = (x * x + y * y + z * z + w * w).sqrt();
+                    if len == 0.0 {
+                        return IDENTITY;
+                    }
+                    let (x, y, z, w) = (x / len, y / len, z / len, w / len);
+                    affine(
+                        [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w)],
+                        [2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w)],
+                        [2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y)],
+                        [0.0; 3]
+                    )
+                }
+            },
+            Transformation::Scale(ref s) => {
+                let s = match *s {
+                    Scale::X(x) => [x, 1.0, 1.0],
+                    Scale::Y(y) => [1.0, y, 1.0],
+                    Scale::Z(z) => [1.0, 1.0, z],
+                    Scale::Xyz(x, y, z) => [x, y, z]
+                };
+                affine([s[0], 0.0, 0.0], [0.0, s[1], 0.0], [0.0, 0.0, s[2]], [0.0; 3])
+            }
+        }
+    }
+}
+
+/// Returns the product of a list of transformations, in the order they appear.
+pub fn local_matrix<'a, I>(transformations: I) -> Matrix
+    where I: IntoIterator<Item = &'a Transformation>
+{
+    transformations.into_iter().fold(IDENTITY, |m, t| multiply(&m, &t.to_matrix()))
+}
+
+macro_rules! local_matrix {
+    ($($node:ident),*) => ($(
+        impl $node {
+            /// Returns the transformation of the node relative to its parent, the product of its
+            /// transformations.
+            pub fn local_matrix(&self) -> Matrix {
+                local_matrix(self.transformations.iter().map(|t| &**t))
+            }
+        }
+    )*)
+}
+
+local_matrix!(Node, BoneNode, GeometryNode, CameraNode, LightNode);
+
+impl Nodes {
+    /// Returns the transformation of the node relative to its parent, the product of its
+    /// transformations.
+    pub fn local_matrix(&self) -> Matrix {
+        local_matrix(self.transformations().iter().map(|t| &**t))
+    }
+}
+
+impl Scene {
+    /// Returns every node of the scene along with its world matrix, which transforms the object
+    /// space of the node into the space of the scene.
+    ///
+    /// The nodes are listed depth-first, every node before its children, in the order they appear
+    /// in the scene.
+    pub fn world_matrices(&self) -> Vec<(&Nodes, Matrix)> {
+        self.posed_world_matrices(&Pose::default())
+    }
+}
@@ -0,0 +1,494 @@
+//! Validation of scenes against the rules of the OpenGEX specification.
+//!
+//! Loading a file already rejects whatever cannot be represented by the structures of the crate,
+//! like a Transform structure holding several matrices inside a node. The rules checked here are
+//! the ones the structures can break, either because the file breaks them or because the scene was
+//! built or modified in code, like two MorphWeight structures of a node with the same target
+//! index, or an index array referring to missing vertices.
+//!
+//! Every finding is a `Diagnostic`, with a `Severity`. Errors break a rule of the specification,
+//! while warnings point at data the specification allows, but which is most likely a mistake:
+//!
+//! ```text
+//! error: index 9 is out of the 8 vertices of the mesh, in GeometryObject #0 / Mesh / IndexArray #0
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use primitive::vertex_count;
+use structure::*;
+
+/// How serious a diagnostic is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The data is allowed, but most likely a mistake.
+    Warning,
+    /// The data breaks a rule of the OpenGEX specification.
+    Error
+}
+
+/// A problem found in a scene.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// What the problem is.
+    pub kind: DiagnosticKind,
+    /// The structures containing the problem, outermost first, e.g. `["GeometryObject #0", "Mesh",
+    /// "VertexArray (attrib = \"normal\")"]`. Objects are numbered by their position in the scene,
+    /// and nodes are written with the content of their Name structure if they have one.
+    pub path: Vec<String>
+}
+
+impl Diagnostic {
+    /// Returns the severity of the diagnostic.
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+/// The kinds of problems found in a scene.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// Several MorphWeight structures of a geometry node have the same target index.
+    DuplicateMorphWeight(u32),
+    /// A MorphWeight structure applies to a morph target without vertex data. It is ignored.
+    UnusedMorphWeight(u32),
+    /// A Morph structure has a base target without vertex data.
+    MissingBaseTarget(u32),
+    /// The meshes of a geometry object do not all use the same primitive.
+    MixedPrimitives {
+        /// The primitive of the mesh with the lowest level of detail.
+        expected: GeometricPrimitive,
+        /// The primitive of the mesh.
+        found: GeometricPrimitive
+    },
+    /// A mesh has no vertex array with positions.
+    MissingPositions,
+    /// The data of a vertex array does not hold a whole number of vertices.
+    PartialVertex {
+        /// The number of components of each vertex.
+        components: usize,
+        /// The number of values in the data.
+        values: usize
+    },
+    /// A vertex array does not have as many vertices as the positions of the mesh.
+    VertexCount {
+        /// The number of positions of the mesh.
+        expected: usize,
+        /// The number of vertices of the array.
+        found: usize
+    },
+    /// An index refers to a vertex the mesh does not have.
+    IndexOutOfRange {
+        /// The index.
+        index: u64,
+        /// The number of vertices of the mesh.
+        vertices: usize
+    },
+    /// A list of primitives ends with an incomplete one, which is ignored.
+    IncompletePrimitive {
+        /// The primitive of the mesh.
+        primitive: GeometricPrimitive,
+        /// The number of indices, or vertices without index arrays, left over.
+        left: usize
+    },
+    /// An index array applies a material that a geometry node referencing the mesh does not have.
+    MissingMaterial(u32),
+    /// The skeleton of a skin does not have a transform for every bone.
+    SkeletonTransformCount {
+        /// The number of bones.
+        bones: usize,
+        /// The number of transforms.
+        transforms: usize
+    },
+    /// The bone count array of a skin does not have an entry for every vertex of the mesh.
+    BoneCountLength {
+        /// The number of vertices of the mesh.
+        expected: usize,
+        /// The number of entries in the bone count array.
+        found: usize
+    },
+    /// The bone index or bone weight array of a skin does not hold the sum of all bone counts.
+    BoneArrayLength {
+        /// The sum of all bone counts.
+        expected: usize,
+        /// The number of bone indices.
+        indices: usize,
+        /// The number of bone weights.
+        weights: usize
+    },
+    /// A bone index refers to a bone the skeleton does not have.
+    BoneIndexOutOfRange {
+        /// The bone index.
+        index: u64,
+        /// The number of bones of the skeleton.
+        bones: usize
+    },
+    /// The bone weights of a vertex do not sum to 1.
+    BoneWeightSum {
+        /// The vertex.
+        vertex: usize,
+        /// The sum of its bone weights.
+        sum: f32
+    },
+    /// The target of a track is not a transformation or morph weight of the node of the
+    /// animation.
+    ForeignTrackTarget,
+    /// The value curve of a track does not hold a value for every key time, or its values do not
+    /// have as many components as its target.
+    TrackValueCount {
+        /// The number of components of the target.
+        components: usize,
+        /// The number of keys of the time curve.
+        keys: usize,
+        /// The number of values of the value curve.
+        values: usize
+    },
+    /// The key times of a track are not increasing.
+    UnsortedKeyTimes
+}
+
+impl DiagnosticKind {
+    /// Returns the severity of this kind of diagnostic.
+    pub fn severity(&self) -> Severity {
+        match *self {
+            DiagnosticKind::UnusedMorphWeight(_) |
+            DiagnosticKind::IncompletePrimitive { .. } |
+            DiagnosticKind::MissingMaterial(_) |
+            DiagnosticKind::BoneWeightSum { .. } => Severity::Warning,
+            _ => Severity::Error
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error")
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DiagnosticKind::DuplicateMorphWeight(index) => {
+                write!(f, "duplicate MorphWeight for target index {}", index)
+            }
+            DiagnosticKind::UnusedMorphWeight(index) => {
+                write!(f, "MorphWeight for target index {} without vertex data", index)
+            }
+            DiagnosticKind::MissingBaseTarget(index) => {
+                write!(f, "base target index {} without vertex data", index)
+            }
+            DiagnosticKind::MixedPrimitives { expected, found } => {
+                write!(f, "mesh primitive {:?} differs from {:?}", found, expected)
+            }
+            DiagnosticKind::MissingPositions => write!(f, "mesh without positions"),
+            DiagnosticKind::PartialVertex { components, values } => {
+                write!(f, "{} values do not make whole vertices of {} components",
+                    values, components)
+            }
+            DiagnosticKind::VertexCount { expected, found } => {
+                write!(f, "{} vertices instead of the {} positions of the mesh", found, expected)
+            }
+            DiagnosticKind::IndexOutOfRange { index, vertices } => {
+                write!(f, "index {} is out of the {} vertices of the mesh", index, vertices)
+            }
+            DiagnosticKind::IncompletePrimitive { primitive, left } => {
+                write!(f, "{} indices left over from the {:?} primitives", left, primitive)
+            }
+            DiagnosticKind::MissingMaterial(index) => {
+                write!(f, "no material for index {} in a GeometryNode referencing the mesh", index)
+            }
+            DiagnosticKind::SkeletonTransformCount { bones, transforms } => {
+                write!(f, "{} transforms for {} bones", transforms, bones)
+            }
+            DiagnosticKind::BoneCountLength { expected, found } => {
+                write!(f, "{} bone counts for {} vertices", found, expected)
+            }
+            DiagnosticKind::BoneArrayLength { expected, indices, weights } => {
+                write!(f, "{} bone indices and {} bone weights for a bone count sum of {}",
+                    indices, weights, expected)
+            }
+            DiagnosticKind::BoneIndexOutOfRange { index, bones } => {
+                write!(f, "bone index {} is out of the {} bones of the skeleton", index, bones)
+            }
+            DiagnosticKind::BoneWeightSum { vertex, sum } => {
+                write!(f, "bone weights of vertex {} sum to {}", vertex, sum)
+            }
+            DiagnosticKind::ForeignTrackTarget => {
+                write!(f, "track target does not belong to the node of the animation")
+            }
+            DiagnosticKind::TrackValueCount { components, keys, values } => {
+                write!(f, "{} values for {} keys of {} components", values, keys, components)
+            }
+            DiagnosticKind::UnsortedKeyTimes => write!(f, "key times are not increasing")
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity(), self.kind)?;
+        if !self.path.is_empty() {
+            write!(f, ", in {}", self.path.join(" / "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Scene {
+    /// Checks the scene against the rules of the OpenGEX specification, and returns the problems
+    /// found. Shared objects are checked once, in the order of the object lists of the scene.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator { path: vec![], diagnostics: vec![] };
+        for (i, geometry) in self.geometry_objects.iter().enumerate() {
+            validator.enter(format!("GeometryObject #{}", i), |v| v.geometry_object(geometry));
+        }
+        validator.nodes(&self.nodes);
+        validator.diagnostics
+    }
+}
+
+/// Gathers the diagnostics of a scene.
+struct Validator {
+    /// The path of the structure being checked.
+    path: Vec<String>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Validator {
+    fn report(&mut self, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic { kind, path: self.path.clone() });
+    }
+
+    /// Checks the contents of a structure, with the structure added to the path.
+    fn enter<F: FnOnce(&mut Validator)>(&mut self, structure: String, f: F) {
+        self.path.push(structure);
+        f(self);
+        self.path.pop();
+    }
+
+    fn geometry_object(&mut self, geometry: &GeometryObject) {
+        let first = geometry.meshes.values().next().map(|m| m.primitive);
+        for (lod, mesh) in &geometry.meshes {
+            let name = if lod == 0 { "Mesh".to_string() } else { format!("Mesh (lod = {})", lod) };
+            self.enter(name, |v| {
+                if let Some(expected) = first {
+                    if mesh.primitive != expected {
+                        v.report(DiagnosticKind::MixedPrimitives {
+                            expected,
+                            found: mesh.primitive
+                        });
+                    }
+                }
+                v.mesh(mesh);
+            });
+        }
+        for (index, morph) in &geometry.morphs {
+            match morph.base_target_index {
+                Some(base) if !geometry.meshes.values().any(|m| has_target(m, base)) => {
+                    self.enter(format!("Morph (index = {})", index),
+                        |v| v.report(DiagnosticKind::MissingBaseTarget(base)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn mesh(&mut self, mesh: &Mesh) {
+        let vertices = vertex_count(mesh);
+        if !mesh.vertex_arrays.iter().any(|a| a.attrib == "position" && a.morph == 0) {
+            self.report(DiagnosticKind::MissingPositions);
+        }
+        for array in &mesh.vertex_arrays {
+            let mut name = format!("VertexArray (attrib = \"{}", array.attrib);
+            if array.index != 0 {
+                name += &format!("[{}]", array.index);
+            }
+            name += "\"";
+            if array.morph != 0 {
+                name += &format!(", morph = {}", array.morph);
+            }
+            name += ")";
+            self.enter(name, |v| {
+                if array.components == 0 || array.data.len() % array.components != 0 {
+                    v.report(DiagnosticKind::PartialVertex {
+                        components: array.components,
+                        values: array.data.len()
+                    });
+                } else if array.len() != vertices {
+                    v.report(DiagnosticKind::VertexCount {
+                        expected: vertices,
+                        found: array.len()
+                    });
+                }
+            });
+        }
+
+        let size = match mesh.primitive {
+            GeometricPrimitive::Points | GeometricPrimitive::LineStrip |
+            GeometricPrimitive::TriangleStrip => 1,
+            GeometricPrimitive::Lines => 2,
+            GeometricPrimitive::Triangles => 3,
+            GeometricPrimitive::Quads => 4
+        };
+        if mesh.index_arrays.is_empty() && vertices % size != 0 {
+            self.report(DiagnosticKind::IncompletePrimitive {
+                primitive: mesh.primitive,
+                left: vertices % size
+            });
+        }
+        for (i, array) in mesh.index_arrays.iter().enumerate() {
+            self.enter(format!("IndexArray #{}", i), |v| {
+                let indices = array.data.to_u64();
+                if let Some(&index) = indices.iter()
+                    .find(|&&i| Some(i) != array.restart && i >= vertices as u64)
+                {
+                    v.report(DiagnosticKind::IndexOutOfRange { index, vertices });
+                }
+                for run in indices.split(|&i| Some(i) == array.restart) {
+                    if run.len() % size != 0 {
+                        v.report(DiagnosticKind::IncompletePrimitive {
+                            primitive: mesh.primitive,
+                            left: run.len() % size
+                        });
+                    }
+                }
+            });
+        }
+
+        if let Some(ref skin) = mesh.skin {
+            self.enter("Skin".to_string(), |v| v.skin(skin, vertices));
+        }
+    }
+
+    fn skin(&mut self, skin: &Skin, vertices: usize) {
+        let bones = skin.skeleton.bone_ref_array.len();
+        if skin.skeleton.transforms.len() != bones {
+            self.enter("Skeleton".to_string(), |v| {
+                v.report(DiagnosticKind::SkeletonTransformCount {
+                    bones,
+                    transforms: skin.skeleton.transforms.len()
+                });
+            });
+        }
+        if skin.bone_count_array.len() != vertices {
+            self.report(DiagnosticKind::BoneCountLength {
+                expected: vertices,
+                found: skin.bone_count_array.len()
+            });
+        }
+        let counts = skin.bone_count_array.to_u64();
+        let expected = counts.iter().sum::<u64>() as usize;
+        if skin.bone_index_array.len() != expected || skin.bone_weight_array.len() != expected {
+            self.report(DiagnosticKind::BoneArrayLength {
+                expected,
+                indices: skin.bone_index_array.len(),
+                weights: skin.bone_weight_array.len()
+            });
+        }
+        if let Some(index) = skin.bone_index_array.to_u64().into_iter()
+            .find(|&i| i >= bones as u64)
+        {
+            self.report(DiagnosticKind::BoneIndexOutOfRange { index, bones });
+        }
+        let mut k = 0;
+        for (vertex, &count) in counts.iter().enumerate() {
+            let end = (k + count as usize).min(skin.bone_weight_array.len());
+            let sum: f32 = skin.bone_weight_array.get(k..end).map_or(0.0, |w| w.iter().sum());
+            k += count as usize;
+            if count > 0 && (sum - 1.0).abs() > 1e-3 {
+                self.report(DiagnosticKind::BoneWeightSum { vertex, sum });
+                break;
+            }
+        }
+    }
+
+    fn nodes(&mut self, nodes: &[Nodes]) {
+        for node in nodes {
+            let kind = match *node {
+                Nodes::Node(_) => "Node",
+                Nodes::BoneNode(_) => "BoneNode",
+                Nodes::GeometryNode(_) => "GeometryNode",
+                Nodes::CameraNode(_) => "CameraNode",
+                Nodes::LightNode(_) => "LightNode"
+            };
+            let name = match node.name() {
+                Some(name) => format!("{} \"{}\"", kind, name),
+                None => kind.to_string()
+            };
+            self.enter(name, |v| {
+                let morph_weights = match *node {
+                    Nodes::GeometryNode(ref node) => {
+                        v.geometry_node(node);
+                        &node.morph_weights[..]
+                    }
+                    _ => &[]
+                };
+                for animation in node.animations() {
+                    v.enter("Animation".to_string(), |v| {
+                        v.animation(animation, node.transformations(), morph_weights);
+                    });
+                }
+                v.nodes(node.children());
+            });
+        }
+    }
+
+    fn geometry_node(&mut self, node: &GeometryNode) {
+        let mut indices = HashSet::new();
+        for weight in &node.morph_weights {
+            if !indices.insert(weight.target_index) {
+                self.report(DiagnosticKind::DuplicateMorphWeight(weight.target_index));
+            } else if !node.geometry.meshes.values().any(|m| has_target(m, weight.target_index)) {
+                self.report(DiagnosticKind::UnusedMorphWeight(weight.target_index));
+            }
+        }
+        let mut missing: Vec<u32> = node.geometry.meshes.values()
+            .flat_map(|m| m.index_arrays.iter().map(|a| a.material))
+            .filter(|&m| !node.materials.contains_key(m as usize))
+            .collect();
+        missing.sort();
+        missing.dedup();
+        for material in missing {
+            self.report(DiagnosticKind::MissingMaterial(material));
+        }
+    }
+
+    fn animation(&mut self, animation: &Animation, transformations: &[Arc<Transformation>],
+        morph_weights: &[Arc<MorphWeight>])
+    {
+        for track in &animation.tracks {
+            let components = match track.target {
+                TrackTarget::Transformation(ref t) => {
+                    if !transformations.iter().any(|x| Arc::ptr_eq(x, t)) {
+                        self.report(DiagnosticKind::ForeignTrackTarget);
+                    }
+                    t.components().len()
+                }
+                TrackTarget::MorphWeight(ref w) => {
+                    if !morph_weights.iter().any(|x| Arc::ptr_eq(x, w)) {
+                        self.report(DiagnosticKind::ForeignTrackTarget);
+                    }
+                    1
+                }
+            };
+            let (keys, values) = (track.time.len(), track.value.len());
+            if values != keys * components {
+                self.report(DiagnosticKind::TrackValueCount { components, keys, values });
+            }
+            if (1..keys).any(|i| track.time.key(i) < track.time.key(i - 1)) {
+                self.report(DiagnosticKind::UnsortedKeyTimes);
+            }
+        }
+    }
+}
+
+/// Whether a mesh has vertex data for a morph target. Target 0 is the mesh itself.
+fn has_target(mesh: &Mesh, morph: u32) -> bool {
+    morph == 0 || mesh.vertex_arrays.iter().any(|a| a.morph == morph)
+}
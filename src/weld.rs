@@ -0,0 +1,227 @@
+//! Welding and unwelding of vertices.
+//!
+//! Welding merges the vertices of a mesh that match within a tolerance, in every vertex array of
+//! every morph target and in their skinning data, and rebuilds the index arrays to refer to the
+//! merged vertices. Unwelding goes the other way, giving every index of the index arrays a vertex
+//! of its own.
+//!
+//! Both operations expect every vertex array, and the bone count array of the skin, to hold the
+//! same number of vertices. The vertex arrays of morph targets, and the bone influences of the
+//! skin, are rebuilt along with the other arrays, so they stay consistent with the new vertices.
+
+use std::collections::HashMap;
+
+use primitive::{ index_data, vertex_count };
+use structure::*;
+
+impl Mesh {
+    /// Merges the vertices whose values all match within `tolerance` in every vertex array, and
+    /// whose bones and bone weights match in the skin. A merged vertex takes the values of its
+    /// first occurrence, and the vertices keep the order of their first occurrences.
+    ///
+    /// The index arrays are rewritten to refer to the merged vertices, leaving out the primitives
+    /// with an index that refers to no vertex, like `Mesh::unweld`. A mesh without index arrays
+    /// gets one, with the vertices in their original order.
+    pub fn weld(&self, tolerance: f64) -> Mesh {
+        let count = vertex_count(self);
+        let arrays: Vec<Vec<f64>> = self.vertex_arrays.iter().map(|a| a.data.to_f64()).collect();
+        let influences = self.skin.as_ref().map(influences);
+        let key = self.vertex_arrays.iter()
+            .position(|a| a.attrib == "position" && a.morph == 0)
+            .unwrap_or(0);
+        let components = self.vertex_arrays.get(key).map_or(0, |a| a.components);
+
+        let same = |a: usize, b: usize| {
+            let arrays_match = self.vertex_arrays.iter().zip(&arrays).all(|(array, values)| {
+                let c = array.components;
+                match (values.get(a * c..(a + 1) * c), values.get(b * c..(b + 1) * c)) {
+                    (Some(x), Some(y)) => x.iter().zip(y).all(|(x, y)| (x - y).abs() <= tolerance),
+                    (x, y) => x.is_none() && y.is_none()
+                }
+            });
+            arrays_match && influences.as_ref().map_or(true, |influences| {
+                match (influences.get(a), influences.get(b)) {
+                    (Some(x), Some(y)) => x.len() == y.len() && x.iter().zip(y).all(|(x, y)| {
+                        x.0 == y.0 && (x.1 as f64 - y.1 as f64).abs() <= tolerance
+                    }),
+                    (x, y) => x.is_none() && y.is_none()
+                }
+            })
+        };
+
+        // Vertices are looked up in a grid of cells as large as the tolerance, keyed by their
+        // position. A match may lie in a neighboring cell.
+        let cell = |v: usize| -> Vec<i64> {
+            (0..components).map(|i| {
+                let x = arrays[key].get(v * components + i).cloned().unwrap_or(0.0);
+                if tolerance > 0.0 {
+                    (x / tolerance).floor() as i64
+                } else {
+                    (x + 0.0).to_bits() as i64
+                }
+            }).collect()
+        };
+        let neighbors = if tolerance > 0.0 { 3usize.pow(components as u32) } else { 1 };
+        let mut grid: HashMap<Vec<i64>, Vec<usize>> = HashMap::new();
+        let mut vertices = vec![];
+        let mut remap = Vec::with_capacity(count);
+        for v in 0..count {
+            let home = cell(v);
+            let found = (0..neighbors).filter_map(|n| {
+                let mut neighbor = home.clone();
+                let mut n = n;
+                for k in neighbor.iter_mut().filter(|_| tolerance > 0.0) {
+                    *k += (n % 3) as i64 - 1;
+                    n /= 3;
+                }
+                grid.get(&neighbor)
+                    .and_then(|candidates| candidates.iter().find(|&&u| same(vertices[u], v)))
+                    .cloned()
+            }).min();
+            match found {
+                Some(u) => remap.push(u as u64),
+                None => {
+                    remap.push(vertices.len() as u64);
+                    grid.entry(home).or_default().push(vertices.len());
+                    vertices.push(v);
+                }
+            }
+        }
+
+        let index_arrays = if self.index_arrays.is_empty() {
+            vec![IndexArray {
+                material: 0,
+                restart: None,
+                front_face: FrontFace::default(),
+                data: index_data(vertices.len(), remap)
+            }]
+        } else {
+            self.index_arrays.iter().map(|array| {
+                let indices = in_range(self.primitive, &array.data.to_u64(), array.restart, count)
+                    .into_iter()
+                    .map(|i| if Some(i) == array.restart { i } else { remap[i as usize] })
+                    .collect();
+                IndexArray { data: array.data.with_values(indices), ..array.clone() }
+            }).collect()
+        };
+        with_vertices(self, &vertices, index_arrays)
+    }
+
+    /// Gives every index of the index arrays a vertex of its own, so that no two primitives share
+    /// a vertex. Returns the mesh as it is if it has no index arrays.
+    ///
+    /// The indices of each index array refer to consecutive vertices. Restart indices are kept,
+    /// and replaced by the largest value of the new index type. A primitive with an index that
+    /// refers to no vertex is left out as a whole, so that the primitives after it are kept as
+    /// they were. For strips, this leaves out the strip up to the next restart index.
+    pub fn unweld(&self) -> Mesh {
+        if self.index_arrays.is_empty() {
+            return self.clone();
+        }
+        let count = vertex_count(self);
+        let mut vertices = vec![];
+        let mut arrays = vec![];
+        for array in &self.index_arrays {
+            let mut indices = vec![];
+            for i in in_range(self.primitive, &array.data.to_u64(), array.restart, count) {
+                if Some(i) == array.restart {
+                    indices.push(None);
+                } else {
+                    indices.push(Some(vertices.len() as u64));
+                    vertices.push(i as usize);
+                }
+            }
+            arrays.push(indices);
+        }
+        let restart = if vertices.len() <= u16::MAX as usize {
+            u16::MAX as u64
+        } else {
+            u32::MAX as u64
+        };
+        let index_arrays = self.index_arrays.iter().zip(arrays).map(|(array, indices)| {
+            IndexArray {
+                restart: array.restart.map(|_| restart),
+                data: index_data(vertices.len(),
+                    indices.into_iter().map(|i| i.unwrap_or(restart)).collect()),
+                ..array.clone()
+            }
+        }).collect();
+        with_vertices(self, &vertices, index_arrays)
+    }
+}
+
+/// Returns the indices of an index array without the primitives that have an index referring to
+/// none of the `count` vertices. A strip counts as one primitive up to the next restart index, and
+/// restart indices are kept.
+fn in_range(primitive: GeometricPrimitive, indices: &[u64], restart: Option<u64>, count: usize)
+    -> Vec<u64>
+{
+    let mut kept = vec![];
+    for (k, run) in indices.split(|&i| Some(i) == restart).enumerate() {
+        if k > 0 {
+            kept.extend(restart);
+        }
+        let size = match primitive {
+            GeometricPrimitive::Points => 1,
+            GeometricPrimitive::Lines => 2,
+            GeometricPrimitive::Triangles => 3,
+            GeometricPrimitive::Quads => 4,
+            GeometricPrimitive::LineStrip |
+            GeometricPrimitive::TriangleStrip => run.len().max(1)
+        };
+        for indices in run.chunks(size) {
+            if indices.iter().all(|&i| i < count as u64) {
+                kept.extend_from_slice(indices);
+            }
+        }
+    }
+    kept
+}
+
+/// Returns a mesh with the given vertices, by their index in the mesh, and index arrays. The
+/// vertex arrays and the skin of the mesh are rebuilt for the new vertices.
+pub fn with_vertices(mesh: &Mesh, vertices: &[usize], index_arrays: Vec<IndexArray>) -> Mesh {
+    let vertex_arrays = mesh.vertex_arrays.iter().map(|array| {
+        let c = array.components;
+        let values = array.data.to_f64();
+        let values = vertices.iter()
+            .filter_map(|&v| values.get(v * c..(v + 1) * c))
+            .flat_map(|v| v.iter().cloned())
+            .collect();
+        VertexArray { data: array.data.with_values(values), ..array.clone() }
+    }).collect();
+    let skin = mesh.skin.as_ref().map(|skin| {
+        let influences = influences(skin);
+        let selected: Vec<&Vec<(u64, f32)>> = vertices.iter()
+            .filter_map(|&v| influences.get(v))
+            .collect();
+        Skin {
+            bone_count_array: skin.bone_count_array.with_values(
+                selected.iter().map(|i| i.len() as u64).collect()),
+            bone_index_array: skin.bone_index_array.with_values(
+                selected.iter().flat_map(|i| i.iter().map(|b| b.0)).collect()),
+            bone_weight_array: selected.iter().flat_map(|i| i.iter().map(|b| b.1)).collect(),
+            ..skin.clone()
+        }
+    });
+    Mesh {
+        vertex_arrays,
+        index_arrays,
+        skin,
+        ..mesh.clone()
+    }
+}
+
+/// Returns the bone indices and weights influencing every vertex of a skin.
+fn influences(skin: &Skin) -> Vec<Vec<(u64, f32)>> {
+    let mut k = 0;
+    (0..skin.bone_count_array.len()).map(|v| {
+        let count = skin.bone_count_array.get(v) as usize;
+        let end = (k + count).min(skin.bone_index_array.len())
+            .min(skin.bone_weight_array.len()).max(k);
+        let bones = (k..end).map(|i| (skin.bone_index_array.get(i), skin.bone_weight_array[i]))
+            .collect();
+        k += count;
+        bones
+    }).collect()
+}
@@ -0,0 +1,1109 @@
+//! Writing of scenes as OpenGEX files.
+//!
+//! A scene is first converted into OpenDDL structures, which are then formatted as text. Objects
+//! shared through an `Arc` are written once, with a generated global name such as `$geometry1`,
+//! and the nodes using them refer to them through `ObjectRef` and `MaterialRef` structures. The
+//! transformations and morph weights targeted by animation tracks get generated local names.
+
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::sync::Arc;
+
+use ddl::{ self, Data, DerivedStructure, Literal, PrimitiveStructure, Property, Reference,
+    Structure };
+use error::{ Error, ErrorKind };
+use structure::*;
+
+impl Scene {
+    /// Formats the scene as OpenGEX source text.
+    pub fn to_string_with(&self, options: &Options) -> Result<String, Error> {
+        Ok(format_with(&to_ddl(self)?, options))
+    }
+
+    /// Writes the scene as OpenGEX source text to a writer, with the default options.
+    pub fn to_writer<W: Write>(&self, writer: W) -> Result<(), Error> {
+        self.to_writer_with(writer, &Options::default())
+    }
+
+    /// Writes the scene as OpenGEX source text to a writer.
+    pub fn to_writer_with<W: Write>(&self, mut writer: W, options: &Options) -> Result<(), Error> {
+        writer.write_all(self.to_string_with(options)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Converts a scene into the OpenDDL structures of an OpenGEX file.
+///
+/// Objects referenced by the nodes of the scene are written even when they are missing from the
+/// object lists of the scene, as are the bone nodes referenced by skeletons but missing from the
+/// node hierarchy. An animation track targeting a transformation or morph weight that does not
+/// belong to the animated node or texture is an error, as OpenGEX cannot express it.
+pub fn to_ddl(scene: &Scene) -> Result<Vec<Structure>, Error> {
+    let mut names = Names::default();
+    for object in &scene.geometry_objects {
+        names.geometry_object(object);
+    }
+    for object in &scene.light_objects {
+        names.light_object(object);
+    }
+    for object in &scene.camera_objects {
+        names.camera_object(object);
+    }
+    for material in &scene.materials {
+        names.material(material);
+    }
+    for node in &scene.nodes {
+        names.node(node);
+    }
+    // Skeletons may refer to bones outside of the node hierarchy, which are written at the top
+    // level.
+    let mut bones: Vec<Nodes> = vec![];
+    while let Some(bone) = names.missing_bones.pop() {
+        if !names.contains(&*bone) {
+            let bone = Nodes::BoneNode(bone);
+            names.node(&bone);
+            bones.push(bone);
+        }
+    }
+    for node in scene.nodes.iter().chain(&bones) {
+        names.check_node(node)?;
+    }
+    names.check_textures()?;
+
+    let mut structures = vec![];
+    let metric = &scene.metric;
+    let axis = |axis| Data::String(vec![match axis {
+        Axis::X => "x",
+        Axis::Y => "y",
+        Axis::Z => "z",
+        Axis::NegX => "-x",
+        Axis::NegY => "-y",
+        Axis::NegZ => "-z"
+    }.to_string()]);
+    let mut metrics = vec![
+        ("distance", Data::Float(vec![metric.distance])),
+        ("angle", Data::Float(vec![metric.angle])),
+        ("time", Data::Float(vec![metric.time])),
+        ("up", axis(metric.up))
+    ];
+    if let Some(forward) = metric.forward {
+        metrics.push(("forward", axis(forward)));
+    }
+    let mut others: Vec<_> = metric.others.iter().collect();
+    others.sort_by(|a, b| a.0.cmp(b.0));
+    metrics.extend(others.into_iter().map(|(key, value)| (key.as_str(), match *value {
+        MetricValue::Float(x) => Data::Float(vec![x]),
+        MetricValue::String(ref s) => Data::String(vec![s.clone()])
+    })));
+    for (key, data) in metrics {
+        structures.push(derived("Metric", None, vec![string_property("key", key)],
+            vec![primitive(data, None)]));
+    }
+    for (index, clip) in scene.clip_structures.iter() {
+        let mut children: Vec<Structure> = clip.name.iter().map(|n| name_structure(n)).collect();
+        children.extend(attributes(&HashMap::new(), &clip.param, &HashMap::new()));
+        structures.push(derived("Clip", None, vec![integer_property("index", index as i64)],
+            children));
+    }
+    for node in scene.nodes.iter().chain(&bones) {
+        structures.push(names.write_node(node));
+    }
+    for object in &names.geometry_objects {
+        structures.push(names.write_geometry_object(object));
+    }
+    for object in &names.light_objects {
+        structures.push(names.write_light_object(object));
+    }
+    for object in &names.camera_objects {
+        structures.push(names.write_camera_object(object));
+    }
+    for material in &names.materials {
+        structures.push(names.write_material(material));
+    }
+    Ok(structures)
+}
+
+/// Options controlling how OpenGEX source text is laid out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// The indentation of nested structures.
+    pub indent: Indent,
+    /// The number of array elements written on each line of a long primitive structure, a
+    /// subarray counting as one element. With 0, all elements are written on a single line.
+    pub elements_per_line: usize,
+    /// How floating-point values are written.
+    pub precision: Precision,
+    /// Whether long primitive structures are followed by a comment holding their element count,
+    /// like `float[3]  // 24`.
+    pub count_comments: bool,
+    /// Whether to write the whole file without any whitespace, ignoring the other layout options.
+    pub minify: bool
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            indent: Indent::Tabs,
+            elements_per_line: 8,
+            precision: Precision::Shortest,
+            count_comments: false,
+            minify: false
+        }
+    }
+}
+
+/// The indentation of nested structures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    /// One tab per level.
+    Tabs,
+    /// The given number of spaces per level.
+    Spaces(usize)
+}
+
+/// How floating-point values are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    /// The shortest representation reading back to the same value.
+    Shortest,
+    /// A fixed number of digits after the decimal point. Values may not read back exactly.
+    Fixed(usize)
+}
+
+/// Formats OpenDDL structures as source text, with the default options.
+pub fn format(structures: &[Structure]) -> String {
+    format_with(structures, &Options::default())
+}
+
+/// Formats OpenDDL structures as source text.
+///
+/// Top-level structures are separated by blank lines, except between structures written on a
+/// single line such as a run of `Metric` structures.
+pub fn format_with(structures: &[Structure], options: &Options) -> String {
+    let mut printer = Printer { options, out: String::new() };
+    let mut previous_inline = true;
+    for (i, s) in structures.iter().enumerate() {
+        let start = printer.out.len();
+        printer.structure(s, 0);
+        if options.minify {
+            continue;
+        }
+        let inline = !printer.out[start..].contains('\n');
+        if i > 0 && !(inline && previous_inline) {
+            printer.out.insert(start, '\n');
+        }
+        printer.out.push('\n');
+        previous_inline = inline;
+    }
+    printer.out
+}
+
+/// The parts every kind of node has.
+struct Common<'a> {
+    name: &'a Option<Name>,
+    transformations: &'a [Arc<Transformation>],
+    animations: &'a [Animation],
+    children: &'a [Nodes]
+}
+
+macro_rules! common {
+    ($node:expr) => (Common {
+        name: &$node.name,
+        transformations: &$node.transformations,
+        animations: &$node.animations,
+        children: &$node.children
+    })
+}
+
+/// Returns the address of a shared object, identifying it regardless of its type.
+fn key<T>(object: &T) -> *const () {
+    object as *const T as *const ()
+}
+
+/// The generated names of the shared objects and nodes of a scene, along with the objects in the
+/// order they are written.
+#[derive(Default)]
+struct Names {
+    names: HashMap<*const (), String>,
+    geometry_objects: Vec<Arc<GeometryObject>>,
+    light_objects: Vec<Arc<LightObject>>,
+    camera_objects: Vec<Arc<CameraObject>>,
+    materials: Vec<Arc<Material>>,
+    node_count: usize,
+    /// Bones referenced by skeletons, which may not be part of the node hierarchy.
+    missing_bones: Vec<Arc<BoneNode>>
+}
+
+impl Names {
+    fn contains<T>(&self, object: &T) -> bool {
+        self.names.contains_key(&key(object))
+    }
+
+    /// Returns the global name of an object or node as a reference.
+    fn reference<T>(&self, object: &T) -> Reference {
+        let name = self.names.get(&key(object)).cloned().unwrap_or_default();
+        Reference(vec![ddl::Name::Global(name)])
+    }
+
+    fn name<T>(&self, object: &T) -> Option<ddl::Name> {
+        self.names.get(&key(object)).map(|name| ddl::Name::Global(name.clone()))
+    }
+
+    /// Registers an object under a name made of `prefix` and its position in `objects`.
+    fn add<T>(names: &mut HashMap<*const (), String>, objects: &mut Vec<Arc<T>>, object: &Arc<T>,
+        prefix: &str) -> bool
+    {
+        if names.contains_key(&key(&**object)) {
+            return false;
+        }
+        objects.push(object.clone());
+        names.insert(key(&**object), format!("{}{}", prefix, objects.len()));
+        true
+    }
+
+    fn geometry_object(&mut self, object: &Arc<GeometryObject>) {
+        if Names::add(&mut self.names, &mut self.geometry_objects, object, "geometry") {
+            for mesh in object.meshes.values() {
+                if let Some(ref skin) = mesh.skin {
+                    self.missing_bones.extend(skin.skeleton.bone_ref_array.iter().cloned());
+                }
+            }
+        }
+    }
+
+    fn light_object(&mut self, object: &Arc<LightObject>) {
+        Names::add(&mut self.names, &mut self.light_objects, object, "light");
+    }
+
+    fn camera_object(&mut self, object: &Arc<CameraObject>) {
+        Names::add(&mut self.names, &mut self.camera_objects, object, "camera");
+    }
+
+    fn material(&mut self, material: &Arc<Material>) {
+        Names::add(&mut self.names, &mut self.materials, material, "material");
+    }
+
+    /// Checks that the animations of a node and its children only target their own
+    /// transformations and morph weights.
+    fn check_node(&self, node: &Nodes) -> Result<(), Error> {
+        let (id, common, morph_weights): (_, Common, &[Arc<MorphWeight>]) = match *node {
+            Nodes::Node(ref node) => (key(node), common!(node), &[]),
+            Nodes::BoneNode(ref node) => (key(&**node), common!(node), &[]),
+            Nodes::GeometryNode(ref node) => (key(node), common!(node), &node.morph_weights),
+            Nodes::CameraNode(ref node) => (key(node), common!(node), &[]),
+            Nodes::LightNode(ref node) => (key(node), common!(node), &[])
+        };
+        if has_foreign_target(common.transformations, morph_weights, common.animations) {
+            let name = self.names.get(&id).cloned().unwrap_or_default();
+            return Err(foreign_target(&format!("node ${}", name)));
+        }
+        for child in common.children {
+            self.check_node(child)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the animations of the textures of the written objects only target the
+    /// transformations of their texture.
+    fn check_textures(&self) -> Result<(), Error> {
+        let objects = self.materials.iter().map(|m| (key(&**m), &m.texture))
+            .chain(self.light_objects.iter().map(|o| (key(&**o), &o.textures)))
+            .chain(self.camera_objects.iter().map(|o| (key(&**o), &o.textures)));
+        for (object, textures) in objects {
+            for (attrib, texture) in textures {
+                if has_foreign_target(&texture.transformations, &[], &texture.animation) {
+                    return Err(foreign_target(&format!("the {} texture of ${}", attrib,
+                        self.names[&object])));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Names a node and its children, and registers the objects they refer to.
+    fn node(&mut self, node: &Nodes) {
+        self.node_count += 1;
+        let name = format!("node{}", self.node_count);
+        let children = match *node {
+            Nodes::Node(ref node) => {
+                self.names.insert(key(node), name);
+                &node.children
+            }
+            Nodes::BoneNode(ref node) => {
+                self.names.insert(key(&**node), name);
+                &node.children
+            }
+            Nodes::GeometryNode(ref node) => {
+                self.names.insert(key(node), name);
+                self.geometry_object(&node.geometry);
+                for material in node.materials.values() {
+                    self.material(material);
+                }
+                &node.children
+            }
+            Nodes::CameraNode(ref node) => {
+                self.names.insert(key(node), name);
+                self.camera_object(&node.camera);
+                &node.children
+            }
+            Nodes::LightNode(ref node) => {
+                self.names.insert(key(node), name);
+                self.light_object(&node.light);
+                &node.children
+            }
+        };
+        for child in children {
+            self.node(child);
+        }
+    }
+
+    fn write_node(&self, node: &Nodes) -> Structure {
+        match *node {
+            Nodes::Node(ref node) => {
+                self.write_node_common("Node", self.name(node), vec![], vec![], common!(node), &[])
+            }
+            Nodes::BoneNode(ref node) => {
+                self.write_node_common("BoneNode", self.name(&**node), vec![], vec![],
+                    common!(node), &[])
+            }
+            Nodes::GeometryNode(ref node) => {
+                let mut properties = vec![];
+                push_bool(&mut properties, "visible", node.visibile);
+                push_bool(&mut properties, "shadow", node.casts_shadows);
+                push_bool(&mut properties, "motion_blur", node.motion_blur);
+                let mut refs = vec![reference_structure("ObjectRef", vec![],
+                    self.reference(&*node.geometry))];
+                for (index, material) in &node.materials {
+                    refs.push(reference_structure("MaterialRef",
+                        vec![integer_property("index", index as i64)],
+                        self.reference(&**material)));
+                }
+                self.write_node_common("GeometryNode", self.name(node), properties, refs,
+                    common!(node), &node.morph_weights)
+            }
+            Nodes::CameraNode(ref node) => {
+                let refs = vec![reference_structure("ObjectRef", vec![],
+                    self.reference(&*node.camera))];
+                self.write_node_common("CameraNode", self.name(node), vec![], refs,
+                    common!(node), &[])
+            }
+            Nodes::LightNode(ref node) => {
+                let mut properties = vec![];
+                push_bool(&mut properties, "visible", node.visibile);
+                push_bool(&mut properties, "shadow", node.casts_shadows);
+                let refs = vec![reference_structure("ObjectRef", vec![],
+                    self.reference(&*node.light))];
+                self.write_node_common("LightNode", self.name(node), properties, refs,
+                    common!(node), &[])
+            }
+        }
+    }
+
+    /// Writes a node, given the structures specific to its kind.
+    fn write_node_common(&self, identifier: &str, name: Option<ddl::Name>,
+        properties: Vec<Property>, mut children: Vec<Structure>, common: Common,
+        morph_weights: &[Arc<MorphWeight>]) -> Structure
+    {
+        if let Some(ref name) = *common.name {
+            children.insert(0, name_structure(name));
+        }
+        let targets = Targets::new(common.transformations, morph_weights, common.animations);
+        for (i, weight) in morph_weights.iter().enumerate() {
+            children.push(derived("MorphWeight", targets.morph_weights[i].clone(),
+                vec![integer_property("index", weight.target_index as i64)],
+                vec![primitive(Data::Float(vec![weight.weight]), None)]));
+        }
+        children.extend(targets.write_transformations(common.transformations));
+        children.extend(common.animations.iter().map(|a| targets.write_animation(a)));
+        children.extend(common.children.iter().map(|n| self.write_node(n)));
+        derived(identifier, name, properties, children)
+    }
+
+    fn write_geometry_object(&self, object: &Arc<GeometryObject>) -> Structure {
+        let mut properties = vec![];
+        push_bool(&mut properties, "visible", Some(object.visible).filter(|&v| !v));
+        push_bool(&mut properties, "shadow", Some(object.casts_shadows).filter(|&v| !v));
+        push_bool(&mut properties, "motion_blur", Some(object.motion_blur).filter(|&v| !v));
+        let mut children = vec![];
+        for (lod, mesh) in &object.meshes {
+            children.push(self.write_mesh(lod, mesh));
+        }
+        for (index, morph) in &object.morphs {
+            let mut properties = vec![integer_property("index", index as i64)];
+            if let Some(base) = morph.base_target_index {
+                properties.push(integer_property("base", base as i64));
+            }
+            children.push(derived("Morph", None, properties,
+                morph.name.iter().map(|name| name_structure(name)).collect()));
+        }
+        derived("GeometryObject", self.name(&**object), properties, children)
+    }
+
+    fn write_mesh(&self, lod: usize, mesh: &Mesh) -> Structure {
+        let mut properties = vec![];
+        if lod != 0 {
+            properties.push(integer_property("lod", lod as i64));
+        }
+        properties.push(string_property("primitive", match mesh.primitive {
+            GeometricPrimitive::Points => "points",
+            GeometricPrimitive::Lines => "lines",
+            GeometricPrimitive::LineStrip => "line_strip",
+            GeometricPrimitive::Triangles => "triangles",
+            GeometricPrimitive::TriangleStrip => "triangle_strip",
+            GeometricPrimitive::Quads => "quads"
+        }));
+        // Indices are grouped by primitive, the way exporters usually write them.
+        let group = match mesh.primitive {
+            GeometricPrimitive::Lines => 2,
+            GeometricPrimitive::Triangles => 3,
+            GeometricPrimitive::Quads => 4,
+            _ => 1
+        };
+        let mut children = vec![];
+        for array in &mesh.vertex_arrays {
+            let mut properties = vec![string_property("attrib", &if array.index == 0 {
+                array.attrib.clone()
+            } else {
+                format!("{}[{}]", array.attrib, array.index)
+            })];
+            if array.morph != 0 {
+                properties.push(integer_property("morph", array.morph as i64));
+            }
+            let data = match array.data {
+                VertexData::Half(ref v) => Data::Half(v.clone()),
+                VertexData::Float(ref v) => Data::Float(v.clone()),
+                VertexData::Double(ref v) => Data::Double(v.clone())
+            };
+            let size = Some(array.components).filter(|&n| n > 1);
+            children.push(derived("VertexArray", None, properties, vec![primitive(data, size)]));
+        }
+        for array in &mesh.index_arrays {
+            let mut properties = vec![];
+            if array.material != 0 {
+                properties.push(integer_property("material", array.material as i64));
+            }
+            if let Some(restart) = array.restart {
+                properties.push(unsigned_property("restart", restart));
+            }
+            if array.front_face == FrontFace::Clockwise {
+                properties.push(string_property("front", "cw"));
+            }
+            let size = Some(group).filter(|&n| n > 1 && array.data.len() % n == 0);
+            children.push(derived("IndexArray", None, properties,
+                vec![primitive(index_data(&array.data), size)]));
+        }
+        if let Some(ref skin) = mesh.skin {
+            children.push(self.write_skin(skin));
+        }
+        derived("Mesh", None, properties, children)
+    }
+
+    fn write_skin(&self, skin: &Skin) -> Structure {
+        let mut children = vec![];
+        if let Some(ref transform) = skin.transform {
+            children.push(transform_structure(transform));
+        }
+        let bones = skin.skeleton.bone_ref_array.iter().map(|b| self.reference(&**b)).collect();
+        let transforms = skin.skeleton.transforms.iter().flat_map(|t| t.0.iter().cloned());
+        children.push(derived("Skeleton", None, vec![], vec![
+            derived("BoneRefArray", None, vec![], vec![primitive(Data::Ref(bones), None)]),
+            derived("Transform", None, vec![], vec![
+                primitive(Data::Float(transforms.collect()), Some(16))
+            ])
+        ]));
+        children.push(derived("BoneCountArray", None, vec![],
+            vec![primitive(index_data(&skin.bone_count_array), None)]));
+        children.push(derived("BoneIndexArray", None, vec![],
+            vec![primitive(index_data(&skin.bone_index_array), None)]));
+        children.push(derived("BoneWeightArray", None, vec![],
+            vec![primitive(Data::Float(skin.bone_weight_array.clone()), None)]));
+        derived("Skin", None, vec![], children)
+    }
+
+    fn write_light_object(&self, object: &Arc<LightObject>) -> Structure {
+        let mut properties = vec![string_property("type", match object.light_type {
+            LightType::Infinite => "infinite",
+            LightType::Point => "point",
+            LightType::Spot => "spot"
+        })];
+        push_bool(&mut properties, "shadow", Some(object.casts_shadows).filter(|&v| !v));
+        let mut children = attributes(&object.colors, &object.params, &object.textures);
+        for atten in &object.attenuations {
+            let properties = vec![
+                string_property("kind", match atten.kind {
+                    AttenuationKind::Distance => "distance",
+                    AttenuationKind::Angle => "angle",
+                    AttenuationKind::CosAngle => "cos_angle"
+                }),
+                string_property("curve", match atten.curve {
+                    AttenuationCurve::Linear => "linear",
+                    AttenuationCurve::Cubic => "smooth",
+                    AttenuationCurve::Inverse => "inverse",
+                    AttenuationCurve::InverseSquare => "inverse_square"
+                })
+            ];
+            let params = attributes(&HashMap::new(), &atten.params, &HashMap::new());
+            children.push(derived("Atten", None, properties, params));
+        }
+        derived("LightObject", self.name(&**object), properties, children)
+    }
+
+    fn write_camera_object(&self, object: &Arc<CameraObject>) -> Structure {
+        let children = attributes(&object.colors, &object.params, &object.textures);
+        derived("CameraObject", self.name(&**object), vec![], children)
+    }
+
+    fn write_material(&self, material: &Arc<Material>) -> Structure {
+        let mut properties = vec![];
+        push_bool(&mut properties, "two_sided", Some(material.two_sided).filter(|&v| v));
+        let mut children: Vec<Structure> = material.name.iter().map(|n| name_structure(n))
+            .collect();
+        children.extend(attributes(&material.color, &material.param, &material.texture));
+        derived("Material", self.name(&**material), properties, children)
+    }
+}
+
+/// Returns whether an animation track targets a transformation or morph weight other than the
+/// given ones.
+fn has_foreign_target(transformations: &[Arc<Transformation>], morph_weights: &[Arc<MorphWeight>],
+    animations: &[Animation]) -> bool
+{
+    animations.iter().flat_map(|a| a.tracks.iter()).any(|track| match track.target {
+        TrackTarget::Transformation(ref t) => !transformations.iter().any(|x| Arc::ptr_eq(x, t)),
+        TrackTarget::MorphWeight(ref w) => !morph_weights.iter().any(|x| Arc::ptr_eq(x, w))
+    })
+}
+
+fn foreign_target(owner: &str) -> Error {
+    let message = format!("an animation track of {} targets a transformation or morph weight \
+        that does not belong to it", owner);
+    Error { kind: ErrorKind::Invalid(message), location: None }
+}
+
+/// The local names of the transformations and morph weights targeted by animation tracks.
+struct Targets {
+    transformations: Vec<Option<ddl::Name>>,
+    morph_weights: Vec<Option<ddl::Name>>,
+    names: HashMap<*const (), ddl::Name>
+}
+
+impl Targets {
+    fn new(transformations: &[Arc<Transformation>], morph_weights: &[Arc<MorphWeight>],
+        animations: &[Animation]) -> Targets
+    {
+        let mut names = HashMap::new();
+        for track in animations.iter().flat_map(|a| a.tracks.iter()) {
+            let (target, name) = match track.target {
+                TrackTarget::Transformation(ref t) => {
+                    match transformations.iter().position(|x| Arc::ptr_eq(x, t)) {
+                        Some(i) => (key(&**t), format!("xform{}", i + 1)),
+                        None => continue
+                    }
+                }
+                TrackTarget::MorphWeight(ref w) => {
+                    match morph_weights.iter().position(|x| Arc::ptr_eq(x, w)) {
+                        Some(i) => (key(&**w), format!("morph{}", i + 1)),
+                        None => continue
+                    }
+                }
+            };
+            names.insert(target, ddl::Name::Local(name));
+        }
+        Targets {
+            transformations: transformations.iter().map(|t| names.get(&key(&**t)).cloned())
+                .collect(),
+            morph_weights: morph_weights.iter().map(|w| names.get(&key(&**w)).cloned()).collect(),
+            names
+        }
+    }
+
+    fn write_transformations(&self, transformations: &[Arc<Transformation>]) -> Vec<Structure> {
+        transformations.iter().enumerate().map(|(i, t)| {
+            let mut s = transformation_structure(t);
+            if let Structure::Derived(ref mut d) = s {
+                d.name = self.transformations[i].clone();
+            }
+            s
+        }).collect()
+    }
+
+    fn write_animation(&self, animation: &Animation) -> Structure {
+        let mut properties = vec![];
+        if animation.clip != 0 {
+            properties.push(integer_property("clip", animation.clip as i64));
+        }
+        if let Some(begin) = animation.begin {
+            properties.push(float_property("begin", begin));
+        }
+        if let Some(end) = animation.end {
+            properties.push(float_property("end", end));
+        }
+        let mut tracks = vec![];
+        for track in &animation.tracks {
+            let target = match track.target {
+                TrackTarget::Transformation(ref t) => self.names.get(&key(&**t)),
+                TrackTarget::MorphWeight(ref w) => self.names.get(&key(&**w))
+            };
+            let target = match target {
+                Some(name) => Reference(vec![name.clone()]),
+                None => continue
+            };
+            tracks.push(derived("Track", None,
+                vec![Property { identifier: "target".to_string(), value: Literal::Ref(target) }],
+                vec![time_structure(&track.time), value_structure(&track.value, &track.time)]));
+        }
+        derived("Animation", None, properties, tracks)
+    }
+}
+
+fn time_structure(time: &Time) -> Structure {
+    match *time {
+        Time::Linear(ref v) => {
+            derived("Time", None, vec![], vec![key_structure(None, v.clone(), None)])
+        }
+        Time::Bezier(ref v) => derived("Time", None, vec![string_property("curve", "bezier")],
+            bezier_keys(v, None))
+    }
+}
+
+fn value_structure(value: &Value, time: &Time) -> Structure {
+    let key_count = match *time {
+        Time::Linear(ref v) => v.len(),
+        Time::Bezier(ref v) => v.len()
+    };
+    let components = |len: usize| {
+        len.checked_div(key_count).filter(|&n| n > 1)
+    };
+    let (curve, keys) = match *value {
+        Value::Constant(ref v) => {
+            ("constant", vec![key_structure(None, v.clone(), components(v.len()))])
+        }
+        Value::Linear(ref v) => {
+            ("linear", vec![key_structure(None, v.clone(), components(v.len()))])
+        }
+        Value::Bezier(ref v) => ("bezier", bezier_keys(v, components(v.len()))),
+        Value::Tcb(ref v) => {
+            let size = components(v.len());
+            let n = size.unwrap_or(1);
+            let per_key = |f: fn(&(f32, f32, f32, f32)) -> f32| {
+                v.iter().step_by(n).map(f).collect::<Vec<f32>>()
+            };
+            ("tcb", vec![
+                key_structure(None, v.iter().map(|k| k.0).collect(), size),
+                key_structure(Some("tension"), per_key(|k| k.1), None),
+                key_structure(Some("continuity"), per_key(|k| k.3), None),
+                key_structure(Some("bias"), per_key(|k| k.2), None)
+            ])
+        }
+    };
+    let properties = if curve == "linear" { vec![] } else { vec![string_property("curve", curve)] };
+    derived("Value", None, properties, keys)
+}
+
+fn bezier_keys(v: &[(f32, f32, f32)], size: Option<usize>) -> Vec<Structure> {
+    vec![
+        key_structure(None, v.iter().map(|k| k.0).collect(), size),
+        key_structure(Some("-control"), v.iter().map(|k| k.1).collect(), size),
+        key_structure(Some("+control"), v.iter().map(|k| k.2).collect(), size)
+    ]
+}
+
+fn key_structure(kind: Option<&str>, values: Vec<f32>, size: Option<usize>) -> Structure {
+    let properties = kind.iter().map(|kind| string_property("kind", kind)).collect();
+    derived("Key", None, properties, vec![primitive(Data::Float(values), size)])
+}
+
+/// Writes the Color, Param and Texture structures of a material, light or camera, sorted by
+/// attribute so that the output does not depend on the order of the maps.
+fn attributes(colors: &HashMap<String, Color>, params: &ParamMap,
+    textures: &HashMap<String, Texture>) -> Vec<Structure>
+{
+    let mut structures = vec![];
+    let mut colors: Vec<_> = colors.iter().collect();
+    colors.sort_by(|a, b| a.0.cmp(b.0));
+    for (attrib, color) in colors {
+        let (values, size) = match *color {
+            Color::Rgb(r, g, b) => (vec![r, g, b], 3),
+            Color::Rgba(r, g, b, a) => (vec![r, g, b, a], 4)
+        };
+        structures.push(derived("Color", None, vec![string_property("attrib", attrib)],
+            vec![primitive(Data::Float(values), Some(size))]));
+    }
+    let mut params: Vec<_> = params.iter().collect();
+    params.sort_by(|a, b| a.0.cmp(b.0));
+    for (attrib, &param) in params {
+        structures.push(derived("Param", None, vec![string_property("attrib", attrib)],
+            vec![primitive(Data::Float(vec![param]), None)]));
+    }
+    let mut textures: Vec<_> = textures.iter().collect();
+    textures.sort_by(|a, b| a.0.cmp(b.0));
+    for (attrib, texture) in textures {
+        let mut properties = vec![string_property("attrib", attrib)];
+        if texture.texcoord != 0 {
+            properties.push(integer_property("texcoord", texture.texcoord as i64));
+        }
+        let targets = Targets::new(&texture.transformations, &[], &texture.animation);
+        let mut children = vec![primitive(Data::String(vec![texture.file_name.clone()]), None)];
+        children.extend(targets.write_transformations(&texture.transformations));
+        children.extend(texture.animation.iter().map(|a| targets.write_animation(a)));
+        structures.push(derived("Texture", None, properties, children));
+    }
+    structures
+}
+
+fn transform_structure(transform: &Transform) -> Structure {
+    derived("Transform", None, vec![],
+        vec![primitive(Data::Float(transform.0.to_vec()), Some(16))])
+}
+
+fn transformation_structure(transformation: &Transformation) -> Structure {
+    let (identifier, (kind, values)) = match *transformation {
+        Transformation::Transform(ref t) => return transform_structure(t),
+        Transformation::Translation(ref t) => ("Translation", match *t {
+            Translation::X(x) => (Some("x"), vec![x]),
+            Translation::Y(y) => (Some("y"), vec![y]),
+            Translation::Z(z) => (Some("z"), vec![z]),
+            Translation::Xyz(x, y, z) => (None, vec![x, y, z])
+        }),
+        Transformation::Rotation(ref r) => ("Rotation", match *r {
+            Rotation::X(angle) => (Some("x"), vec![angle]),
+            Rotation::Y(angle) => (Some("y"), vec![angle]),
+            Rotation::Z(angle) => (Some("z"), vec![angle]),
+            Rotation::Axis(a, x, y, z) => (None, vec![a, x, y, z]),
+            Rotation::Quaternion(x, y, z, w) => (Some("quaternion"), vec![x, y, z, w])
+        }),
+        Transformation::Scale(ref s) => ("Scale", match *s {
+            Scale::X(x) => (Some("x"), vec![x]),
+            Scale::Y(y) => (Some("y"), vec![y]),
+            Scale::Z(z) => (Some("z"), vec![z]),
+            Scale::Xyz(x, y, z) => (None, vec![x, y, z])
+        })
+    };
+    let size = Some(values.len()).filter(|&n| n > 1);
+    let properties = kind.iter().map(|kind| string_property("kind", kind)).collect();
+    derived(identifier, None, properties, vec![primitive(Data::Float(values), size)])
+}
+
+fn index_data(data: &IndexData) -> Data {
+    match *data {
+        IndexData::U8(ref v) => Data::UnsignedInt8(v.clone()),
+        IndexData::U16(ref v) => Data::UnsignedInt16(v.clone()),
+        IndexData::U32(ref v) => Data::UnsignedInt32(v.clone()),
+        IndexData::U64(ref v) => Data::UnsignedInt64(v.clone())
+    }
+}
+
+fn name_structure(name: &str) -> Structure {
+    derived("Name", None, vec![], vec![primitive(Data::String(vec![name.to_string()]), None)])
+}
+
+fn reference_structure(identifier: &str, properties: Vec<Property>, r: Reference) -> Structure {
+    derived(identifier, None, properties, vec![primitive(Data::Ref(vec![r]), None)])
+}
+
+fn derived(identifier: &str, name: Option<ddl::Name>, properties: Vec<Property>,
+    children: Vec<Structure>) -> Structure
+{
+    Structure::Derived(DerivedStructure {
+        identifier: identifier.to_string(),
+        name,
+        properties,
+        children,
+        offset: 0
+    })
+}
+
+fn primitive(data: Data, array_size: Option<usize>) -> Structure {
+    Structure::Primitive(PrimitiveStructure { name: None, array_size, data, offset: 0 })
+}
+
+fn string_property(identifier: &str, value: &str) -> Property {
+    Property { identifier: identifier.to_string(), value: Literal::String(value.to_string()) }
+}
+
+fn integer_property(identifier: &str, value: i64) -> Property {
+    Property { identifier: identifier.to_string(), value: Literal::Integer(value) }
+}
+
+/// Returns an integer property, written as unsigned when it doesn't fit an `i64`.
+fn unsigned_property(identifier: &str, value: u64) -> Property {
+    let value = if value > i64::MAX as u64 {
+        Literal::Unsigned(value)
+    } else {
+        Literal::Integer(value as i64)
+    };
+    Property { identifier: identifier.to_string(), value }
+}
+
+fn float_property(identifier: &str, value: f32) -> Property {
+    // Going through the shortest representation of the `f32` keeps the property short.
+    let value = format!("{:?}", value).parse().unwrap_or(value as f64);
+    Property { identifier: identifier.to_string(), value: Literal::Float(value) }
+}
+
+/// Adds a boolean property if it has a value.
+fn push_bool(properties: &mut Vec<Property>, identifier: &str, value: Option<bool>) {
+    if let Some(value) = value {
+        properties.push(Property {
+            identifier: identifier.to_string(),
+            value: Literal::Bool(value)
+        });
+    }
+}
+
+/// The number of values up to which a primitive structure is written on a single line.
+const INLINE_VALUES: usize = 4;
+
+/// Returns the number of elements of a primitive structure, counting subarrays as one element.
+fn elements(p: &PrimitiveStructure) -> usize {
+    p.data.len() / p.array_size.unwrap_or(1).max(1)
+}
+
+/// Returns whether a derived structure is short enough to be written on a single line.
+fn is_inline(s: &DerivedStructure) -> bool {
+    s.children.iter().all(|c| match *c {
+        Structure::Primitive(ref p) => p.data.len() <= INLINE_VALUES,
+        Structure::Derived(_) => false
+    }) && s.children.len() <= 1
+}
+
+/// Formats structures according to the output options.
+struct Printer<'a> {
+    options: &'a Options,
+    out: String
+}
+
+impl<'a> Printer<'a> {
+    /// Starts a new line at the given depth. In minified mode, nothing is written.
+    fn line(&mut self, depth: usize) {
+        if self.options.minify {
+            return;
+        }
+        self.out.push('\n');
+        self.indent(depth);
+    }
+
+    fn indent(&mut self, depth: usize) {
+        if self.options.minify {
+            return;
+        }
+        for _ in 0..depth {
+            match self.options.indent {
+                Indent::Tabs => self.out.push('\t'),
+                Indent::Spaces(n) => self.out.extend((0..n).map(|_| ' '))
+            }
+        }
+    }
+
+    /// Writes a space, except in minified mode.
+    fn space(&mut self) {
+        if !self.options.minify {
+            self.out.push(' ');
+        }
+    }
+
+    /// Writes a separator between values, such as ", ", or "," in minified mode.
+    fn separator(&mut self, separator: &str) {
+        self.out.push_str(if self.options.minify { separator.trim() } else { separator });
+    }
+
+    fn structure(&mut self, s: &Structure, depth: usize) {
+        match *s {
+            Structure::Primitive(ref p) => self.primitive(p, depth),
+            Structure::Derived(ref d) => self.derived(d, depth)
+        }
+    }
+
+    fn derived(&mut self, d: &DerivedStructure, depth: usize) {
+        self.out.push_str(&d.identifier);
+        if let Some(ref name) = d.name {
+            self.space();
+            write!(self.out, "{}", name).unwrap();
+        }
+        if !d.properties.is_empty() {
+            self.space();
+            self.out.push('(');
+            for (i, p) in d.properties.iter().enumerate() {
+                if i > 0 {
+                    self.separator(", ");
+                }
+                self.out.push_str(&p.identifier);
+                self.separator(" = ");
+                self.literal(&p.value);
+            }
+            self.out.push(')');
+        }
+        if is_inline(d) {
+            self.space();
+            self.out.push('{');
+            if let Some(Structure::Primitive(p)) = d.children.first() {
+                self.primitive(p, depth);
+            }
+            self.out.push('}');
+            return;
+        }
+        self.line(depth);
+        self.out.push('{');
+        for child in &d.children {
+            self.line(depth + 1);
+            self.structure(child, depth + 1);
+        }
+        self.line(depth);
+        self.out.push('}');
+    }
+
+    fn primitive(&mut self, p: &PrimitiveStructure, depth: usize) {
+        self.out.push_str(p.data.data_type().identifier());
+        if let Some(size) = p.array_size {
+            write!(self.out, "[{}]", size).unwrap();
+        }
+        if let Some(ref name) = p.name {
+            self.space();
+            write!(self.out, "{}", name).unwrap();
+        }
+        let count = elements(p);
+        if p.data.len() <= INLINE_VALUES {
+            self.space();
+            self.out.push('{');
+            for i in 0..count {
+                if i > 0 {
+                    self.separator(", ");
+                }
+                self.element(p, i);
+            }
+            self.out.push('}');
+            return;
+        }
+        if self.options.count_comments && !self.options.minify {
+            let gap = if self.options.indent == Indent::Tabs { "\t\t" } else { "  " };
+            write!(self.out, "{}// {}", gap, count).unwrap();
+        }
+        self.line(depth);
+        self.out.push('{');
+        let per_line = match self.options.elements_per_line {
+            0 => count,
+            n => n
+        };
+        for i in 0..count {
+            if i % per_line == 0 {
+                self.line(depth + 1);
+            }
+            self.element(p, i);
+            if i + 1 < count {
+                self.out.push(',');
+                if (i + 1) % per_line != 0 {
+                    self.space();
+                }
+            }
+        }
+        self.line(depth);
+        self.out.push('}');
+    }
+
+    /// Writes an element of a primitive structure: a value, or a subarray in braces.
+    fn element(&mut self, p: &PrimitiveStructure, i: usize) {
+        let size = p.array_size.unwrap_or(1).max(1);
+        if p.array_size.is_some() {
+            self.out.push('{');
+        }
+        for j in i * size..(i + 1) * size {
+            if j > i * size {
+                self.separator(", ");
+            }
+            self.value(&p.data, j);
+        }
+        if p.array_size.is_some() {
+            self.out.push('}');
+        }
+    }
+
+    /// Writes the value at index `i` of some primitive data.
+    fn value(&mut self, data: &Data, i: usize) {
+        let out = &mut self.out;
+        match *data {
+            Data::Bool(ref v) => out.push_str(if v[i] { "true" } else { "false" }),
+            Data::Int8(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::Int16(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::Int32(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::Int64(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::UnsignedInt8(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::UnsignedInt16(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::UnsignedInt32(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::UnsignedInt64(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::Half(ref v) => write_half(out, v[i], self.options.precision),
+            Data::Float(ref v) => write_float(out, v[i], self.options.precision),
+            Data::Double(ref v) => write_double(out, v[i], self.options.precision),
+            Data::String(ref v) => write_string(out, &v[i]),
+            Data::Ref(ref v) => write!(out, "{}", v[i]).unwrap(),
+            Data::Type(ref v) => out.push_str(v[i].identifier())
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        let out = &mut self.out;
+        match *literal {
+            Literal::Bool(v) => out.push_str(if v { "true" } else { "false" }),
+            Literal::Integer(v) => write!(out, "{}", v).unwrap(),
+            Literal::Unsigned(v) => write!(out, "{}", v).unwrap(),
+            Literal::Float(v) => write_double(out, v, self.options.precision),
+            Literal::String(ref v) => write_string(out, v),
+            Literal::Ref(ref v) => write!(out, "{}", v).unwrap(),
+            Literal::Type(v) => out.push_str(v.identifier())
+        }
+    }
+}
+
+// OpenDDL has no literals for infinities and NaNs, which are written as bit patterns instead.
+
+fn write_half(out: &mut String, x: f32, precision: Precision) {
+    if x.is_finite() {
+        write_finite(out, x as f64, &format!("{:?}", x), precision);
+    } else {
+        let sign = if x.is_sign_negative() { 0x8000 } else { 0 };
+        let bits = if x.is_nan() { 0x7e00 } else { 0x7c00 };
+        write!(out, "0x{:04X}", sign | bits).unwrap();
+    }
+}
+
+fn write_float(out: &mut String, x: f32, precision: Precision) {
+    if x.is_finite() {
+        write_finite(out, x as f64, &format!("{:?}", x), precision);
+    } else {
+        write!(out, "0x{:08X}", x.to_bits()).unwrap();
+    }
+}
+
+fn write_double(out: &mut String, x: f64, precision: Precision) {
+    if x.is_finite() {
+        write_finite(out, x, &format!("{:?}", x), precision);
+    } else {
+        write!(out, "0x{:016X}", x.to_bits()).unwrap();
+    }
+}
+
+/// Writes a finite value, given its shortest representation in its own type.
+fn write_finite(out: &mut String, x: f64, shortest: &str, precision: Precision) {
+    match precision {
+        Precision::Shortest => out.push_str(shortest),
+        Precision::Fixed(digits) => write!(out, "{:.*}", digits, x).unwrap()
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                write!(out, "\\x{:02X}", c as u32).unwrap()
+            }
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,9 @@
+//! Helpers shared by the integration tests.
+
+/// Asserts that two slices have the same length and approximately equal elements.
+pub fn assert_close(a: &[f32], b: &[f32]) {
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b) {
+        assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+    }
+}
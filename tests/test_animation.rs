@@ -0,0 +1,165 @@
+extern crate opengex;
+
+mod common;
+
+use std::sync::Arc;
+use opengex::animation::Pose;
+use opengex::structure::*;
+use common::assert_close;
+
+fn track(time: Time, value: Value) -> Track {
+    let target = Arc::new(Transformation::Translation(Translation::X(0.0)));
+    Track { target: TrackTarget::Transformation(target), time, value }
+}
+
+#[test]
+fn test_animation_curves() {
+    let linear = track(Time::Linear(vec![0.0, 1.0, 3.0]), Value::Linear(vec![0.0, 2.0, 6.0]));
+    assert_close(&linear.sample(0.5), &[1.0]);
+    assert_close(&linear.sample(2.0), &[4.0]);
+    assert_close(&linear.sample(-1.0), &[0.0]);
+    assert_close(&linear.sample(5.0), &[6.0]);
+
+    let constant = track(Time::Linear(vec![0.0, 1.0]), Value::Constant(vec![3.0, 5.0]));
+    assert_close(&constant.sample(0.99), &[3.0]);
+    assert_close(&constant.sample(1.0), &[5.0]);
+
+    // A Bezier time curve with its controls at thirds is linear, so the time at which the value
+    // curve reaches its middle is found at the middle of the interval.
+    let bezier = track(Time::Bezier(vec![(0.0, 0.0, 1.0), (3.0, 2.0, 3.0)]),
+        Value::Bezier(vec![(0.0, 0.0, 0.0), (1.0, 1.0, 1.0)]));
+    assert_close(&bezier.sample(1.5), &[0.5]);
+    // With both controls at the start, time passes slowly at first, so the middle of the curve is
+    // reached early.
+    let bezier = track(Time::Bezier(vec![(0.0, 0.0, 0.0), (3.0, 0.0, 3.0)]),
+        Value::Linear(vec![0.0, 1.0]));
+    assert_close(&bezier.sample(0.375), &[0.5]);
+
+    // Without tension, continuity and bias, a TCB spline through evenly spaced points is a line.
+    let tcb = track(Time::Linear(vec![0.0, 1.0, 2.0, 3.0]),
+        Value::Tcb(vec![(0.0, 0.0, 0.0, 0.0), (1.0, 0.0, 0.0, 0.0), (2.0, 0.0, 0.0, 0.0),
+            (3.0, 0.0, 0.0, 0.0)]));
+    assert_close(&tcb.sample(1.25), &[1.25]);
+    // Full tension gives zero tangents.
+    let tcb = track(Time::Linear(vec![0.0, 1.0]),
+        Value::Tcb(vec![(0.0, 1.0, 0.0, 0.0), (1.0, 1.0, 0.0, 0.0)]));
+    assert_close(&tcb.sample(0.25), &[0.15625]);
+
+    let xyz = track(Time::Linear(vec![0.0, 2.0]),
+        Value::Linear(vec![0.0, 0.0, 0.0, 2.0, 4.0, 6.0]));
+    assert_eq!(xyz.components(), 3);
+    assert_close(&xyz.sample(1.0), &[1.0, 2.0, 3.0]);
+}
+
+#[test]
+fn test_animation_sample() {
+    let source = r#"
+        GeometryNode
+        {
+            ObjectRef {ref {$geometry}}
+            Translation %xlat {float[3] {{0, 0, 0}}}
+            Rotation %rot (kind = "quaternion") {float[4] {{0, 0, 0, 1}}}
+            MorphWeight %weight (index = 1) {float {0}}
+            Animation (begin = 1, end = 2)
+            {
+                Track (target = %xlat)
+                {
+                    Time {Key {float {0, 4}}}
+                    Value {Key {float[3] {{0, 0, 0}, {4, 8, 0}}}}
+                }
+                Track (target = %rot)
+                {
+                    Time {Key {float {0, 4}}}
+                    Value {Key {float[4] {{0, 0, 0, 1}, {0, 0, 1, 0}}}}
+                }
+                Track (target = %weight)
+                {
+                    Time {Key {float {0, 4}}}
+                    Value {Key {float {0, 1}}}
+                }
+            }
+        }
+        GeometryObject $geometry {}
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let node = match scene.nodes[0] {
+        Nodes::GeometryNode(ref node) => node,
+        _ => panic!("expected a geometry node")
+    };
+    let pose = node.animations[0].sample(1.5);
+    assert_eq!(*pose.transformation(&node.transformations[0]),
+        Transformation::Translation(Translation::Xyz(1.5, 3.0, 0.0)));
+    assert_eq!(pose.morph_weight(&node.morph_weights[0]), 0.375);
+
+    // Times outside of the animation are clamped to its end.
+    let pose = node.animations[0].sample(10.0);
+    assert_eq!(pose.morph_weight(&node.morph_weights[0]), 0.5);
+    match *pose.transformation(&node.transformations[1]) {
+        Transformation::Rotation(Rotation::Quaternion(x, y, z, w)) => {
+            let s = 0.5f32.sqrt();
+            assert_close(&[x, y, z, w], &[0.0, 0.0, s, s]);
+        }
+        ref t => panic!("expected a quaternion, found {:?}", t)
+    }
+
+    let matrices = scene.posed_world_matrices(&pose);
+    assert_close(&matrices[0].1[12..15], &[2.0, 4.0, 0.0]);
+    assert_eq!(Pose::default().transformation(&node.transformations[0]),
+        &*node.transformations[0]);
+}
+
+#[test]
+fn test_animation_clips() {
+    let source = r#"
+        Clip (index = 1)
+        {
+            Name {string {"walk"}}
+            Param (attrib = "frame_rate") {float {30}}
+        }
+        Clip (index = 2) {Name {string {"run"}}}
+        Node
+        {
+            Translation %xlat (kind = "x") {float {0}}
+            Animation (clip = 1, end = 5)
+            {
+                Track (target = %xlat) {Time {Key {float {1, 2}}} Value {Key {float {0, 1}}}}
+            }
+            Node
+            {
+                Translation %xlat (kind = "x") {float {0}}
+                Animation (clip = 1)
+                {
+                    Track (target = %xlat) {Time {Key {float {0, 3}}} Value {Key {float {0, 3}}}}
+                }
+                Animation (clip = 3)
+                {
+                    Track (target = %xlat) {Time {Key {float {0, 1}}} Value {Key {float {0, 2}}}}
+                }
+            }
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    assert_eq!(scene.clip_structures[1].name, Some("walk".to_string()));
+    assert_eq!(scene.clip_structures[1].param["frame_rate"], 30.0);
+    assert_eq!(scene.to_string_with(&Default::default()).unwrap().parse::<Scene>().unwrap(), scene);
+
+    let clips = scene.clips();
+    assert_eq!(clips.iter().map(|c| c.index).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(clips[0].name(), Some("walk"));
+    assert_eq!(clips[0].animations.len(), 2);
+    assert_eq!((clips[0].begin, clips[0].end), (0.0, 5.0));
+    assert_eq!(clips[1].name(), Some("run"));
+    assert!(clips[1].animations.is_empty());
+    assert_eq!(clips[2].name(), None);
+    assert_eq!((clips[2].begin, clips[2].end), (0.0, 1.0));
+
+    let pose = clips[0].sample(1.5);
+    let child = &scene.nodes[0].children()[0];
+    assert_eq!(*pose.transformation(&scene.nodes[0].transformations()[0]),
+        Transformation::Translation(Translation::X(0.5)));
+    assert_eq!(*pose.transformation(&child.transformations()[0]),
+        Transformation::Translation(Translation::X(1.5)));
+
+    let error = "Clip (index = 1) {} Clip (index = 1) {}".parse::<Scene>().unwrap_err();
+    assert!(error.to_string().contains("duplicate Clip index 1"));
+}
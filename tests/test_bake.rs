@@ -0,0 +1,159 @@
+extern crate opengex;
+
+use opengex::structure::*;
+
+const SOURCE: &str = r#"
+    Node
+    {
+        Translation %xlat (kind = "x") {float {0}}
+        Animation
+        {
+            Track (target = %xlat)
+            {
+                Time (curve = "bezier")
+                {
+                    Key {float {0, 1}}
+                    Key (kind = "-control") {float {0, 0.5}}
+                    Key (kind = "+control") {float {0.5, 1}}
+                }
+                Value (curve = "bezier")
+                {
+                    Key {float {0, 1}}
+                    Key (kind = "-control") {float {0, 1}}
+                    Key (kind = "+control") {float {0, 1}}
+                }
+            }
+        }
+        Node
+        {
+            Translation %xlat {float[3] {{0, 0, 0}}}
+            Animation
+            {
+                Track (target = %xlat)
+                {
+                    Time {Key {float {0, 0.5}}}
+                    Value {Key {float[3] {{0, 0, 0}, {1, 2, 3}}}}
+                }
+            }
+            Animation (clip = 1)
+            {
+                Track (target = %xlat)
+                {
+                    Time {Key {float {0, 3}}}
+                    Value {Key {float[3] {{0, 0, 0}, {1, 2, 3}}}}
+                }
+            }
+        }
+    }
+"#;
+
+#[test]
+fn test_bake_animation() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let animation = &scene.nodes[0].animations()[0];
+
+    let baked = animation.bake(4.0, None);
+    match baked.tracks[0].time {
+        Time::Linear(ref t) => assert_eq!(*t, vec![0.0, 0.25, 0.5, 0.75, 1.0]),
+        _ => panic!("expected linear times")
+    }
+    match baked.tracks[0].value {
+        Value::Linear(ref v) => {
+            assert_eq!(v.len(), 5);
+            for (i, &v) in v.iter().enumerate() {
+                assert_eq!(v, animation.tracks[0].sample(i as f32 / 4.0)[0]);
+            }
+        }
+        _ => panic!("expected linear values")
+    }
+    assert_eq!(baked.tracks[0].target, animation.tracks[0].target);
+
+    // A rate that does not divide the time range still ends on the last key.
+    match animation.bake(3.0, None).tracks[0].time {
+        Time::Linear(ref t) => assert_eq!(t.len(), 4),
+        _ => panic!("expected linear times")
+    }
+
+    // The keys of a straight line are all dropped but the first and last.
+    let line = &scene.nodes[0].children()[0].animations()[0];
+    let baked = line.bake(10.0, Some(1e-5));
+    assert_eq!(baked.tracks[0].time, Time::Linear(vec![0.0, 0.5]));
+    assert_eq!(baked.tracks[0].value, Value::Linear(vec![0.0, 0.0, 0.0, 1.0, 2.0, 3.0]));
+}
+
+#[test]
+fn test_bake_rate() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let animation = &scene.nodes[0].animations()[0];
+
+    // A rate too high for the time range spreads the keys over it instead of exhausting memory.
+    let baked = animation.bake(1e12, None);
+    match baked.tracks[0].time {
+        Time::Linear(ref times) => {
+            assert_eq!(times.len(), 1 << 20);
+            assert_eq!((times[0], times[times.len() - 1]), (0.0, 1.0));
+        }
+        _ => panic!("expected linear times")
+    }
+}
+
+#[test]
+fn test_bake_tolerance() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let animation = &scene.nodes[0].animations()[0];
+
+    // Every dropped key stays within the tolerance of the line between the keys around it.
+    let (all, baked) = (animation.bake(100.0, None), animation.bake(100.0, Some(0.01)));
+    let (times, values) = match (&all.tracks[0].time, &all.tracks[0].value) {
+        (Time::Linear(t), Value::Linear(v)) => (t.clone(), v.clone()),
+        _ => panic!("expected linear curves")
+    };
+    let keys = match baked.tracks[0].time {
+        Time::Linear(ref t) => t.clone(),
+        _ => panic!("expected linear times")
+    };
+    assert!(keys.len() > 2 && keys.len() < times.len());
+    for (&t, &v) in times.iter().zip(&values) {
+        assert!((baked.tracks[0].sample(t)[0] - v).abs() <= 0.01 + 1e-6);
+    }
+
+    // A straight line sampled at the largest number of keys is still simplified in one pass.
+    let line = &scene.nodes[0].children()[0].animations()[0];
+    let baked = line.bake(1e12, Some(1e-5));
+    assert_eq!(baked.tracks[0].time, Time::Linear(vec![0.0, 0.5]));
+}
+
+#[test]
+#[should_panic(expected = "the rate must be finite and positive")]
+fn test_bake_zero_rate() {
+    let mut scene: Scene = SOURCE.parse().unwrap();
+    scene.bake_clip(0, 0.0, None);
+}
+
+#[test]
+#[should_panic(expected = "the rate must be finite and positive")]
+fn test_bake_infinite_rate() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    scene.nodes[0].animations()[0].bake(f32::INFINITY, None);
+}
+
+#[test]
+fn test_bake_clip() {
+    let mut scene: Scene = SOURCE.parse().unwrap();
+    scene.bake_clip(0, 2.0, None);
+
+    let animation = &scene.nodes[0].animations()[0];
+    assert_eq!(animation.tracks[0].time, Time::Linear(vec![0.0, 0.5, 1.0]));
+    // The shorter animation of the clip is sampled over the time range of the whole clip.
+    let child = &scene.nodes[0].children()[0];
+    assert_eq!(child.animations()[0].tracks[0].time, Time::Linear(vec![0.0, 0.5, 1.0]));
+    match child.animations()[0].tracks[0].target {
+        TrackTarget::Transformation(ref t) => assert!(std::sync::Arc::ptr_eq(t,
+            &child.transformations()[0])),
+        _ => panic!("expected a transformation target")
+    }
+    // Other clips are left as they are.
+    assert_eq!(child.animations()[1].tracks[0].time, Time::Linear(vec![0.0, 3.0]));
+
+    assert_eq!(scene.to_string_with(&Default::default()).unwrap().parse::<Scene>().unwrap(), scene);
+}
@@ -0,0 +1,184 @@
+extern crate opengex;
+
+use opengex::ddl::*;
+
+#[test]
+fn test_ddl_cube() {
+    let structures = parse(include_str!("assets/cube.ogex")).unwrap();
+    assert_eq!(structures.len(), 11);
+
+    let geometry = match structures[7] {
+        Structure::Derived(ref s) => s,
+        _ => panic!("expected a derived structure")
+    };
+    assert_eq!(geometry.identifier, "GeometryObject");
+    assert_eq!(geometry.name, Some(Name::Global("geometry1".to_string())));
+
+    let mesh = match geometry.children[0] {
+        Structure::Derived(ref s) => s,
+        _ => panic!("expected a derived structure")
+    };
+    assert_eq!(mesh.property("primitive"), Some(&Literal::String("triangles".to_string())));
+    let indices = match mesh.children[2] {
+        Structure::Derived(ref s) => match s.children[0] {
+            Structure::Primitive(ref p) => p,
+            _ => panic!("expected a primitive structure")
+        },
+        _ => panic!("expected a derived structure")
+    };
+    assert_eq!(indices.array_size, Some(3));
+    assert_eq!(indices.data.data_type(), DataType::UnsignedInt32);
+    assert_eq!(indices.data.len(), 36);
+}
+
+#[test]
+fn test_ddl_literals() {
+    let source = r#"
+        /* A block comment. */
+        Test $a (x = 0x10, y = -1.5e2, z = "a" "b", r = $a%b, t = float, flag,
+            u = 0xFFFFFFFFFFFFFFFF, d = 18446744073709551615, m = -0x8000000000000000) {
+            int8 {-128, 0xFF, 'A'}  // Line comment.
+            u16 %b {0b1010_1010}
+            float {1, 0x3F800000, .5}
+            string {"tab\té"}
+            ref {null, $a%b}
+            bool {true, false}
+        }
+    "#;
+    let structures = parse(source).unwrap();
+    let test = match structures[0] {
+        Structure::Derived(ref s) => s,
+        _ => panic!("expected a derived structure")
+    };
+    assert_eq!(test.property("x"), Some(&Literal::Integer(16)));
+    assert_eq!(test.property("y"), Some(&Literal::Float(-150.0)));
+    assert_eq!(test.property("z"), Some(&Literal::String("ab".to_string())));
+    assert_eq!(test.property("r"), Some(&Literal::Ref(Reference(vec![
+        Name::Global("a".to_string()),
+        Name::Local("b".to_string())
+    ]))));
+    assert_eq!(test.property("t"), Some(&Literal::Type(DataType::Float)));
+    assert_eq!(test.property("flag"), Some(&Literal::Bool(true)));
+    assert_eq!(test.property("u"), Some(&Literal::Unsigned(u64::MAX)));
+    assert_eq!(test.property("d"), Some(&Literal::Unsigned(u64::MAX)));
+    assert_eq!(test.property("m"), Some(&Literal::Integer(i64::MIN)));
+
+    let data: Vec<&Data> = test.children.iter().map(|s| match *s {
+        Structure::Primitive(ref p) => &p.data,
+        _ => panic!("expected a primitive structure")
+    }).collect();
+    assert_eq!(*data[0], Data::Int8(vec![-128, -1, 65]));
+    assert_eq!(*data[1], Data::UnsignedInt16(vec![0xaa]));
+    assert_eq!(*data[2], Data::Float(vec![1.0, 1.0, 0.5]));
+    assert_eq!(*data[3], Data::String(vec!["tab\t\u{e9}".to_string()]));
+    assert_eq!(*data[4], Data::Ref(vec![
+        Reference(vec![]),
+        Reference(vec![Name::Global("a".to_string()), Name::Local("b".to_string())])
+    ]));
+    assert_eq!(*data[5], Data::Bool(vec![true, false]));
+    assert_eq!(test.children[1].name(), Some(&Name::Local("b".to_string())));
+}
+
+#[test]
+fn test_ddl_errors() {
+    let err = parse("float[3] {{1, 2}}").unwrap_err();
+    assert_eq!(err.expected, Expected::Subarray(3));
+    assert_eq!(err.offset, 15);
+
+    let err = parse("unsigned_int8 {256}").unwrap_err();
+    assert_eq!(err.expected, Expected::Value(DataType::UnsignedInt8));
+    assert_eq!(err.offset, 15);
+
+    let err = parse("Node {").unwrap_err();
+    assert_eq!(err.expected, Expected::Char('}'));
+    assert_eq!(err.offset, 6);
+
+    let err = parse("Node {} }").unwrap_err();
+    assert_eq!(err.expected, Expected::Structure);
+    assert_eq!(err.offset, 8);
+}
+
+/// Returns the data of the only primitive structure of the source text.
+fn data(source: &str) -> Data {
+    match parse(source).unwrap().pop() {
+        Some(Structure::Primitive(p)) => p.data,
+        _ => panic!("expected a primitive structure")
+    }
+}
+
+#[test]
+fn test_ddl_numbers() {
+    assert_eq!(data("u32 {0x1F, 0XF_F, 0o17, 0O7_7, 0b101, 0B1_0, 1_000}"),
+        Data::UnsignedInt32(vec![31, 255, 15, 63, 5, 2, 1000]));
+    assert_eq!(data("i16 {-0x10, +0o10, -0b1, 'AB', -'a'}"),
+        Data::Int16(vec![-16, 8, -1, 0x4142, -97]));
+    assert_eq!(data("u64 {0xFFFFFFFFFFFFFFFF, '\\x01\\x02'}"),
+        Data::UnsignedInt64(vec![u64::MAX, 0x0102]));
+    assert_eq!(data("double {0x3FF0000000000000, 1e3, -2.5E-1, 7.}"),
+        Data::Double(vec![1.0, 1000.0, -0.25, 7.0]));
+    assert_eq!(data("half {0x3C00, 0b0_01111_0000000000}"), Data::Half(vec![1.0, 1.0]));
+
+    // Digits outside of the radix, a leading separator and overflows are rejected.
+    for source in &["u8 {0o8}", "u8 {0b2}", "u8 {0x_1}", "u8 {0x}",
+        "u64 {0x10000000000000000}", "i8 {128}", "u8 {-1}", "float {1e}"]
+    {
+        assert!(parse(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn test_ddl_escapes() {
+    assert_eq!(data(r#"string {"\"\'\?\\\a\b\f\n\r\t\v", "\x41é\U01F600", "a" "b"}"#),
+        Data::String(vec![
+            "\"'?\\\x07\x08\x0c\n\r\t\x0b".to_string(),
+            "A\u{e9}\u{1F600}".to_string(),
+            "ab".to_string()
+        ]));
+
+    for &(source, offset) in &[(r#"string {"\q"}"#, 9), (r#"string {"\x4"}"#, 9),
+        (r#"string {"\U110000"}"#, 9), (r#"string {"open}"#, 14)]
+    {
+        let err = parse(source).unwrap_err();
+        assert_eq!(err.offset, offset, "{}", source);
+    }
+    assert_eq!(parse(r#"string {"\q"}"#).unwrap_err().expected, Expected::Escape);
+}
+
+#[test]
+fn test_ddl_comments() {
+    let source = "// Leading comment.\nNode /* between */ $a /* multi\nline */ {// inside\n}";
+    let structures = parse(source).unwrap();
+    assert_eq!(structures.len(), 1);
+    assert_eq!(structures[0].name(), Some(&Name::Global("a".to_string())));
+    assert_eq!(structures[0].offset(), 20);
+    assert_eq!(data("float {1, /* two */ 2 // three\n, 3}"), Data::Float(vec![1.0, 2.0, 3.0]));
+
+    let err = parse("Node {} /* unterminated").unwrap_err();
+    assert_eq!(err.expected, Expected::CommentEnd);
+    assert_eq!(err.offset, 23);
+}
+
+#[test]
+fn test_ddl_error_offsets() {
+    // The expected size of a subarray is reported even when it does not fit an error code.
+    let err = parse("float[4294967295] {{1}}").unwrap_err();
+    assert_eq!(err.expected, Expected::Subarray(4294967295));
+    assert_eq!(err.offset, 21);
+
+    let cases: &[(&str, Expected, usize)] = &[
+        ("float[0] {}", Expected::ArraySize, 6),
+        ("float[4294967296] {}", Expected::ArraySize, 6),
+        ("float[2 {}", Expected::Char(']'), 8),
+        ("Node (x = ) {}", Expected::PropertyValue, 10),
+        ("Node (x = 1 {}", Expected::Char(')'), 12),
+        ("Node $ {}", Expected::Identifier, 6),
+        ("bool {true, maybe}", Expected::Value(DataType::Bool), 12),
+        ("float {1 2}", Expected::Char('}'), 9),
+        ("ref {$a%}", Expected::Identifier, 8),
+        ("\n\n  Node }", Expected::Char('{'), 9)
+    ];
+    for &(source, expected, offset) in cases {
+        let err = parse(source).unwrap_err();
+        assert_eq!((err.expected, err.offset), (expected, offset), "{}", source);
+    }
+}
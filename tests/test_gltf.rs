@@ -0,0 +1,302 @@
+extern crate opengex;
+
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use opengex::gltf::Options;
+use opengex::structure::*;
+
+const SOURCE: &str = r#"
+    Metric (key = "up") {string {"z"}}
+    BoneNode $root
+    {
+        Name {string {"Root"}}
+        BoneNode $arm
+        {
+            Translation %xlat {float[3] {{0, 0, 1}}}
+            Animation
+            {
+                Track (target = %xlat)
+                {
+                    Time {Key {float {0, 1}}}
+                    Value {Key {float[3] {{0, 0, 1}, {1, 0, 1}}}}
+                }
+            }
+        }
+    }
+    GeometryNode
+    {
+        Name {string {"Body"}}
+        ObjectRef {ref {$body}}
+        MaterialRef (index = 0) {ref {$skin}}
+        MorphWeight (index = 0) {float {0.5}}
+        MorphWeight %smile (index = 1) {float {0.5}}
+        Animation
+        {
+            Track (target = %smile)
+            {
+                Time (curve = "bezier")
+                {
+                    Key {float {0, 1}}
+                    Key (kind = "-control") {float {0, 0.5}}
+                    Key (kind = "+control") {float {0.5, 1}}
+                }
+                Value (curve = "bezier")
+                {
+                    Key {float {0, 1}}
+                    Key (kind = "-control") {float {0, 1}}
+                    Key (kind = "+control") {float {0, 1}}
+                }
+            }
+        }
+    }
+    CameraNode {ObjectRef {ref {$camera}} Translation {float[3] {{0, -5, 0}}}}
+    LightNode {ObjectRef {ref {$spot}}}
+    GeometryObject $body
+    {
+        Mesh
+        {
+            VertexArray (attrib = "position") {float[3] {{0, 0, 0}, {1, 0, 0}, {0, 0, 2}}}
+            VertexArray (attrib = "position", morph = 1)
+            {
+                float[3] {{0, 0, 0}, {2, 0, 0}, {0, 0, 2}}
+            }
+            VertexArray (attrib = "normal") {float[3] {{0, -2, 0}, {0, -1, 0}, {0, -1, 0}}}
+            VertexArray (attrib = "texcoord") {float[2] {{0, 0}, {1, 0}, {0, 1}}}
+            IndexArray {unsigned_int16 {0, 1, 2}}
+            Skin
+            {
+                Skeleton
+                {
+                    BoneRefArray {ref {$root, $arm}}
+                    Transform
+                    {
+                        float[16]
+                        {
+                            {1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1},
+                            {1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1}
+                        }
+                    }
+                }
+                BoneCountArray {unsigned_int16 {1, 1, 2}}
+                BoneIndexArray {unsigned_int16 {0, 0, 0, 1}}
+                BoneWeightArray {float {1, 1, 0.5, 0.5}}
+            }
+        }
+    }
+    Material $skin
+    {
+        Name {string {"Skin \"pale\""}}
+        Color (attrib = "diffuse") {float[3] {{1, 0.5, 0.25}}}
+        Color (attrib = "opacity") {float[3] {{0.5, 0.5, 0.5}}}
+        Param (attrib = "specular_power") {float {6}}
+        Texture (attrib = "diffuse")
+        {
+            string {"skin tone.png"}
+            Scale {float[3] {{2, 2, 1}}}
+        }
+    }
+    CameraObject $camera
+    {
+        Param (attrib = "fov") {float {1.5}}
+        Param (attrib = "near") {float {0.5}}
+        Param (attrib = "far") {float {100}}
+    }
+    LightObject $spot (type = "spot")
+    {
+        Color (attrib = "light") {float[3] {{1, 1, 0.5}}}
+        Param (attrib = "intensity") {float {20}}
+        Atten (kind = "angle")
+        {
+            Param (attrib = "begin") {float {0.25}}
+            Param (attrib = "end") {float {0.5}}
+        }
+        Atten (kind = "distance") {Param (attrib = "end") {float {10}}}
+    }
+"#;
+
+/// Returns the part of a JSON document from the first occurrence of `key` to the end of its
+/// value.
+fn value<'a>(json: &'a str, key: &str) -> &'a str {
+    let start = json.find(&format!("\"{}\":", key)).unwrap();
+    let mut depth = 0;
+    for (i, c) in json[start..].char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' if depth == 0 => return &json[start..start + i],
+            ']' | '}' => depth -= 1,
+            ',' if depth == 0 => return &json[start..start + i],
+            _ => {}
+        }
+    }
+    &json[start..]
+}
+
+#[test]
+fn test_gltf_document() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let (json, buffer) = scene.to_gltf("body data.bin", &Options::default());
+    assert!(json.starts_with("{\"asset\":{\"version\":\"2.0\""));
+    assert_eq!(value(&json, "extensionsUsed"),
+        "\"extensionsUsed\":[\"KHR_texture_transform\",\"KHR_lights_punctual\"]");
+    assert_eq!(value(&json, "buffers"),
+        format!("\"buffers\":[{{\"byteLength\":{},\"uri\":\"body%20data.bin\"}}]", buffer.len()));
+    assert_eq!(buffer.len() % 4, 0);
+
+    // The scene is turned so that y is up, and every node keeps its place in the hierarchy.
+    assert_eq!(value(&json, "scenes"), "\"scenes\":[{\"nodes\":[0,2,3,4]}]");
+    assert!(json.contains("{\"name\":\"Root\",\"children\":[1]}"));
+    assert!(json.contains("{\"translation\":[0,1,0]}"));
+    assert!(json.contains("{\"translation\":[0,0,5],\"rotation\":[-0.7071068,0,0,0.7071068],\
+        \"camera\":0}"));
+
+    // The skin binds the vertices to the bones, and the morph target is a displacement.
+    assert!(json.contains("\"mesh\":0,\"skin\":0,\"weights\":[0.5]"));
+    assert!(json.contains("\"attributes\":{\"POSITION\":1,\"NORMAL\":2,\"TEXCOORD_0\":3,\
+        \"JOINTS_0\":5,\"WEIGHTS_0\":6}"));
+    assert!(json.contains("\"targets\":[{\"POSITION\":7}]"));
+    assert!(json.contains("\"joints\":[0,1]"));
+
+    assert_eq!(value(&json, "materials"), "\"materials\":[{\"name\":\"Skin \\\"pale\\\"\",\
+        \"pbrMetallicRoughness\":{\"baseColorFactor\":[1,0.5,0.25,0.5],\"baseColorTexture\":\
+        {\"index\":0,\"extensions\":{\"KHR_texture_transform\":{\"offset\":[0,-1],\
+        \"scale\":[2,2]}}},\"metallicFactor\":0,\"roughnessFactor\":0.5},\
+        \"alphaMode\":\"BLEND\"}]");
+    assert_eq!(value(&json, "images"), "\"images\":[{\"uri\":\"skin%20tone.png\"}]");
+
+    let yfov = 2.0 * (0.75f32.tan() * 9.0 / 16.0).atan();
+    assert_eq!(value(&json, "cameras"), format!("\"cameras\":[{{\"type\":\"perspective\",\
+        \"perspective\":{{\"aspectRatio\":{},\"yfov\":{},\"znear\":0.5,\"zfar\":100}}}}]",
+        16.0f32 / 9.0, yfov));
+    assert!(json.contains("\"extensions\":{\"KHR_lights_punctual\":{\"light\":0}}"));
+    assert!(json.contains("\"extensions\":{\"KHR_lights_punctual\":{\"lights\":[{\"type\":\
+        \"spot\",\"color\":[1,1,0.5],\"intensity\":20,\"range\":10,\"spot\":\
+        {\"innerConeAngle\":0.25,\"outerConeAngle\":0.5}}]}},\"buffers\":[{\"byteLength\":"));
+}
+
+#[test]
+fn test_gltf_animation() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let options = Options { rate: 4.0, ..Options::default() };
+    let (json, _) = scene.to_gltf("body.bin", &options);
+    let animations = value(&json, "animations");
+
+    // The linear track keeps its keys, while the Bezier curve of the morph weight is baked.
+    assert!(animations.contains("\"target\":{\"node\":1,\"path\":\"translation\"}"));
+    assert!(animations.contains("\"target\":{\"node\":1,\"path\":\"rotation\"}"));
+    assert!(animations.contains("\"target\":{\"node\":2,\"path\":\"weights\"}"));
+    let accessors = value(&json, "accessors");
+    assert!(accessors.contains("\"count\":2,\"type\":\"SCALAR\",\"min\":[0],\"max\":[1]"));
+    assert!(accessors.contains("\"count\":5,\"type\":\"SCALAR\",\"min\":[0],\"max\":[1]"));
+
+    // Without meshes or animations, there is no buffer, and z is still turned into y.
+    let still: Scene = "Node {Translation {float[3] {{1, 2, 3}}}}".parse().unwrap();
+    let (json, buffer) = still.to_gltf("still.bin", &options);
+    assert!(buffer.is_empty());
+    assert_eq!(json, "{\"asset\":{\"version\":\"2.0\",\"generator\":\"opengex\"},\"scene\":0,\
+        \"scenes\":[{\"nodes\":[0]}],\"nodes\":[{\"translation\":[1,3,-2]}]}");
+
+    // An empty scene has no nodes, rather than an empty array of them.
+    let (json, _) = Scene::default().to_gltf("empty.bin", &options);
+    assert!(json.ends_with("\"scene\":0,\"scenes\":[{}]}"));
+}
+
+/// Returns the number of keys of every sampler input of the animations of a document.
+fn input_counts(json: &str) -> Vec<usize> {
+    let animations = value(json, "animations");
+    let accessors = value(json, "accessors");
+    let mut counts = vec![];
+    for part in animations.split("\"input\":").skip(1) {
+        let input: usize = part[..part.find(',').unwrap()].parse().unwrap();
+        let accessor = accessors.split("{\"bufferView\"").nth(input + 1).unwrap();
+        let count = &accessor[accessor.find("\"count\":").unwrap() + 8..];
+        counts.push(count[..count.find(',').unwrap()].parse().unwrap());
+    }
+    counts
+}
+
+#[test]
+fn test_gltf_baked_rotations() {
+    let options = Options { rate: 4.0, ..Options::default() };
+    let animated = |transformations: &str, target: &str, values: &str| {
+        let source = format!("Metric (key = \"up\") {{string {{\"y\"}}}}
+            Node
+            {{
+                {}
+                Animation
+                {{
+                    Track (target = %{}) {{Time {{Key {{float {{0, 1}}}}}} Value {{Key {{{}}}}}}}
+                }}
+            }}", transformations, target, values);
+        let scene: Scene = source.parse().unwrap();
+        input_counts(&scene.to_gltf("turn.bin", &options).0)
+    };
+
+    // A quarter turn keeps its keys, but the keys of a full turn are the same rotation, so it is
+    // baked.
+    let rotation = "Rotation %r (kind = \"z\") {float {0}}";
+    assert_eq!(animated(rotation, "r", "float {0, 1.5707964}"), vec![2, 2, 2]);
+    assert_eq!(animated(rotation, "r", "float {0, 6.2831855}"), vec![5, 5, 5]);
+
+    // A translation after a rotation is rotated with it, so the node is baked.
+    let rotated = "Rotation (kind = \"z\") {float {1}} Translation %t {float[3] {{0, 0, 0}}}";
+    assert_eq!(animated(rotated, "t", "float[3] {{0, 0, 0}, {1, 0, 0}}"), vec![5, 5, 5]);
+    let translated = "Translation %t {float[3] {{0, 0, 0}}} Rotation (kind = \"z\") {float {1}}";
+    assert_eq!(animated(translated, "t", "float[3] {{0, 0, 0}, {1, 0, 0}}"), vec![2, 2, 2]);
+}
+
+#[test]
+fn test_gltf_glb() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let glb = scene.to_glb(&Options::default());
+    let (json, buffer) = scene.to_gltf("body.bin", &Options::default());
+    let word = |offset: usize| {
+        u32::from_le_bytes([glb[offset], glb[offset + 1], glb[offset + 2], glb[offset + 3]])
+            as usize
+    };
+    assert_eq!(&glb[..4], b"glTF");
+    assert_eq!(word(4), 2);
+    assert_eq!(word(8), glb.len());
+
+    // The JSON chunk is padded with spaces, and has no URI for the buffer in the same file.
+    let json_length = word(12);
+    assert_eq!(&glb[16..20], b"JSON");
+    assert_eq!(json_length % 4, 0);
+    let chunk = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+    assert_eq!(chunk.trim_end(), json.replace(",\"uri\":\"body.bin\"", ""));
+
+    let bin = 20 + json_length;
+    assert_eq!(word(bin), buffer.len());
+    assert_eq!(&glb[bin + 4..bin + 8], b"BIN\0");
+    assert_eq!(&glb[bin + 8..], &buffer[..]);
+}
+
+/// Runs the glTF validator at the path given by the `GLTF_VALIDATOR` environment variable on the
+/// test scenes, written both as .gltf files with a .bin buffer and as .glb files. The validator is
+/// not part of the build, so the test only runs when asked for, with `--ignored`.
+#[test]
+#[ignore = "needs the glTF validator, given by GLTF_VALIDATOR"]
+fn test_gltf_validator() {
+    let validator = env::var_os("GLTF_VALIDATOR")
+        .expect("GLTF_VALIDATOR must be the path of the glTF validator");
+    let dir = env::temp_dir().join(format!("opengex-gltf-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let sources = [SOURCE, "", "Node {Translation {float[3] {{1, 2, 3}}}}"];
+    for (i, source) in sources.iter().enumerate() {
+        let scene: Scene = source.parse().unwrap();
+        let gltf = dir.join(format!("scene{}.gltf", i));
+        let glb = dir.join(format!("scene{}.glb", i));
+        scene.to_gltf_file(&gltf, &Options::default()).unwrap();
+        scene.to_glb_file(&glb, &Options::default()).unwrap();
+        for path in &[gltf, glb] {
+            // The report is printed as JSON, with its issues counted by severity.
+            let output = Command::new(&validator).arg("--stdout").arg(path).output().unwrap();
+            let report = String::from_utf8_lossy(&output.stdout);
+            let compact: String = report.chars().filter(|c| !c.is_whitespace()).collect();
+            assert!(output.status.success() && compact.contains("\"numErrors\":0"),
+                "{}: {}{}", path.display(), report, String::from_utf8_lossy(&output.stderr));
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,75 @@
+extern crate opengex;
+
+use opengex::structure::*;
+
+fn geometry_node(scene: &Scene) -> &GeometryNode {
+    match scene.nodes[0] {
+        Nodes::GeometryNode(ref node) => node,
+        _ => panic!("expected a geometry node")
+    }
+}
+
+#[test]
+fn test_morph_blend() {
+    let source = r#"
+        GeometryNode
+        {
+            ObjectRef {ref {$geometry}}
+            MorphWeight (index = 0) {float {0.5}}
+            MorphWeight (index = 1) {float {0.5}}
+            MorphWeight %smile (index = 2) {float {0}}
+            Animation
+            {
+                Track (target = %smile) {Time {Key {float {0, 2}}} Value {Key {float {0, 1}}}}
+            }
+        }
+        GeometryObject $geometry
+        {
+            Morph (index = 2, base = 0) {Name {string {"smile"}}}
+            Mesh
+            {
+                VertexArray (attrib = "position") {float[3] {{0, 0, 0}, {2, 0, 0}}}
+                VertexArray (attrib = "position", morph = 1) {float[3] {{0, 2, 0}, {2, 2, 0}}}
+                VertexArray (attrib = "position", morph = 2) {float[3] {{0, 0, 4}, {2, 0, 0}}}
+                VertexArray (attrib = "normal") {float[3] {{0, 0, 1}, {0, 0, 1}}}
+                VertexArray (attrib = "normal", morph = 1) {float[3] {{0, 1, 0}, {0, 0, 1}}}
+                VertexArray (attrib = "texcoord") {float[2] {{0, 0}, {1, 0}}}
+            }
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let node = geometry_node(&scene);
+
+    let mesh = node.blend_morphs(0, None).unwrap();
+    assert_eq!(mesh.vertex_arrays.len(), 3);
+    assert_eq!(mesh.vertex_arrays[0].data,
+        VertexData::Float(vec![0.0, 1.0, 0.0, 2.0, 1.0, 0.0]));
+    let s = 0.5f32.sqrt();
+    assert_eq!(mesh.vertex_arrays[1].data, VertexData::Float(vec![0.0, s, s, 0.0, 0.0, 1.0]));
+    assert_eq!(mesh.vertex_arrays[2], node.geometry.meshes[0].vertex_arrays[5]);
+
+    // Halfway through the animation, the relative target adds half of its difference to target 0.
+    let mesh = node.blend_morphs(0, Some(1.0)).unwrap();
+    assert_eq!(mesh.vertex_arrays[0].data,
+        VertexData::Float(vec![0.0, 1.0, 2.0, 2.0, 1.0, 0.0]));
+    assert!(node.blend_morphs(1, None).is_none());
+}
+
+#[test]
+fn test_morph_default_weights() {
+    let source = r#"
+        GeometryNode {ObjectRef {ref {$geometry}}}
+        GeometryObject $geometry
+        {
+            Mesh
+            {
+                VertexArray (attrib = "position") {double[3] {{1, 2, 3}}}
+                VertexArray (attrib = "position", morph = 1) {double[3] {{4, 5, 6}}}
+            }
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let mesh = geometry_node(&scene).blend_morphs(0, None).unwrap();
+    assert_eq!(mesh.vertex_arrays.len(), 1);
+    assert_eq!(mesh.vertex_arrays[0].data, VertexData::Double(vec![1.0, 2.0, 3.0]));
+}
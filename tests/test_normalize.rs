@@ -0,0 +1,145 @@
+extern crate opengex;
+
+mod common;
+
+use std::f32::consts::FRAC_PI_2;
+use std::sync::Arc;
+use opengex::structure::*;
+use common::assert_close;
+
+#[test]
+fn test_normalize_cube() {
+    let mut scene = Scene::from_reader(&include_bytes!("assets/cube.ogex")[..]).unwrap();
+    scene.normalize(&Metric { up: Axis::Y, distance: 0.01, ..Metric::default() });
+
+    assert_eq!(scene.metric.up, Axis::Y);
+    assert_eq!(scene.metric.distance, 0.01);
+    match scene.nodes[0] {
+        Nodes::GeometryNode(ref node) => {
+            let mesh = &node.geometry.meshes[0];
+            // The first vertex (1, 1, -1) is turned into (x, z, -y) in centimeters.
+            let position = mesh.vertex_arrays[0].data.to_f32();
+            assert_close(&position[..3], &[100.0, -100.0, -100.0]);
+            let normal = mesh.vertex_arrays[1].data.to_f32();
+            assert_close(&normal[..3], &[0.0, -1.0, 0.0]);
+        }
+        _ => panic!("expected a geometry node")
+    }
+    match scene.nodes[1] {
+        Nodes::LightNode(ref node) => {
+            assert_eq!(node.transformations.len(), 2);
+            match *node.transformations[0] {
+                Transformation::Transform(Transform(ref m)) => {
+                    assert_close(&m[12..], &[407.62453, 590.3862, -100.545395, 1.0])
+                }
+                _ => panic!("expected a transform")
+            }
+            match *node.transformations[1] {
+                Transformation::Transform(Transform(ref m)) => assert_close(m, &[
+                    1.0, 0.0, 0.0, 0.0,
+                    0.0, 0.0, -1.0, 0.0,
+                    0.0, 1.0, 0.0, 0.0,
+                    0.0, 0.0, 0.0, 1.0]),
+                _ => panic!("expected a transform")
+            }
+        }
+        _ => panic!("expected a light node")
+    }
+    match scene.nodes[2] {
+        Nodes::CameraNode(ref node) => assert_close(&[node.camera.params["far"]], &[10000.0]),
+        _ => panic!("expected a camera node")
+    }
+
+    let source = scene.to_string_with(&Default::default()).unwrap();
+    assert!(source.contains("Metric (key = \"up\") {string {\"y\"}}"));
+    assert_eq!(source.parse::<Scene>().unwrap(), scene);
+}
+
+#[test]
+fn test_normalize_animation() {
+    let source = r#"
+        Metric (key = "angle") {float {0.017453292}}
+        Metric (key = "time") {float {0.001}}
+        Metric (key = "forward") {string {"-y"}}
+        Node
+        {
+            Translation %x (kind = "x") {float {2}}
+            Rotation %y (kind = "y") {float {90}}
+            Translation %xyz {float[3] {{1, 2, 3}}}
+            Animation (begin = 500)
+            {
+                Track (target = %y)
+                {
+                    Time {Key {float {0, 1000}}}
+                    Value {Key {float {0, 180}}}
+                }
+                Track (target = %xyz)
+                {
+                    Time {Key {float {0, 1000}}}
+                    Value {Key {float[3] {{0, 0, 0}, {1, 2, 3}}}}
+                }
+            }
+        }
+    "#;
+    let mut scene: Scene = source.parse().unwrap();
+    scene.normalize(&Metric { up: Axis::Y, ..Metric::default() });
+
+    assert_eq!(scene.metric.forward, Some(Axis::Z));
+    match scene.nodes[0] {
+        Nodes::Node(ref node) => {
+            assert_eq!(*node.transformations[0], Transformation::Translation(Translation::X(2.0)));
+            match *node.transformations[1] {
+                Transformation::Rotation(Rotation::Z(a)) => assert_close(&[a], &[-FRAC_PI_2]),
+                ref t => panic!("expected a rotation about z, found {:?}", t)
+            }
+            assert_eq!(*node.transformations[2],
+                Transformation::Translation(Translation::Xyz(1.0, 3.0, -2.0)));
+
+            let animation = &node.animations[0];
+            assert_close(&[animation.begin.unwrap()], &[0.5]);
+            let track = &animation.tracks[0];
+            match track.target {
+                TrackTarget::Transformation(ref t) => assert!(Arc::ptr_eq(t,
+                    &node.transformations[1])),
+                _ => panic!("expected a transformation target")
+            }
+            match track.time {
+                Time::Linear(ref t) => assert_close(t, &[0.0, 1.0]),
+                _ => panic!("expected linear times")
+            }
+            match track.value {
+                Value::Linear(ref v) => assert_close(v, &[0.0, -std::f32::consts::PI]),
+                _ => panic!("expected linear values")
+            }
+            match animation.tracks[1].value {
+                Value::Linear(ref v) => assert_eq!(*v, vec![0.0, 0.0, 0.0, 1.0, 3.0, -2.0]),
+                _ => panic!("expected linear values")
+            }
+        }
+        _ => panic!("expected a node")
+    }
+}
+
+#[test]
+fn test_normalize_double() {
+    let source = r#"
+        GeometryNode {ObjectRef {ref {$geometry}}}
+        GeometryObject $geometry
+        {
+            Mesh {VertexArray (attrib = "position") {double[3] {{1.000000000001, 2, 3}}}}
+        }
+    "#;
+    let mut scene: Scene = source.parse().unwrap();
+    scene.normalize(&Metric { up: Axis::Y, distance: 0.5, ..Metric::default() });
+
+    match scene.nodes[0] {
+        Nodes::GeometryNode(ref node) => {
+            // The digits that do not fit in an `f32` are kept.
+            match node.geometry.meshes[0].vertex_arrays[0].data {
+                VertexData::Double(ref v) => assert_eq!(*v, vec![2.000000000002, 6.0, -4.0]),
+                ref data => panic!("expected double vertex data, found {:?}", data)
+            }
+        }
+        _ => panic!("expected a geometry node")
+    }
+}
@@ -0,0 +1,191 @@
+extern crate opengex;
+
+mod common;
+
+use opengex::normals::Weighting;
+use opengex::structure::*;
+use common::assert_close;
+
+fn floats(array: &VertexArray) -> Vec<f32> {
+    array.data.to_f32()
+}
+
+fn find<'a>(mesh: &'a Mesh, attrib: &str) -> &'a VertexArray {
+    mesh.vertex_arrays.iter().find(|a| a.attrib == attrib).unwrap()
+}
+
+/// Two triangles folded along the y axis by a right angle, with duplicated vertices on the fold.
+const FOLD: &str = r#"
+    GeometryObject
+    {
+        Mesh
+        {
+            VertexArray (attrib = "position")
+            {
+                float[3] {{0, 0, 0}, {0, 1, 0}, {-1, 0, 0}, {0, 0, 0}, {0, 0, -1}, {0, 1, 0}}
+            }
+            VertexArray (attrib = "texcoord")
+            {
+                float[2] {{0, 0}, {0, 1}, {1, 0}, {0, 0}, {1, 0}, {0, 1}}
+            }
+        }
+    }
+"#;
+
+/// A curved grid of two by two quads, with its texture mirrored on the right column, a triangle
+/// without area in texture space on its top edge, and a degenerate triangle, with a vertex for
+/// every corner.
+const CURVED: &str = r#"
+    GeometryObject
+    {
+        Mesh
+        {
+            VertexArray (attrib = "position")
+            {
+                float[3]
+                {
+                    {0.0, 0.0, 0.0}, {1.0, 0.0, 0.3}, {1.0, 1.0, 0.10000001}, {0.0, 0.0, 0.0},
+                    {1.0, 1.0, 0.10000001}, {0.0, 1.0, 0.0}, {1.0, 0.0, 0.3}, {2.0, 0.0, 1.2},
+                    {2.0, 1.0, 0.8000001}, {1.0, 0.0, 0.3}, {2.0, 1.0, 0.8000001},
+                    {1.0, 1.0, 0.10000001}, {0.0, 1.0, 0.0}, {1.0, 1.0, 0.10000001},
+                    {1.0, 2.0, -0.099999994}, {0.0, 1.0, 0.0}, {1.0, 2.0, -0.099999994},
+                    {0.0, 2.0, 0.0}, {1.0, 1.0, 0.10000001}, {2.0, 1.0, 0.8000001},
+                    {2.0, 2.0, 0.40000004}, {1.0, 1.0, 0.10000001}, {2.0, 2.0, 0.40000004},
+                    {1.0, 2.0, -0.099999994}, {0.0, 2.0, 0.0}, {1.0, 2.0, -0.099999994},
+                    {0.5, 2.5, 0.0}, {0.0, 0.0, 0.0}, {0.0, 0.0, 0.0}, {1.0, 0.0, 0.3}
+                }
+            }
+            VertexArray (attrib = "normal")
+            {
+                float[3]
+                {
+                    {-0.0, 0.0, 1.0}, {-0.50709254, 0.16903085, 0.8451542},
+                    {-0.3651484, 0.18257418, 0.9128709}, {-0.0, 0.0, 1.0},
+                    {-0.3651484, 0.18257418, 0.9128709}, {0.19611615, 0.0, 0.9805807},
+                    {-0.50709254, 0.16903085, 0.8451542}, {-0.74420846, 0.24806947, 0.6201737},
+                    {-0.68041384, 0.27216554, 0.68041384}, {-0.50709254, 0.16903085, 0.8451542},
+                    {-0.68041384, 0.27216554, 0.68041384}, {-0.3651484, 0.18257418, 0.9128709},
+                    {0.19611615, 0.0, 0.9805807}, {-0.3651484, 0.18257418, 0.9128709},
+                    {-0.1924501, 0.19245009, 0.9622505}, {0.19611615, 0.0, 0.9805807},
+                    {-0.1924501, 0.19245009, 0.9622505}, {0.3713907, 0.0, 0.92847675},
+                    {-0.3651484, 0.18257418, 0.9128709}, {-0.68041384, 0.27216554, 0.68041384},
+                    {-0.5962848, 0.2981424, 0.74535596}, {-0.3651484, 0.18257418, 0.9128709},
+                    {-0.5962848, 0.2981424, 0.74535596}, {-0.1924501, 0.19245009, 0.9622505},
+                    {0.3713907, 0.0, 0.92847675}, {-0.1924501, 0.19245009, 0.9622505},
+                    {0.0, 0.0, 1.0}, {-0.0, 0.0, 1.0}, {-0.0, 0.0, 1.0},
+                    {-0.50709254, 0.16903085, 0.8451542}
+                }
+            }
+            VertexArray (attrib = "texcoord")
+            {
+                float[2]
+                {
+                    {0.0, 0.0}, {0.5, 0.125}, {0.5, 0.625}, {0.0, 0.0}, {0.5, 0.625}, {0.0, 0.5},
+                    {0.5, 0.125}, {0.0, 0.25}, {0.0, 0.75}, {0.5, 0.125}, {0.0, 0.75}, {0.5, 0.625},
+                    {0.0, 0.5}, {0.5, 0.625}, {0.5, 1.125}, {0.0, 0.5}, {0.5, 1.125}, {0.0, 1.0},
+                    {0.5, 0.625}, {0.0, 0.75}, {0.0, 1.25}, {0.5, 0.625}, {0.0, 1.25}, {0.5, 1.125},
+                    {0.0, 1.0}, {0.5, 1.125}, {0.25, 1.0625}, {0.0, 0.0}, {0.25, 0.25},
+                    {0.5, 0.125}
+                }
+            }
+        }
+    }
+"#;
+
+/// The tangents and the signs of the bitangents generated for every corner of `CURVED` by the
+/// reference implementation of MikkTSpace.
+const MIKKTSPACE: [[f32; 4]; 30] = [
+    [0.970142, -0.242536, 0.000000, 1.0],
+    [0.823160, -0.195669, 0.533030, 1.0],
+    [0.894698, -0.202153, 0.398310, 1.0],
+    [0.970142, -0.242536, 0.000000, 1.0],
+    [0.894698, -0.202153, 0.398310, 1.0],
+    [0.949427, -0.250063, -0.189885, 1.0],
+    [-0.819181, 0.210332, -0.533575, -1.0],
+    [-0.604997, 0.143147, -0.783255, -1.0],
+    [-0.667291, 0.153690, -0.728767, -1.0],
+    [-0.819181, 0.210332, -0.533575, -1.0],
+    [-0.667291, 0.153690, -0.728767, -1.0],
+    [-0.884351, 0.238323, -0.401405, -1.0],
+    [0.949427, -0.250063, -0.189885, 1.0],
+    [0.894698, -0.202153, 0.398310, 1.0],
+    [0.950708, -0.206396, 0.231421, 1.0],
+    [0.949427, -0.250063, -0.189885, 1.0],
+    [0.950708, -0.206396, 0.231421, 1.0],
+    [0.898840, -0.250638, -0.359536, 1.0],
+    [-0.884351, 0.238323, -0.401405, -1.0],
+    [-0.667291, 0.153690, -0.728767, -1.0],
+    [-0.737124, 0.164393, -0.655456, -1.0],
+    [-0.884351, 0.238323, -0.401405, -1.0],
+    [-0.737124, 0.164393, -0.655456, -1.0],
+    [-0.932381, 0.269900, -0.240456, -1.0],
+    [0.898840, -0.250638, -0.359536, 1.0],
+    [0.950708, -0.206396, 0.231421, 1.0],
+    [1.000000, 0.000000, 0.000000, -1.0],
+    [0.970142, -0.242536, 0.000000, 1.0],
+    [1.000000, 0.000000, 0.000000, -1.0],
+    [0.823160, -0.195669, 0.533030, 1.0]
+];
+
+#[test]
+fn test_normals_generate() {
+    let scene: Scene = FOLD.parse().unwrap();
+    let mesh = &scene.geometry_objects[0].meshes[0];
+
+    // The vertices on the fold are smoothed, and welded as they end up with the same values.
+    let smooth = mesh.generate_normals(Weighting::Angle, None).unwrap();
+    let s = 0.5f32.sqrt();
+    let normals = floats(find(&smooth, "normal"));
+    assert_eq!(normals.len(), 12);
+    assert_close(&normals[..9], &[s, 0.0, s, s, 0.0, s, 0.0, 0.0, 1.0]);
+    assert_close(&normals[9..], &[1.0, 0.0, 0.0]);
+    assert_eq!(smooth.index_arrays[0].data, IndexData::U16(vec![0, 1, 2, 0, 3, 1]));
+
+    // Below the crease angle, the fold stays sharp.
+    let sharp = mesh.generate_normals(Weighting::Area, Some(0.5)).unwrap();
+    assert_eq!(find(&sharp, "normal").len(), 6);
+    assert_close(&floats(find(&sharp, "normal"))[..3], &[0.0, 0.0, 1.0]);
+    let smooth = mesh.generate_normals(Weighting::Area, Some(1.6)).unwrap();
+    assert_eq!(find(&smooth, "normal").len(), 4);
+}
+
+#[test]
+fn test_normals_tangents() {
+    let scene: Scene = FOLD.parse().unwrap();
+    let mesh = &scene.geometry_objects[0].meshes[0];
+    assert!(mesh.generate_tangents(0).is_none());
+
+    let mesh = mesh.generate_normals(Weighting::Angle, Some(0.5)).unwrap();
+    let mesh = mesh.generate_tangents(0).unwrap();
+    let tangents = floats(find(&mesh, "tangent"));
+    let bitangents = floats(find(&mesh, "bitangent"));
+    // On the first triangle, u runs along -x and v along y, mirroring the texture.
+    assert_close(&tangents[..3], &[-1.0, 0.0, 0.0]);
+    assert_close(&bitangents[..3], &[0.0, 1.0, 0.0]);
+    // On the second one, u runs along -z.
+    assert_close(&tangents[9..12], &[0.0, 0.0, -1.0]);
+    assert_close(&bitangents[9..12], &[0.0, 1.0, 0.0]);
+    assert!(mesh.generate_tangents(1).is_none());
+}
+
+#[test]
+fn test_normals_tangents_mikktspace() {
+    let scene: Scene = CURVED.parse().unwrap();
+    let mesh = scene.geometry_objects[0].meshes[0].generate_tangents(0).unwrap();
+    let normals = floats(find(&mesh, "normal"));
+    let tangents = floats(find(&mesh, "tangent"));
+    let bitangents = floats(find(&mesh, "bitangent"));
+    let indices = mesh.index_arrays[0].data.to_u64();
+    // The tangent of every corner, and the sign of its bitangent, from the reference implementation
+    // of MikkTSpace.
+    for (c, expected) in MIKKTSPACE.iter().enumerate() {
+        let v = indices[c] as usize * 3;
+        let (n, t) = (&normals[v..v + 3], &tangents[v..v + 3]);
+        let b = [n[1] * t[2] - n[2] * t[1], n[2] * t[0] - n[0] * t[2], n[0] * t[1] - n[1] * t[0]];
+        assert_close(t, &expected[..3]);
+        // MikkTSpace leaves the two corners without another triangle at their vertex with a
+        // tangent along x, and a negative sign, where a positive one is given instead.
+        let sign = if c == 26 || c == 28 { 1.0 } else { expected[3] };
+        assert_close(&bitangents[v..v + 3], &[b[0] * sign, b[1] * sign, b[2] * sign]);
+    }
+}
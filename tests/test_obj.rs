@@ -0,0 +1,241 @@
+extern crate opengex;
+
+use std::io;
+
+use opengex::error::ErrorKind;
+use opengex::structure::*;
+
+const CUBE_FACES: &str = "
+# Two faces of a cube, with different materials.
+mtllib cube.mtl
+o Cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 -1
+vn 0 -1 0
+usemtl Red
+f 1/1/1 4/4/1 3/3/1 2/2/1
+usemtl Textured
+f 1/1/2 2/2/2 -1/3/2
+";
+
+const CUBE_MTL: &str = "
+newmtl Red
+Kd 1 0 0
+Ks 0.5
+Ns 32
+newmtl Textured
+Kd 1 1 1
+d 0.5
+map_Kd -s 2 2 1 -o 0.5 0 0 textures/brick wall.png
+";
+
+fn library(name: &str) -> io::Result<String> {
+    match name {
+        "cube.mtl" => Ok(CUBE_MTL.to_string()),
+        _ => Err(io::Error::new(io::ErrorKind::NotFound, name.to_string()))
+    }
+}
+
+#[test]
+fn test_obj_geometry() {
+    let scene = Scene::from_obj(CUBE_FACES, library).unwrap();
+    assert_eq!(scene.metric.up, Axis::Y);
+    assert_eq!(scene.nodes.len(), 1);
+    assert_eq!(scene.geometry_objects.len(), 1);
+    let node = match scene.nodes[0] {
+        Nodes::GeometryNode(ref node) => node,
+        _ => panic!("expected a geometry node")
+    };
+    assert_eq!(node.name.as_ref().map(|n| &n[..]), Some("Cube"));
+    let mesh = &node.geometry.meshes[0];
+    assert_eq!(mesh.primitive, GeometricPrimitive::Triangles);
+
+    let array = |attrib: &str| {
+        let array = mesh.vertex_arrays.iter().find(|a| a.attrib == attrib).unwrap();
+        (array.components, array.data.to_f64())
+    };
+    // The corners 1/1/1 and 1/1/2 differ by their normal, so they are distinct vertices.
+    let (components, positions) = array("position");
+    assert_eq!(components, 3);
+    assert_eq!(positions, vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+    let (components, texcoords) = array("texcoord");
+    assert_eq!(components, 2);
+    assert_eq!(texcoords, vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+        1.0, 1.0]);
+    let (_, normals) = array("normal");
+    assert_eq!(&normals[9..15], &[0.0, 0.0, -1.0, 0.0, -1.0, 0.0]);
+
+    // The quad is split into a fan, and every material gets an index array.
+    assert_eq!(mesh.index_arrays.len(), 2);
+    assert_eq!(mesh.index_arrays[0].material, 0);
+    assert_eq!(mesh.index_arrays[0].data.to_u64(), vec![0, 1, 2, 0, 2, 3]);
+    assert_eq!(mesh.index_arrays[1].material, 1);
+    assert_eq!(mesh.index_arrays[1].data.to_u64(), vec![4, 5, 6]);
+    assert_eq!(node.materials[0].name.as_ref().map(|n| &n[..]), Some("Red"));
+    assert_eq!(node.materials[1].name.as_ref().map(|n| &n[..]), Some("Textured"));
+    assert!(scene.validate().is_empty());
+}
+
+#[test]
+fn test_obj_materials() {
+    let scene = Scene::from_obj(CUBE_FACES, library).unwrap();
+    assert_eq!(scene.materials.len(), 2);
+    let red = &scene.materials[0];
+    assert_eq!(red.color["diffuse"], Color::Rgb(1.0, 0.0, 0.0));
+    assert_eq!(red.color["specular"], Color::Rgb(0.5, 0.5, 0.5));
+    assert_eq!(red.param["specular_power"], 32.0);
+    assert!(red.texture.is_empty());
+
+    let textured = &scene.materials[1];
+    assert_eq!(textured.color["opacity"], Color::Rgb(0.5, 0.5, 0.5));
+    let texture = &textured.texture["diffuse"];
+    assert_eq!(texture.file_name, "textures/brick wall.png");
+    assert_eq!(texture.transformations.len(), 2);
+    // The scale is applied first, so it comes last.
+    assert_eq!(*texture.transformations[0],
+        Transformation::Translation(Translation::Xyz(0.5, 0.0, 0.0)));
+    assert_eq!(*texture.transformations[1],
+        Transformation::Scale(Scale::Xyz(2.0, 2.0, 1.0)));
+}
+
+#[test]
+fn test_obj_errors() {
+    let no_library = |_: &str| Ok(String::new());
+    let error = Scene::from_obj("v 0 0 0\nv 1 x 0\n", no_library).unwrap_err();
+    match error.kind {
+        ErrorKind::Invalid(ref message) => assert_eq!(message, "invalid number `x`"),
+        ref kind => panic!("expected an invalid number, got {:?}", kind)
+    }
+    assert_eq!(error.location.map(|l| (l.line, l.column)), Some((2, 5)));
+
+    let error = Scene::from_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n", no_library).unwrap_err();
+    match error.kind {
+        ErrorKind::Invalid(ref message) => assert_eq!(message, "invalid face vertex `3`"),
+        ref kind => panic!("expected an invalid face vertex, got {:?}", kind)
+    }
+
+    let denied = |name: &str| Err(io::Error::new(io::ErrorKind::PermissionDenied, name));
+    let error = Scene::from_obj("mtllib locked.mtl\n", denied).unwrap_err();
+    match error.kind {
+        ErrorKind::Io(_) => {}
+        kind => panic!("expected an I/O error, got {:?}", kind)
+    }
+}
+
+#[test]
+fn test_obj_colors_and_missing_library() {
+    let source = "
+        mtllib missing.mtl
+        v 0 0 0 1 0 0
+        v 1 0 0 0 1 0
+        v 0 1 0
+        usemtl Paint
+        f 1 2 3
+    ";
+    let scene = Scene::from_obj(source, library).unwrap();
+    let mesh = &scene.geometry_objects[0].meshes[0];
+    let colors = mesh.vertex_arrays.iter().find(|a| a.attrib == "color").unwrap();
+    assert_eq!(colors.components, 3);
+    assert_eq!(colors.data.to_f64(), vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0]);
+    // The material of the missing library keeps its name.
+    assert_eq!(scene.materials.len(), 1);
+    assert_eq!(scene.materials[0].name.as_ref().map(|n| &n[..]), Some("Paint"));
+    assert!(scene.materials[0].color.is_empty());
+
+    // An alpha gives the colors a fourth component, and five numbers are neither.
+    let scene = Scene::from_obj("v 0 0 0 1 1 1 0.5\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", library)
+        .unwrap();
+    let colors = &scene.geometry_objects[0].meshes[0].vertex_arrays[1];
+    assert_eq!((colors.attrib.as_str(), colors.components), ("color", 4));
+    assert_eq!(&colors.data.to_f64()[..4], &[1.0, 1.0, 1.0, 0.5]);
+    assert!(Scene::from_obj("v 0 0 0 1 1\n", library).is_err());
+}
+
+const QUAD: &str = r#"
+    GeometryNode
+    {
+        Name {string {"Quad"}}
+        ObjectRef {ref {$quad}}
+        MaterialRef (index = 0) {ref {$paint}}
+        Translation {float[3] {{1, 2, 3}}}
+        Scale (kind = "x") {float {-1}}
+    }
+    GeometryObject $quad
+    {
+        Mesh (primitive = "quads")
+        {
+            VertexArray (attrib = "position")
+            {
+                float[3] {{0, 0, 0}, {1, 0, 0}, {1, 1, 0}, {0, 1, 0}}
+            }
+            VertexArray (attrib = "normal") {float[3] {{0, 0, 1}, {0, 0, 1}, {1, 0, 0}, {1, 0, 0}}}
+            IndexArray (material = 0) {unsigned_int16 {0, 1, 2, 3}}
+            IndexArray (material = 1) {unsigned_int16 {3, 2, 1, 0}}
+        }
+    }
+    Material $paint
+    {
+        Name {string {"Paint"}}
+        Color (attrib = "diffuse") {float[3] {{0.5, 0.25, 1}}}
+        Param (attrib = "specular_power") {float {16}}
+        Texture (attrib = "diffuse")
+        {
+            string {"paint.png"}
+            Scale (kind = "xyz") {float[3] {{2, 2, 1}}}
+        }
+    }
+"#;
+
+#[test]
+fn test_obj_export() {
+    let scene: Scene = QUAD.parse().unwrap();
+    let (obj, mtl) = scene.to_obj("quad.mtl");
+    // The quad is mirrored by its node, so its triangles are wound the other way around, and the
+    // index array without a material gets a default one.
+    assert_eq!(obj, "\
+mtllib quad.mtl
+o Quad
+v 1 2 3
+v 0 2 3
+v 0 3 3
+v 1 3 3
+vn 0 0 1
+vn 0 0 1
+vn -1 0 0
+vn -1 0 0
+g Quad_0
+usemtl Paint
+f 1//1 3//3 2//2
+f 1//1 4//4 3//3
+g Quad_1
+usemtl default
+f 4//4 2//2 3//3
+f 4//4 1//1 2//2
+");
+    assert_eq!(mtl, "\
+newmtl Paint
+Kd 0.5 0.25 1
+Ns 16
+map_Kd -s 2 2 1 paint.png
+
+newmtl default
+");
+}
+
+#[test]
+fn test_obj_round_trip() {
+    let scene = Scene::from_obj(CUBE_FACES, library).unwrap();
+    let (obj, mtl) = scene.to_obj("cube.mtl");
+    let read = Scene::from_obj(&obj, |_| Ok(mtl.clone())).unwrap();
+    assert_eq!(read.materials, scene.materials);
+    assert_eq!(read.geometry_objects[0].meshes[0], scene.geometry_objects[0].meshes[0]);
+}
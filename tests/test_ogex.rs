@@ -0,0 +1,132 @@
+use std::env;
+use std::fs;
+use std::process::{ Command, Output };
+
+fn ogex(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ogex")).args(args).output().unwrap()
+}
+
+/// Copies the cube asset into a temporary file with the given name.
+fn cube(name: &str) -> String {
+    let path = env::temp_dir().join(format!("ogex-{}-{}", std::process::id(), name));
+    fs::copy("tests/assets/cube.ogex", &path).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_ogex_info() {
+    let output = ogex(&["info", "tests/assets/cube.ogex"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("    geometry: 1\n"));
+    assert!(stdout.contains("    triangles: 12\n"));
+    assert!(stdout.contains("GeometryNode \"Cube\" ("));
+
+    assert_eq!(ogex(&["info"]).status.code(), Some(2));
+    assert_eq!(ogex(&["frobnicate"]).status.code(), Some(2));
+    assert_eq!(ogex(&["info", "missing.ogex"]).status.code(), Some(1));
+}
+
+#[test]
+fn test_ogex_validate() {
+    assert!(ogex(&["validate", "tests/assets/cube.ogex"]).status.success());
+
+    let path = cube("invalid.ogex");
+    let source = fs::read_to_string(&path).unwrap();
+    fs::write(&path, source.replacen("{0, 1, 2}", "{99, 1, 2}", 1)).unwrap();
+    let output = ogex(&["validate", &path]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("index 99 is out of"));
+}
+
+#[test]
+fn test_ogex_fmt_and_convert() {
+    // The cube has comments, so it is left as it is, since formatting would remove them.
+    let path = cube("fmt.ogex");
+    let source = fs::read_to_string(&path).unwrap();
+    let result = ogex(&["fmt", &path]);
+    assert_eq!(result.status.code(), Some(1));
+    assert!(String::from_utf8(result.stderr).unwrap().contains("has comments"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), source);
+
+    let uncommented: Vec<&str> = source.lines().map(|l| l.split("//").next().unwrap()).collect();
+    fs::write(&path, uncommented.join("\n")).unwrap();
+    assert_eq!(ogex(&["fmt", "--check", &path]).status.code(), Some(1));
+    assert!(ogex(&["fmt", "--spaces", "2", &path]).status.success());
+    assert!(ogex(&["fmt", "--check", "--spaces", "2", &path]).status.success());
+    assert!(fs::read_to_string(&path).unwrap().contains("\n  ObjectRef"));
+
+    // A file that cannot be read is reported like a file that cannot be parsed, and the other
+    // files are still checked.
+    let result = ogex(&["fmt", "--check", "missing.ogex", &path]);
+    assert_eq!(result.status.code(), Some(1));
+    assert!(String::from_utf8(result.stderr).unwrap().contains("error: missing.ogex: "));
+    assert!(String::from_utf8(result.stdout).unwrap().contains(&path));
+
+    // Slashes in strings are not comments.
+    let url = cube("url.ogex");
+    let material = "Material {Texture (attrib = \"diffuse\") {string {\"http://a/*b\"}}}";
+    fs::write(&url, material).unwrap();
+    assert!(ogex(&["fmt", &url]).status.success());
+    assert!(fs::read_to_string(&url).unwrap().contains("\"http://a/*b\""));
+    fs::remove_file(&url).unwrap();
+
+    let output = cube("converted.ogex");
+    assert!(ogex(&["convert", "--up", "y", &path, &output]).status.success());
+    let converted = fs::read_to_string(&output).unwrap();
+    assert!(converted.contains("Metric (key = \"up\") {string {\"y\"}}"));
+    assert_eq!(ogex(&["convert", &path, "cube.unknown"]).status.code(), Some(2));
+    for option in &["--bake", "--distance", "--angle", "--time"] {
+        for value in &["0", "-1", "inf", "NaN"] {
+            let result = ogex(&["convert", option, value, &path, &output]);
+            assert_eq!(result.status.code(), Some(2), "{} {}", option, value);
+        }
+    }
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn test_ogex_convert_obj() {
+    let dir = env::temp_dir().join(format!("ogex-{}-obj", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("quad.obj"), "mtllib quad.mtl\no Quad\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+        usemtl Blue\nf 1 2 3 4\n").unwrap();
+    fs::write(dir.join("quad.mtl"), "newmtl Blue\nKd 0 0 1\n").unwrap();
+    let input = dir.join("quad.obj");
+    let output = dir.join("quad.ogex");
+    let status = ogex(&["convert", input.to_str().unwrap(), output.to_str().unwrap()]).status;
+    let converted = fs::read_to_string(&output).unwrap();
+    let back = dir.join("back.obj");
+    let back_status = ogex(&["convert", output.to_str().unwrap(), back.to_str().unwrap()]).status;
+    let written = fs::read_to_string(&back).unwrap();
+    let library = fs::read_to_string(dir.join("back.mtl")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(status.success());
+    assert!(converted.contains("GeometryNode"));
+    assert!(converted.contains("Name {string {\"Quad\"}}"));
+    assert!(converted.contains("Color (attrib = \"diffuse\") {float[3] {{0.0, 0.0, 1.0}}}"));
+    assert!(back_status.success());
+    assert!(written.starts_with("mtllib back.mtl\no Quad\n"));
+    assert_eq!(library, "newmtl Blue\nKd 0 0 1\n");
+}
+
+#[test]
+fn test_ogex_convert_gltf() {
+    let input = cube("gltf.ogex");
+    let gltf = input.replace(".ogex", ".gltf");
+    let glb = input.replace(".ogex", ".glb");
+    let gltf_status = ogex(&["convert", &input, &gltf]).status;
+    let glb_status = ogex(&["convert", &input, &glb]).status;
+    let json = fs::read_to_string(&gltf).unwrap();
+    let bin = fs::read(input.replace(".ogex", ".bin")).unwrap();
+    let binary = fs::read(&glb).unwrap();
+    for path in &[&input, &gltf, &glb, &input.replace(".ogex", ".bin")] {
+        fs::remove_file(path).unwrap();
+    }
+    assert!(gltf_status.success());
+    assert!(json.contains(&format!("\"buffers\":[{{\"byteLength\":{},\"uri\":\"ogex-", bin.len())));
+    assert!(glb_status.success());
+    assert_eq!(&binary[..4], b"glTF");
+}
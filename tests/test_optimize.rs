@@ -0,0 +1,179 @@
+extern crate opengex;
+
+use opengex::structure::*;
+
+/// Returns a grid of quads with `n` quads on a side, with its triangles in a shuffled order, and
+/// a morph target and skin data holding the index of every vertex.
+fn grid(n: usize) -> Mesh {
+    let mut positions = vec![];
+    for y in 0..n + 1 {
+        for x in 0..n + 1 {
+            positions.extend_from_slice(&[x as f32, y as f32, 0.0]);
+        }
+    }
+    let count = (n + 1) * (n + 1);
+    let mut triangles = vec![];
+    for y in 0..n {
+        for x in 0..n {
+            let v = (y * (n + 1) + x) as u32;
+            let w = v + n as u32 + 1;
+            triangles.push([v, v + 1, w + 1]);
+            triangles.push([v, w + 1, w]);
+        }
+    }
+    // A deterministic shuffle.
+    let len = triangles.len();
+    for i in 0..len {
+        triangles.swap(i, (i * 7919 + 13) % len);
+    }
+    Mesh {
+        primitive: GeometricPrimitive::Triangles,
+        vertex_arrays: vec![
+            VertexArray {
+                attrib: "position".into(),
+                index: 0,
+                morph: 0,
+                components: 3,
+                data: VertexData::Float(positions.clone())
+            },
+            VertexArray {
+                attrib: "position".into(),
+                index: 0,
+                morph: 1,
+                components: 3,
+                data: VertexData::Float(positions.iter().map(|x| x * 2.0).collect())
+            }
+        ],
+        index_arrays: vec![IndexArray {
+            material: 0,
+            restart: None,
+            front_face: FrontFace::CounterClockwise,
+            data: IndexData::U32(triangles.iter().flat_map(|t| t.iter().cloned()).collect())
+        }],
+        skin: Some(Skin {
+            transform: None,
+            skeleton: Skeleton { bone_ref_array: vec![], transforms: vec![] },
+            bone_count_array: IndexData::U8(vec![1; count]),
+            bone_index_array: IndexData::U32((0..count as u32).collect()),
+            bone_weight_array: vec![1.0; count]
+        })
+    }
+}
+
+/// Returns the number of misses of a FIFO vertex cache of the given size.
+fn misses(indices: &[u64], size: usize) -> usize {
+    let mut cache: Vec<u64> = vec![];
+    let mut misses = 0;
+    for &i in indices {
+        if !cache.contains(&i) {
+            misses += 1;
+            cache.push(i);
+            if cache.len() > size {
+                cache.remove(0);
+            }
+        }
+    }
+    misses
+}
+
+/// Returns the positions of the corners of every triangle, with each triangle rotated to start
+/// with its smallest corner, sorted.
+fn triangles(mesh: &Mesh) -> Vec<Vec<u32>> {
+    let positions = mesh.vertex_arrays[0].data.to_f32();
+    let indices = mesh.index_arrays[0].data.to_u64();
+    let mut triangles: Vec<Vec<u32>> = indices.chunks(3).map(|t| {
+        let corners: Vec<u32> = t.iter()
+            .map(|&i| (positions[i as usize * 3] * 100.0 + positions[i as usize * 3 + 1]) as u32)
+            .collect();
+        let first = (0..3).min_by_key(|&k| corners[k]).unwrap();
+        (0..3).map(|k| corners[(first + k) % 3]).collect()
+    }).collect();
+    triangles.sort();
+    triangles
+}
+
+/// Returns the average cache miss ratio of a FIFO vertex cache of the given size, the number of
+/// misses per triangle.
+fn acmr(mesh: &Mesh, size: usize) -> f32 {
+    let indices = mesh.index_arrays[0].data.to_u64();
+    misses(&indices, size) as f32 / (indices.len() / 3) as f32
+}
+
+#[test]
+fn test_optimize() {
+    let mesh = grid(16);
+    let optimized = mesh.optimize().unwrap();
+
+    // The same triangles are drawn, with the same winding.
+    assert_eq!(triangles(&optimized), triangles(&mesh));
+
+    // The vertices are in the order of their first use.
+    let mut last = None;
+    for i in optimized.index_arrays[0].data.to_u64() {
+        if last.map_or(true, |last| i > last) {
+            assert_eq!(i, last.map_or(0, |last| last + 1));
+            last = Some(i);
+        }
+    }
+
+    assert!(Mesh { primitive: GeometricPrimitive::Points, ..mesh }.optimize().is_none());
+}
+
+#[test]
+fn test_optimize_acmr() {
+    let mesh = grid(32);
+    let optimized = mesh.optimize().unwrap();
+    for &size in &[8, 16, 32] {
+        let (before, after) = (acmr(&mesh, size), acmr(&optimized, size));
+        assert!(after < before * 0.5, "ACMR {} for a cache of {}, {} before", after, size, before);
+    }
+    // A grid has about one vertex per two triangles, which a cache of 32 comes close to.
+    assert!(acmr(&optimized, 32) < 0.7, "ACMR {}", acmr(&optimized, 32));
+}
+
+#[test]
+fn test_optimize_morph_and_skin() {
+    let mesh = grid(16);
+    let optimized = mesh.optimize().unwrap();
+
+    // Every vertex keeps its morph target and skin data, which hold its original index.
+    let positions = optimized.vertex_arrays[0].data.to_f32();
+    let morph = optimized.vertex_arrays[1].data.to_f32();
+    let skin = optimized.skin.as_ref().unwrap();
+    assert_eq!(positions.len(), mesh.vertex_arrays[0].data.len());
+    assert_eq!(morph.len(), positions.len());
+    assert_eq!(skin.bone_count_array.len(), positions.len() / 3);
+    for v in 0..positions.len() / 3 {
+        let original = (positions[v * 3 + 1] * 17.0 + positions[v * 3]) as u64;
+        assert_eq!(&morph[v * 3..v * 3 + 3], &[
+            positions[v * 3] * 2.0, positions[v * 3 + 1] * 2.0, positions[v * 3 + 2] * 2.0][..]);
+        assert_eq!(skin.bone_count_array.get(v), 1);
+        assert_eq!(skin.bone_index_array.get(v), original);
+    }
+}
+
+#[test]
+fn test_optimize_disconnected() {
+    // Triangles sharing no vertex leave the cache empty after every triangle, which must not make
+    // the optimization look through all the remaining triangles each time.
+    let n = 200_000;
+    let mesh = Mesh {
+        vertex_arrays: vec![VertexArray {
+            attrib: "position".into(),
+            index: 0,
+            morph: 0,
+            components: 3,
+            data: VertexData::Float(vec![0.0; n * 9])
+        }],
+        index_arrays: vec![IndexArray {
+            material: 0,
+            restart: None,
+            front_face: FrontFace::CounterClockwise,
+            data: IndexData::U32((0..n as u32 * 3).rev().collect())
+        }],
+        skin: None,
+        ..grid(1)
+    };
+    let optimized = mesh.optimize().unwrap();
+    assert_eq!(optimized.index_arrays[0].data.len(), n * 3);
+}
@@ -0,0 +1,62 @@
+extern crate opengex;
+
+use opengex::structure::*;
+
+fn mesh(primitive: &str, indices: &str) -> Mesh {
+    let source = format!(r#"
+        GeometryObject
+        {{
+            Mesh (primitive = "{}")
+            {{
+                VertexArray (attrib = "position")
+                {{
+                    float[3] {{{{0, 0, 0}}, {{1, 0, 0}}, {{0, 1, 0}}, {{1, 1, 0}}, {{0, 2, 0}}}}
+                }}
+                {}
+            }}
+        }}
+    "#, primitive, indices);
+    let scene: Scene = source.parse().unwrap();
+    scene.geometry_objects[0].meshes[0].clone()
+}
+
+#[test]
+fn test_primitive_triangles() {
+    // Odd triangles of a strip are swapped, and a restart index starts a new strip.
+    let strip = mesh("triangle_strip", r#"
+        IndexArray (material = 1, restart = 255) {unsigned_int8 {0, 1, 2, 3, 255, 2, 3, 4}}
+        IndexArray (material = 2) {unsigned_int8 {0, 1, 1, 2}}
+    "#);
+    let list = strip.to_triangle_list().unwrap();
+    assert_eq!(list.primitive, GeometricPrimitive::Triangles);
+    assert_eq!(list.vertex_arrays, strip.vertex_arrays);
+    assert_eq!(list.index_arrays.len(), 2);
+    assert_eq!(list.index_arrays[0].material, 1);
+    assert_eq!(list.index_arrays[0].restart, None);
+    assert_eq!(list.index_arrays[0].data, IndexData::U8(vec![0, 1, 2, 1, 3, 2, 2, 3, 4]));
+    // Triangles joining strips together are dropped.
+    assert_eq!(list.index_arrays[1].data, IndexData::U8(vec![]));
+
+    let quads = mesh("quads", "IndexArray {unsigned_int16 {0, 1, 3, 2, 4}}");
+    let list = quads.to_triangle_list().unwrap();
+    assert_eq!(list.index_arrays[0].data, IndexData::U16(vec![0, 1, 3, 0, 3, 2]));
+
+    // Without index arrays, the vertices are used in order.
+    let list = mesh("triangle_strip", "").to_triangle_list().unwrap();
+    assert_eq!(list.index_arrays[0].data, IndexData::U16(vec![0, 1, 2, 1, 3, 2, 2, 3, 4]));
+
+    assert!(mesh("lines", "").to_triangle_list().is_none());
+}
+
+#[test]
+fn test_primitive_lines() {
+    let strip = mesh("line_strip", "IndexArray (restart = 9) {unsigned_int32 {0, 1, 2, 9, 3, 4}}");
+    let list = strip.to_line_list().unwrap();
+    assert_eq!(list.primitive, GeometricPrimitive::Lines);
+    assert_eq!(list.index_arrays[0].data, IndexData::U32(vec![0, 1, 1, 2, 3, 4]));
+
+    let list = mesh("lines", "").to_line_list().unwrap();
+    assert_eq!(list.index_arrays[0].data, IndexData::U16(vec![0, 1, 2, 3]));
+
+    assert!(mesh("triangles", "").to_line_list().is_none());
+}
@@ -0,0 +1,300 @@
+extern crate opengex;
+
+use std::sync::Arc;
+use opengex::ddl::{ Name, Reference };
+use opengex::error::{ ErrorKind, ReferenceError };
+use opengex::structure::*;
+
+#[test]
+fn test_read_cube() {
+    let scene = Scene::from_reader(&include_bytes!("assets/cube.ogex")[..]).unwrap();
+
+    assert_eq!(scene.metric.distance, 1.0);
+    assert_eq!(scene.metric.up, Axis::Z);
+    assert_eq!(scene.nodes.len(), 3);
+    assert_eq!(scene.geometry_objects.len(), 1);
+    assert_eq!(scene.materials.len(), 1);
+
+    match scene.nodes[0] {
+        Nodes::GeometryNode(ref node) => {
+            assert_eq!(node.name, Some("Cube".to_string()));
+            assert!(Arc::ptr_eq(&node.geometry, &scene.geometry_objects[0]));
+            assert!(Arc::ptr_eq(&node.materials[0], &scene.materials[0]));
+            let mesh = &node.geometry.meshes[0];
+            assert_eq!(mesh.primitive, GeometricPrimitive::Triangles);
+            assert_eq!(mesh.vertex_arrays[0].attrib, "position");
+            assert_eq!(mesh.vertex_arrays[0].components, 3);
+            assert_eq!(mesh.vertex_arrays[0].len(), 24);
+            assert_eq!(mesh.vertex_arrays[1].attrib, "normal");
+            assert_eq!(mesh.index_arrays[0].data.len(), 36);
+            assert_eq!(mesh.index_arrays[0].data.get(5), 3);
+        }
+        _ => panic!("expected a geometry node")
+    }
+    match scene.nodes[1] {
+        Nodes::LightNode(ref node) => {
+            assert_eq!(node.light.light_type, LightType::Point);
+            assert_eq!(node.light.attenuations[0].curve, AttenuationCurve::InverseSquare);
+        }
+        _ => panic!("expected a light node")
+    }
+    match scene.nodes[2] {
+        Nodes::CameraNode(ref node) => assert_eq!(node.camera.params["far"], 100.0),
+        _ => panic!("expected a camera node")
+    }
+    assert_eq!(scene.materials[0].param["specular_power"], 50.0);
+}
+
+#[test]
+fn test_read_animation() {
+    let source = r#"
+        Node
+        {
+            Translation %xlat (kind = "x") {float {0}}
+            Animation (begin = 0.5)
+            {
+                Track (target = %xlat)
+                {
+                    Time {Key {float {0, 1}}}
+                    Value (curve = "bezier")
+                    {
+                        Key {float {0, 2}}
+                        Key (kind = "-control") {float {0, 1}}
+                        Key (kind = "+control") {float {1, 2}}
+                    }
+                }
+            }
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let node = match scene.nodes[0] {
+        Nodes::Node(ref node) => node,
+        _ => panic!("expected a node")
+    };
+    let animation = &node.animations[0];
+    assert_eq!(animation.begin, Some(0.5));
+    match animation.tracks[0].target {
+        TrackTarget::Transformation(ref t) => assert!(Arc::ptr_eq(t, &node.transformations[0])),
+        _ => panic!("expected a transformation target")
+    }
+    assert_eq!(animation.tracks[0].value, Value::Bezier(vec![(0.0, 0.0, 1.0), (2.0, 1.0, 2.0)]));
+}
+
+#[test]
+fn test_read_mesh() {
+    let source = r#"
+        GeometryObject
+        {
+            Mesh (primitive = "triangle_strip")
+            {
+                VertexArray (attrib = "texcoord[1]", morph = 2) {half[2] {{0, 1}, {1, 0}}}
+                IndexArray (material = 1, restart = 255, front = "cw") {unsigned_int8 {0, 1}}
+            }
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let mesh = &scene.geometry_objects[0].meshes[0];
+    let vertices = &mesh.vertex_arrays[0];
+    assert_eq!((vertices.attrib.as_str(), vertices.index, vertices.morph), ("texcoord", 1, 2));
+    assert_eq!(vertices.data, VertexData::Half(vec![0.0, 1.0, 1.0, 0.0]));
+    let indices = &mesh.index_arrays[0];
+    assert_eq!((indices.material, indices.restart), (1, Some(255)));
+    assert_eq!(indices.front_face, FrontFace::Clockwise);
+    assert_eq!(indices.data, IndexData::U8(vec![0, 1]));
+
+    // The usual restart index of 64-bit indices is above the largest signed integer.
+    for restart in &["0xFFFFFFFFFFFFFFFF", "18446744073709551615"] {
+        let source = format!("GeometryObject {{Mesh {{VertexArray (attrib = \"position\") \
+            {{float[3] {{{{0, 0, 0}}}}}} IndexArray (restart = {}) {{unsigned_int64 {{0}}}}}}}}",
+            restart);
+        let scene: Scene = source.parse().unwrap();
+        assert_eq!(scene.geometry_objects[0].meshes[0].index_arrays[0].restart, Some(u64::MAX));
+    }
+}
+
+#[test]
+fn test_read_skin() {
+    let source = r#"
+        BoneNode $root
+        {
+            BoneNode $arm {Translation {float[3] {{0, 1, 0}}}}
+        }
+        GeometryNode {ObjectRef {ref {$geometry}}}
+        GeometryObject $geometry
+        {
+            Mesh
+            {
+                VertexArray (attrib = "position") {float[3] {{0, 0, 0}, {0, 2, 0}}}
+                Skin
+                {
+                    Transform {float[16] {{1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1}}}
+                    Skeleton
+                    {
+                        BoneRefArray {ref {$root, $arm}}
+                        Transform
+                        {
+                            float[16]
+                            {
+                                {1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1},
+                                {1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 1, 0, 1}
+                            }
+                        }
+                    }
+                    BoneCountArray {unsigned_int16 {1, 2}}
+                    BoneIndexArray {unsigned_int16 {0, 0, 1}}
+                    BoneWeightArray {float {1, 0.5, 0.5}}
+                }
+            }
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let root = match scene.nodes[0] {
+        Nodes::BoneNode(ref node) => node,
+        _ => panic!("expected a bone node")
+    };
+    let arm = match root.children[0] {
+        Nodes::BoneNode(ref node) => node,
+        _ => panic!("expected a bone node")
+    };
+    let skin = scene.geometry_objects[0].meshes[0].skin.as_ref().unwrap();
+    assert_eq!(skin.transform.as_ref().unwrap().0[14], 1.0);
+    assert!(Arc::ptr_eq(&skin.skeleton.bone_ref_array[0], root));
+    assert!(Arc::ptr_eq(&skin.skeleton.bone_ref_array[1], arm));
+    assert_eq!(skin.skeleton.transforms.len(), 2);
+    assert_eq!(skin.skeleton.transforms[1].0[13], 1.0);
+    assert_eq!(skin.bone_count_array, IndexData::U16(vec![1, 2]));
+    assert_eq!(skin.bone_index_array, IndexData::U16(vec![0, 0, 1]));
+    assert_eq!(skin.bone_weight_array, vec![1.0, 0.5, 0.5]);
+}
+
+#[test]
+fn test_read_errors() {
+    assert!("GeometryNode {ObjectRef {ref {$missing}}}".parse::<Scene>().is_err());
+    assert!("Node {Transform {float[16] {{1, 0, 0, 0}}}}".parse::<Scene>().is_err());
+    assert!("LightObject {}".parse::<Scene>().is_err());
+
+    // A bone containing a geometry node skinned to the bone itself.
+    let cycle = r#"
+        BoneNode $bone {GeometryNode {ObjectRef {ref {$geometry}}}}
+        GeometryObject $geometry
+        {
+            Mesh
+            {
+                Skin
+                {
+                    Skeleton
+                    {
+                        BoneRefArray {ref {$bone}}
+                        Transform {float[16] {{1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1}}}
+                    }
+                    BoneCountArray {u16 {}}
+                    BoneIndexArray {u16 {}}
+                    BoneWeightArray {float {}}
+                }
+            }
+        }
+    "#;
+    assert!(cycle.parse::<Scene>().unwrap_err().to_string().contains("cyclic reference"));
+    assert!(cycle.replace("$bone}", "$geometry}").parse::<Scene>().is_err());
+
+    let materials = r#"
+        GeometryNode
+        {
+            ObjectRef {ref {$geometry}}
+            MaterialRef (index = 1) {ref {$a}}
+            MaterialRef (index = 1) {ref {$b}}
+        }
+        GeometryObject $geometry {}
+        Material $a {}
+        Material $b {}
+    "#;
+    let err = materials.parse::<Scene>().unwrap_err();
+    assert!(err.to_string().starts_with("duplicate MaterialRef index 1\n"));
+    assert_eq!(err.location.map(|l| l.line), Some(6));
+}
+
+#[test]
+fn test_read_references() {
+    // Local names are looked up in the enclosing structures, and may follow a global name.
+    let source = r#"
+        Node $a
+        {
+            Node %b {Translation %t (kind = "x") {float {1}}}
+            Animation {Track (target = $a%b%t) {Time {Key {float {0}}} Value {Key {float {1}}}}}
+        }
+        Node %b {Translation %t (kind = "x") {float {2}}}
+    "#;
+    match source.parse::<Scene>().unwrap_err().kind {
+        ErrorKind::Invalid(message) => assert!(message.contains("does not belong")),
+        _ => panic!("expected an invalid track target")
+    }
+    let source = r#"
+        Node %t
+        {
+            Translation %t (kind = "x") {float {1}}
+            Animation {Track (target = %t) {Time {Key {float {0}}} Value {Key {float {1}}}}}
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    match scene.nodes[0] {
+        Nodes::Node(ref node) => match node.animations[0].tracks[0].target {
+            TrackTarget::Transformation(ref t) => {
+                assert!(Arc::ptr_eq(t, &node.transformations[0]))
+            }
+            _ => panic!("expected a transformation target")
+        },
+        _ => panic!("expected a node")
+    }
+
+    let err = "Node $a {} Node $a {}".parse::<Scene>().unwrap_err();
+    assert_eq!(err.location.unwrap().offset, 11);
+    match err.kind {
+        ErrorKind::Reference(error) => {
+            assert_eq!(error, ReferenceError::Duplicate(Name::Global("a".to_string())));
+        }
+        _ => panic!("expected a duplicate name")
+    }
+    assert!("Node {Node %a {}} Node {Node %a {}}".parse::<Scene>().is_ok());
+    assert!("Node {Node %a {} Node %a {}}".parse::<Scene>().is_err());
+
+    let err = "GeometryNode {ObjectRef {ref {$missing}}}".parse::<Scene>().unwrap_err();
+    assert_eq!(err.location.unwrap().offset, 14);
+    match err.kind {
+        ErrorKind::Reference(error) => {
+            let missing = Reference(vec![Name::Global("missing".to_string())]);
+            assert_eq!(error, ReferenceError::Unresolved(missing));
+        }
+        _ => panic!("expected an unresolved reference")
+    }
+
+    let err = "Material $m {} LightNode {ObjectRef {ref {$m}}}".parse::<Scene>().unwrap_err();
+    assert_eq!(err.kind.to_string(), "$m refers to a Material instead of a LightObject");
+}
+
+#[test]
+fn test_read_error_location() {
+    let source = "GeometryObject $geometry1\n{\n\tMesh\n\t{\n\t\t\
+        VertexArray (attrib = \"position\") {int32[2] {{0, 0}}}\n\t}\n}\n";
+    let err = source.parse::<Scene>().unwrap_err();
+    let location = err.location.as_ref().unwrap();
+    assert_eq!((location.line, location.column), (5, 3));
+    assert_eq!(location.path, vec!["GeometryObject $geometry1", "Mesh", "VertexArray"]);
+    assert_eq!(err.to_string(), "\
+VertexArray must contain floating-point data, found int32[2] data
+ --> line 5, column 3, in GeometryObject $geometry1 / Mesh / VertexArray
+  |
+5 | \t\tVertexArray (attrib = \"position\") {int32[2] {{0, 0}}}
+  | \t\t^^^^^^^^^^^");
+
+    let err = "Node $a { Node { Translation {float {1, }} } }".parse::<Scene>().unwrap_err();
+    match err.kind {
+        ErrorKind::Parse { ref found, .. } => assert_eq!(found, "`}`"),
+        _ => panic!("expected a parse error")
+    }
+    let location = err.location.unwrap();
+    assert_eq!((location.line, location.column, location.width), (1, 41, 1));
+    assert_eq!(location.path, vec!["Node $a", "Node", "Translation"]);
+
+    let err = "Node {".parse::<Scene>().unwrap_err();
+    assert_eq!(err.kind.to_string(), "expected `}`, found end of file");
+}
@@ -0,0 +1,132 @@
+extern crate opengex;
+
+mod common;
+
+use opengex::animation::Pose;
+use opengex::structure::*;
+use opengex::transform::IDENTITY;
+use common::assert_close;
+
+const SOURCE: &str = r#"
+    BoneNode $root
+    {
+        BoneNode $arm
+        {
+            Translation %xlat {float[3] {{0, 1, 0}}}
+            Animation
+            {
+                Track (target = %xlat)
+                {
+                    Time {Key {float {0, 1}}}
+                    Value {Key {float[3] {{0, 1, 0}, {1, 1, 0}}}}
+                }
+            }
+        }
+    }
+    GeometryNode {ObjectRef {ref {$geometry}}}
+    GeometryObject $geometry
+    {
+        Mesh
+        {
+            VertexArray (attrib = "position") {float[3] {{0, 0, 0}, {0, 2, 0}}}
+            VertexArray (attrib = "normal") {float[3] {{1, 0, 0}, {1, 0, 0}}}
+            Skin
+            {
+                Skeleton
+                {
+                    BoneRefArray {ref {$root, $arm}}
+                    Transform
+                    {
+                        float[16]
+                        {
+                            {1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1},
+                            {1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 1, 0, 1}
+                        }
+                    }
+                }
+                BoneCountArray {unsigned_int16 {1, 2}}
+                BoneIndexArray {unsigned_int16 {0, 0, 1}}
+                BoneWeightArray {float {1, 0.5, 0.5}}
+            }
+        }
+    }
+"#;
+
+fn geometry_node(scene: &Scene) -> &GeometryNode {
+    match scene.nodes[1] {
+        Nodes::GeometryNode(ref node) => node,
+        _ => panic!("expected a geometry node")
+    }
+}
+
+#[test]
+fn test_skin_rest_pose() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let node = geometry_node(&scene);
+    let pose = Pose::default();
+
+    // The bones are in their bind pose, so the skin leaves the mesh as it is.
+    let matrices = node.skinning_matrices(&scene, &pose, 0).unwrap();
+    assert_eq!(matrices, vec![IDENTITY, IDENTITY]);
+    let mesh = node.skin(&scene, &pose, 0).unwrap();
+    assert_eq!(mesh.vertex_arrays, node.geometry.meshes[0].vertex_arrays);
+    assert!(node.skinning_matrices(&scene, &pose, 1).is_none());
+}
+
+#[test]
+fn test_skin_deform() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let node = geometry_node(&scene);
+    let mut pose = Pose::default();
+    scene.nodes[0].children()[0].animations()[0].sample_into(1.0, &mut pose);
+
+    // The arm moves one unit along x from its bind pose.
+    let matrices = node.skinning_matrices(&scene, &pose, 0).unwrap();
+    assert_eq!(matrices[0], IDENTITY);
+    assert_eq!(&matrices[1][12..], &[1.0, 0.0, 0.0, 1.0]);
+
+    // The second vertex follows the arm with half its weight.
+    let mesh = node.skin(&scene, &pose, 0).unwrap();
+    assert_eq!(mesh.vertex_arrays[0].data,
+        VertexData::Float(vec![0.0, 0.0, 0.0, 0.5, 2.0, 0.0]));
+    assert_eq!(mesh.vertex_arrays[1].data,
+        VertexData::Float(vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0]));
+
+    // A bone scaled along x turns the normals of its vertices away from x.
+    let skin = mesh.skin.as_ref().unwrap();
+    let mut scale = IDENTITY;
+    scale[0] = 2.0;
+    let mut mesh = node.geometry.meshes[0].clone();
+    mesh.vertex_arrays[1].data = VertexData::Float(vec![1.0, 1.0, 0.0, 1.0, 1.0, 0.0]);
+    let mesh = skin.deform(&mesh, &[scale, scale]);
+    let n = 1.0 / 1.25f32.sqrt();
+    match mesh.vertex_arrays[1].data {
+        VertexData::Float(ref v) => assert_close(v, &[0.5 * n, n, 0.0, 0.5 * n, n, 0.0]),
+        _ => panic!("expected float normals")
+    }
+    assert_eq!(mesh.vertex_arrays[0].data,
+        VertexData::Float(vec![0.0, 0.0, 0.0, 0.0, 2.0, 0.0]));
+}
+
+#[test]
+fn test_skin_limit_influences() {
+    let source = SOURCE
+        .replace("{1, 2}}", "{3, 1}}")
+        .replace("{0, 0, 1}}", "{1, 0, 1, 1}}")
+        .replace("{1, 0.5, 0.5}}", "{0.25, 0.5, 0.25, 2}}");
+    let scene: Scene = source.parse().unwrap();
+    let skin = scene.geometry_objects[0].meshes[0].skin.as_ref().unwrap();
+
+    // The two weights of bone 1 add up, and bone 0 is dropped.
+    let influences = skin.limit_influences::<1>();
+    assert_eq!(influences.indices, vec![[1], [1]]);
+    assert_eq!(influences.weights, vec![[1.0], [1.0]]);
+    assert_eq!(influences.dropped, vec![0.5, 0.0]);
+    assert_eq!(influences.max_dropped(), 0.5);
+    assert_eq!(influences.pruned_vertices(), 1);
+
+    let influences = skin.limit_influences::<4>();
+    assert_eq!(influences.indices, vec![[1, 0, 0, 0], [1, 0, 0, 0]]);
+    assert_eq!(influences.weights, vec![[0.5, 0.5, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0]]);
+    assert_eq!(influences.max_dropped(), 0.0);
+}
@@ -0,0 +1,81 @@
+extern crate opengex;
+
+mod common;
+
+use std::f32::consts::FRAC_PI_2;
+use opengex::structure::*;
+use opengex::transform::*;
+use common::assert_close;
+
+/// Applies a matrix to a point.
+fn apply(m: &Matrix, p: [f32; 3]) -> [f32; 3] {
+    let mut r = [m[12], m[13], m[14]];
+    for (i, x) in r.iter_mut().enumerate() {
+        *x += m[i] * p[0] + m[4 + i] * p[1] + m[8 + i] * p[2];
+    }
+    r
+}
+
+#[test]
+fn test_transform_to_matrix() {
+    let x = Transformation::Rotation(Rotation::Z(FRAC_PI_2)).to_matrix();
+    assert_close(&apply(&x, [1.0, 0.0, 0.0]), &[0.0, 1.0, 0.0]);
+    let axis = Transformation::Rotation(Rotation::Axis(FRAC_PI_2, 0.0, 0.0, 2.0)).to_matrix();
+    assert_close(&axis, &x);
+    let s = FRAC_PI_2.sin() / 2.0f32.sqrt();
+    let quaternion = Transformation::Rotation(Rotation::Quaternion(0.0, 0.0, s, s)).to_matrix();
+    assert_close(&quaternion, &x);
+
+    let translation = Transformation::Translation(Translation::Xyz(1.0, 2.0, 3.0)).to_matrix();
+    assert_close(&apply(&translation, [1.0, 1.0, 1.0]), &[2.0, 3.0, 4.0]);
+    let scale = Transformation::Scale(Scale::Y(2.0)).to_matrix();
+    assert_close(&apply(&scale, [1.0, 1.0, 1.0]), &[1.0, 2.0, 1.0]);
+
+    let m = multiply(&translation, &multiply(&x, &scale));
+    assert_close(&multiply(&m, &invert(&m).unwrap()), &IDENTITY);
+    assert_close(&transform_point(&m, [1.0, 1.0, 1.0]), &apply(&m, [1.0, 1.0, 1.0]));
+    assert_close(&transform_vector(&m, [1.0, 0.0, 0.0]), &[0.0, 1.0, 0.0]);
+    assert!(invert(&[0.0; 16]).is_none());
+    assert_close(&transpose(&transpose(&m)), &m);
+    assert_close(&multiply(&transpose(&m), &transpose(&invert(&m).unwrap())), &IDENTITY);
+}
+
+#[test]
+fn test_transform_world_matrices() {
+    let source = r#"
+        Node $parent
+        {
+            Translation {float[3] {{0, 0, 1}}}
+            Rotation (kind = "z") {float {1.5707963}}
+            Node $child
+            {
+                Translation (kind = "x") {float {2}}
+            }
+        }
+        Node $other {}
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let matrices = scene.world_matrices();
+    assert_eq!(matrices.len(), 3);
+
+    // The rotation is applied before the translation of the parent.
+    assert_close(&apply(&matrices[0].1, [1.0, 0.0, 0.0]), &[0.0, 1.0, 1.0]);
+    assert!(std::ptr::eq(matrices[1].0, &scene.nodes[0].children()[0]));
+    assert_close(&apply(&matrices[1].1, [0.0, 0.0, 0.0]), &[0.0, 2.0, 1.0]);
+    assert_eq!(matrices[2].1, IDENTITY);
+    assert_eq!(matrices[2].0.local_matrix(), IDENTITY);
+}
+
+#[test]
+fn test_transform_cube() {
+    let scene = Scene::from_reader(&include_bytes!("assets/cube.ogex")[..]).unwrap();
+    let matrices = scene.world_matrices();
+    match *matrices[2].0 {
+        Nodes::CameraNode(ref node) => {
+            assert_eq!(node.local_matrix(), matrices[2].1);
+            let position = apply(&matrices[2].1, [0.0, 0.0, 0.0]);
+            assert_close(&position, &[7.4811316, -6.50764, 5.343665]);
+        }
+        _ => panic!("expected a camera node")
+    }
+}
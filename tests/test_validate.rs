@@ -0,0 +1,102 @@
+extern crate opengex;
+
+use opengex::structure::*;
+use opengex::validate::*;
+
+#[test]
+fn test_validate_cube() {
+    let scene = Scene::from_reader(&include_bytes!("assets/cube.ogex")[..]).unwrap();
+    let errors: Vec<_> = scene.validate().into_iter()
+        .filter(|d| d.severity() == Severity::Error)
+        .collect();
+    assert_eq!(errors, vec![]);
+}
+
+#[test]
+fn test_validate_mesh() {
+    let source = r#"
+        BoneNode $bone {}
+        GeometryNode
+        {
+            Name {string {"node"}}
+            ObjectRef {ref {$geometry}}
+            MorphWeight (index = 1) {float {1}}
+            MorphWeight (index = 1) {float {0}}
+            MorphWeight (index = 2) {float {0}}
+        }
+        GeometryObject $geometry
+        {
+            Morph (index = 1, base = 3) {}
+            Mesh
+            {
+                VertexArray (attrib = "position") {float[3] {{0, 0, 0}, {1, 0, 0}, {0, 1, 0}}}
+                VertexArray (attrib = "position", morph = 1) {float[3] {{0, 0, 1}}}
+                IndexArray {unsigned_int8 {0, 1, 2, 2, 3}}
+                Skin
+                {
+                    Skeleton
+                    {
+                        BoneRefArray {ref {$bone}}
+                        Transform {float[16] {{1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1}}}
+                    }
+                    BoneCountArray {unsigned_int8 {1, 1, 2}}
+                    BoneIndexArray {unsigned_int8 {0, 0, 0, 1}}
+                    BoneWeightArray {float {1, 1, 0.5}}
+                }
+            }
+            Mesh (lod = 1, primitive = "lines") {VertexArray (attrib = "position") {float[2] {}}}
+        }
+    "#;
+    let scene: Scene = source.parse().unwrap();
+    let diagnostics = scene.validate();
+    let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind.clone()).collect();
+    assert_eq!(kinds, vec![
+        DiagnosticKind::VertexCount { expected: 3, found: 1 },
+        DiagnosticKind::IndexOutOfRange { index: 3, vertices: 3 },
+        DiagnosticKind::IncompletePrimitive { primitive: GeometricPrimitive::Triangles, left: 2 },
+        DiagnosticKind::BoneArrayLength { expected: 4, indices: 4, weights: 3 },
+        DiagnosticKind::BoneIndexOutOfRange { index: 1, bones: 1 },
+        DiagnosticKind::BoneWeightSum { vertex: 2, sum: 0.5 },
+        DiagnosticKind::MixedPrimitives {
+            expected: GeometricPrimitive::Triangles,
+            found: GeometricPrimitive::Lines
+        },
+        DiagnosticKind::MissingBaseTarget(3),
+        DiagnosticKind::DuplicateMorphWeight(1),
+        DiagnosticKind::UnusedMorphWeight(2),
+        DiagnosticKind::MissingMaterial(0)
+    ]);
+    assert_eq!(diagnostics[1].to_string(), "error: index 3 is out of the 3 vertices of the mesh, \
+        in GeometryObject #0 / Mesh / IndexArray #0");
+    assert_eq!(diagnostics[8].path, vec!["GeometryNode \"node\"".to_string()]);
+    assert_eq!(diagnostics[10].severity(), Severity::Warning);
+}
+
+#[test]
+fn test_validate_animation() {
+    let source = r#"
+        Node
+        {
+            Translation %xlat {float[3] {{0, 0, 0}}}
+            Animation
+            {
+                Track (target = %xlat)
+                {
+                    Time {Key {float {0, 2, 1}}}
+                    Value {Key {float[3] {{0, 0, 0}, {1, 2, 3}, {0, 0, 0}}}}
+                }
+            }
+        }
+    "#;
+    let mut scene: Scene = source.parse().unwrap();
+    assert_eq!(scene.validate()[0].kind, DiagnosticKind::UnsortedKeyTimes);
+
+    // A track targeting a transformation of another node.
+    let foreign = std::sync::Arc::new(Transformation::Scale(Scale::X(1.0)));
+    if let Nodes::Node(ref mut node) = scene.nodes[0] {
+        node.animations[0].tracks[0].target = TrackTarget::Transformation(foreign);
+    }
+    let kinds: Vec<_> = scene.validate().into_iter().map(|d| d.kind).collect();
+    assert_eq!(kinds[0], DiagnosticKind::ForeignTrackTarget);
+    assert_eq!(kinds[1], DiagnosticKind::TrackValueCount { components: 1, keys: 3, values: 9 });
+}
@@ -0,0 +1,113 @@
+extern crate opengex;
+
+use opengex::structure::*;
+
+const SOURCE: &str = r#"
+    BoneNode $bone {}
+    GeometryObject
+    {
+        Mesh
+        {
+            VertexArray (attrib = "position")
+            {
+                float[3] {{0, 0, 0}, {1, 0, 0}, {0, 1, 0}, {1, 0, 0.001}, {0, 1, 0}, {1, 1, 0}}
+            }
+            VertexArray (attrib = "normal") {float[3] {{0, 0, 1}, {0, 0, 1}, {0, 0, 1},
+                {0, 0, 1}, {0, 0, 1}, {1, 0, 0}}}
+            VertexArray (attrib = "position", morph = 1)
+            {
+                float[3] {{0, 0, 1}, {1, 0, 1}, {0, 1, 1}, {1, 0, 1}, {0, 1, 1}, {1, 1, 1}}
+            }
+            Skin
+            {
+                Skeleton
+                {
+                    BoneRefArray {ref {$bone}}
+                    Transform {float[16] {{1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1}}}
+                }
+                BoneCountArray {unsigned_int8 {1, 1, 1, 1, 0, 1}}
+                BoneIndexArray {unsigned_int8 {0, 0, 0, 0, 0}}
+                BoneWeightArray {float {1, 1, 1, 1, 1}}
+            }
+        }
+    }
+"#;
+
+#[test]
+fn test_weld() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let mesh = &scene.geometry_objects[0].meshes[0];
+
+    // Vertex 3 matches vertex 1 within the tolerance, and vertex 4 has no bones unlike vertex 2.
+    let welded = mesh.weld(0.01);
+    assert_eq!(welded.index_arrays.len(), 1);
+    assert_eq!(welded.index_arrays[0].data, IndexData::U16(vec![0, 1, 2, 1, 3, 4]));
+    assert_eq!(welded.vertex_arrays[0].len(), 5);
+    assert_eq!(welded.vertex_arrays[0].data, VertexData::Float(vec![
+        0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0]));
+    assert_eq!(welded.vertex_arrays[2].len(), 5);
+    let skin = welded.skin.as_ref().unwrap();
+    assert_eq!(skin.bone_count_array, IndexData::U8(vec![1, 1, 1, 0, 1]));
+    assert_eq!(skin.bone_weight_array.len(), 4);
+
+    // Without a tolerance, vertex 3 stays apart, and existing indices are remapped.
+    let welded = mesh.weld(0.0);
+    assert_eq!(welded.vertex_arrays[0].len(), 6);
+    let welded = welded.weld(0.01);
+    assert_eq!(welded.index_arrays[0].data, IndexData::U16(vec![0, 1, 2, 1, 3, 4]));
+}
+
+#[test]
+fn test_unweld() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let mut mesh = scene.geometry_objects[0].meshes[0].weld(0.01);
+    mesh.index_arrays[0].restart = Some(255);
+    mesh.index_arrays[0].data = IndexData::U8(vec![0, 1, 255, 1, 4]);
+
+    let unwelded = mesh.unweld();
+    assert_eq!(unwelded.index_arrays[0].restart, Some(65535));
+    assert_eq!(unwelded.index_arrays[0].data, IndexData::U16(vec![0, 1, 65535, 2, 3]));
+    assert_eq!(unwelded.vertex_arrays[0].data, VertexData::Float(vec![
+        0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0]));
+    assert_eq!(unwelded.vertex_arrays[1].data, VertexData::Float(vec![
+        0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0]));
+    let skin = unwelded.skin.as_ref().unwrap();
+    assert_eq!(skin.bone_count_array, IndexData::U8(vec![1, 1, 1, 1]));
+
+    // Welding again gives back the shared vertices.
+    let welded = unwelded.weld(0.0);
+    assert_eq!(welded.vertex_arrays[0].len(), 3);
+    assert_eq!(welded.index_arrays[0].data, IndexData::U16(vec![0, 1, 65535, 1, 2]));
+}
+
+#[test]
+fn test_weld_out_of_range() {
+    let scene: Scene = SOURCE.parse().unwrap();
+    let mut mesh = scene.geometry_objects[0].meshes[0].clone();
+    mesh.index_arrays.push(IndexArray {
+        material: 0,
+        restart: None,
+        front_face: FrontFace::default(),
+        data: IndexData::U32(vec![0, 9, 1, 2, 3, 4, 5, 0, 1])
+    });
+
+    // A triangle with an index past the last vertex is left out, and the others are kept whole.
+    let welded = mesh.weld(0.01);
+    assert_eq!(welded.index_arrays[0].data, IndexData::U32(vec![2, 1, 3, 4, 0, 1]));
+    let unwelded = mesh.unweld();
+    assert_eq!(unwelded.vertex_arrays[0].len(), 6);
+    assert_eq!(unwelded.vertex_arrays[0].data, VertexData::Float(vec![
+        0.0, 1.0, 0.0, 1.0, 0.0, 0.001, 0.0, 1.0, 0.0,
+        1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]));
+    assert_eq!(unwelded.index_arrays[0].data, IndexData::U16(vec![0, 1, 2, 3, 4, 5]));
+
+    // A strip is left out up to the next restart index.
+    mesh.primitive = GeometricPrimitive::TriangleStrip;
+    mesh.index_arrays[0].restart = Some(0xFFFFFFFF);
+    mesh.index_arrays[0].data = IndexData::U32(vec![0, 1, 2, 3, 0xFFFFFFFF, 4, 9, 5, 0]);
+    let welded = mesh.weld(0.01);
+    assert_eq!(welded.index_arrays[0].data, IndexData::U32(vec![0, 1, 2, 1, 0xFFFFFFFF]));
+    let unwelded = mesh.unweld();
+    assert_eq!(unwelded.vertex_arrays[0].len(), 4);
+    assert_eq!(unwelded.index_arrays[0].data, IndexData::U16(vec![0, 1, 2, 3, 65535]));
+}
@@ -0,0 +1,240 @@
+extern crate opengex;
+
+use std::sync::Arc;
+use opengex::structure::*;
+
+#[test]
+fn test_write_cube() {
+    let scene: Scene = include_str!("assets/cube.ogex").parse().unwrap();
+    let source = scene.to_string_with(&Default::default()).unwrap();
+    assert!(source.contains("GeometryNode $node1"));
+    assert!(source.contains("ObjectRef {ref {$geometry1}}"));
+    assert!(source.contains("MaterialRef (index = 0) {ref {$material1}}"));
+
+    let written: Scene = source.parse().unwrap();
+    assert_eq!(written, scene);
+    match written.nodes[0] {
+        Nodes::GeometryNode(ref node) => {
+            assert!(Arc::ptr_eq(&node.geometry, &written.geometry_objects[0]));
+        }
+        _ => panic!("expected a geometry node")
+    }
+}
+
+#[test]
+fn test_write_round_trip() {
+    let source = r#"
+        BoneNode
+        {
+            Name {string {"Root \"bone\""}}
+            Rotation %r (kind = "z") {float {0}}
+            Animation (clip = 1, begin = 0.25)
+            {
+                Track (target = %r)
+                {
+                    Time (curve = "bezier")
+                    {
+                        Key {float {0, 1}}
+                        Key (kind = "-control") {float {0, 0.5}}
+                        Key (kind = "+control") {float {0.5, 1}}
+                    }
+                    Value (curve = "tcb")
+                    {
+                        Key {float {0, 1.5}}
+                        Key (kind = "tension") {float {0, 0.5}}
+                        Key (kind = "continuity") {float {0.1, 0}}
+                        Key (kind = "bias") {float {0, -0.5}}
+                    }
+                }
+            }
+        }
+        GeometryNode (visible = false)
+        {
+            ObjectRef {ref {$g}}
+            MorphWeight %w (index = 1) {float {0.5}}
+            Animation
+            {
+                Track (target = %w)
+                {
+                    Time {Key {float {0, 1}}}
+                    Value {Key {float {0, 1}}}
+                }
+            }
+        }
+        GeometryObject $g (shadow = false)
+        {
+            Mesh (lod = 1, primitive = "quads")
+            {
+                VertexArray (attrib = "position")
+                {
+                    double[3] {{0, 0, 0}, {1, 0, 0}, {1, 1, 0}, {0, 1, 0}}
+                }
+                VertexArray (attrib = "texcoord[1]", morph = 1)
+                {
+                    half[2] {{0, 0}, {1, 0}, {1, 1}, {0, 1}}
+                }
+                IndexArray (restart = 65535, front = "cw") {u16 {0, 1, 2, 3}}
+                Skin
+                {
+                    Skeleton
+                    {
+                        BoneRefArray {ref {$bone}}
+                        Transform {float[16] {{1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1}}}
+                    }
+                    BoneCountArray {u8 {1, 1, 1, 1}}
+                    BoneIndexArray {u8 {0, 0, 0, 0}}
+                    BoneWeightArray {float {1, 1, 1, 1}}
+                }
+            }
+            Morph (index = 1, base = 0) {Name {string {"smile"}}}
+        }
+        LightObject (type = "spot", shadow = false)
+        {
+            Color (attrib = "light") {float[4] {{1, 0.5, 0.25, 1}}}
+            Atten (kind = "angle", curve = "smooth") {Param (attrib = "end") {float {0x7F800000}}}
+        }
+        Material (two_sided = true)
+        {
+            Texture (attrib = "diffuse", texcoord = 1)
+            {
+                string {"a\tb.png"}
+                Scale %s (kind = "xyz") {float[3] {{1, 2, 3}}}
+                Animation
+                {
+                    Track (target = %s) {Time {Key {float {0}}} Value {Key {float[3] {{1, 1, 1}}}}}
+                }
+            }
+        }
+    "#.replace("BoneNode\n", "BoneNode $bone\n");
+    let scene: Scene = source.parse().unwrap();
+    let written: Scene = scene.to_string_with(&Default::default()).unwrap().parse().unwrap();
+    assert_eq!(written, scene);
+
+    let bone = match written.nodes[0] {
+        Nodes::BoneNode(ref bone) => bone,
+        _ => panic!("expected a bone node")
+    };
+    let skin = written.geometry_objects[0].meshes[1].skin.as_ref().unwrap();
+    assert!(Arc::ptr_eq(&skin.skeleton.bone_ref_array[0], bone));
+    match bone.animations[0].tracks[0].target {
+        TrackTarget::Transformation(ref t) => assert!(Arc::ptr_eq(t, &bone.transformations[0])),
+        _ => panic!("expected a transformation target")
+    }
+    assert!(written.light_objects[0].attenuations[0].params["end"].is_infinite());
+}
+
+#[test]
+fn test_write_unsigned_restart() {
+    // A restart index above the largest `i64` is written as an unsigned literal.
+    let scene: Scene = r#"
+        GeometryObject
+        {
+            Mesh
+            {
+                VertexArray (attrib = "position") {float[3] {{0, 0, 0}, {1, 0, 0}, {0, 1, 0}}}
+                IndexArray (restart = 0xFFFFFFFFFFFFFFFF) {u64 {0, 1, 2, 18446744073709551615}}
+            }
+        }
+    "#.parse().unwrap();
+    let source = scene.to_string_with(&Default::default()).unwrap();
+    assert!(source.contains("restart = 18446744073709551615"));
+    let written: Scene = source.parse().unwrap();
+    assert_eq!(written, scene);
+    assert_eq!(written.geometry_objects[0].meshes[0].index_arrays[0].restart, Some(u64::MAX));
+}
+
+#[test]
+fn test_write_shared_objects() {
+    // Objects missing from the scene lists are still written, once.
+    let material = Arc::new(Material {
+        two_sided: false,
+        name: None,
+        color: Default::default(),
+        param: Default::default(),
+        texture: Default::default()
+    });
+    let camera = Arc::new(CameraObject {
+        params: Default::default(),
+        colors: Default::default(),
+        textures: Default::default()
+    });
+    let node = |name: &str| Nodes::CameraNode(CameraNode {
+        name: Some(name.to_string()),
+        transformations: vec![],
+        animations: vec![],
+        children: vec![],
+        camera: camera.clone()
+    });
+    let mut scene = Scene::default();
+    scene.materials.push(material);
+    scene.nodes = vec![node("a"), node("b")];
+    let source = scene.to_string_with(&Default::default()).unwrap();
+    assert_eq!(source.matches("CameraObject $camera1").count(), 1);
+    assert_eq!(source.matches("ObjectRef {ref {$camera1}}").count(), 2);
+
+    let written: Scene = source.parse().unwrap();
+    assert_eq!(written.camera_objects.len(), 1);
+    assert_eq!(written.materials.len(), 1);
+    assert_eq!(written.nodes, scene.nodes);
+}
+
+#[test]
+fn test_write_options() {
+    use opengex::write::{ Indent, Options, Precision };
+
+    let scene: Scene = include_str!("assets/cube.ogex").parse().unwrap();
+    let options = Options {
+        indent: Indent::Spaces(2),
+        elements_per_line: 4,
+        precision: Precision::Fixed(3),
+        count_comments: true,
+        minify: false
+    };
+    let source = scene.to_string_with(&options).unwrap();
+    assert!(source.contains("\n  Mesh (primitive = \"triangles\")\n  {\n"));
+    assert!(source.contains("float[3]  // 24\n"));
+    assert!(source.contains("{1.000, 1.000, -1.000}, {1.000, -1.000, -1.000}, \
+        {-1.000, -1.000, -1.000}, {-1.000, 1.000, -1.000},\n"));
+    assert!(source.contains("Param (attrib = \"specular_power\") {float {50.000}}"));
+    assert_eq!(source.parse::<Scene>().unwrap().nodes.len(), 3);
+
+    let minified = scene.to_string_with(&Options { minify: true, ..Options::default() })
+        .unwrap();
+    assert!(!minified.contains(|c: char| c.is_whitespace()));
+    assert!(minified.starts_with("Metric(key=\"distance\"){float{1.0}}Metric(key=\"angle\")"));
+    assert_eq!(minified.parse::<Scene>().unwrap(), scene);
+
+    let wide = scene.to_string_with(&Options { elements_per_line: 0, ..Options::default() })
+        .unwrap();
+    assert_eq!(wide.parse::<Scene>().unwrap(), scene);
+    let default = scene.to_string_with(&Options::default()).unwrap();
+    assert!(wide.lines().count() < default.lines().count());
+}
+
+#[test]
+fn test_write_foreign_target() {
+    // A track targeting the transformation of another node cannot be written.
+    let mut scene: Scene = r#"
+        Node
+        {
+            Translation %t {float[3] {{0, 0, 0}}}
+            Animation
+            {
+                Track (target = %t) {Time {Key {float {0}}} Value {Key {float[3] {{1, 1, 1}}}}}
+            }
+        }
+        Node {}
+    "#.parse().unwrap();
+    let animations = match scene.nodes[0] {
+        Nodes::Node(ref mut node) => std::mem::take(&mut node.animations),
+        _ => panic!("expected a node")
+    };
+    match scene.nodes[1] {
+        Nodes::Node(ref mut node) => node.animations = animations,
+        _ => panic!("expected a node")
+    }
+    let err = scene.to_string_with(&Default::default()).unwrap_err();
+    assert_eq!(err.to_string(), "an animation track of node $node2 targets a transformation or \
+        morph weight that does not belong to it");
+    assert!(scene.to_writer(vec![]).is_err());
+}